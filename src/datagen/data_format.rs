@@ -0,0 +1,126 @@
+﻿use cozy_chess::{Board, Color};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+// Binary format, 32 bytes per position, everything little endian:
+//   0..8    occupancy bitboard (u64), bit n set when square n (a1 = 0, h8 = 63) has a piece
+//   8..24   one nibble per occupied square in ascending square order, low nibble first:
+//           color << 3 | piece, with white = 0, black = 1 and pawn, knight, bishop, rook, queen,
+//           king = 0..5. Unused nibbles are 0.
+//   24..26  search score in centipawns from white's point of view (i16)
+//   26      game result from white's point of view: 0 loss, 1 draw, 2 win
+//   27      side to move: 0 white, 1 black
+//   28      halfmove clock
+//   29      reserved, 0
+//   30..32  fullmove number (u16)
+// Castling rights and the en passant square are not stored, the positions are only used for
+// evaluation.
+pub const DATA_ENTRY_SIZE: usize = 32;
+pub const BINARY_FILE_EXTENSION: &str = "bin";
+
+// Text format, one position per line: "<fen> | <score> | <result>" with the same white relative
+// score and the result as 1.0, 0.5 or 0.0.
+pub const TEXT_FILE_EXTENSION: &str = "txt";
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum GameResult {
+    BlackWin = 0,
+    Draw = 1,
+    WhiteWin = 2,
+}
+
+impl GameResult {
+    pub fn as_text(self) -> &'static str {
+        match self {
+            GameResult::BlackWin => "0.0",
+            GameResult::Draw => "0.5",
+            GameResult::WhiteWin => "1.0",
+        }
+    }
+}
+
+pub struct DataEntry {
+    pub board: Board,
+    pub score: i16,
+    pub result: GameResult,
+}
+
+pub fn encode_data_entry(entry: &DataEntry) -> [u8; DATA_ENTRY_SIZE] {
+    let board = &entry.board;
+    let mut bytes = [0u8; DATA_ENTRY_SIZE];
+    bytes[0..8].copy_from_slice(&board.occupied().0.to_le_bytes());
+
+    for (i, square) in board.occupied().into_iter().enumerate() {
+        let color = board.color_on(square).unwrap() as u8;
+        let piece = board.piece_on(square).unwrap() as u8;
+        bytes[8 + i / 2] |= (color << 3 | piece) << (4 * (i % 2));
+    }
+
+    bytes[24..26].copy_from_slice(&entry.score.to_le_bytes());
+    bytes[26] = entry.result as u8;
+    bytes[27] = (board.side_to_move() == Color::Black) as u8;
+    bytes[28] = board.halfmove_clock();
+    bytes[30..32].copy_from_slice(&board.fullmove_number().to_le_bytes());
+    bytes
+}
+
+pub fn encode_text_entry(entry: &DataEntry) -> String {
+    format!("{} | {} | {}", entry.board, entry.score, entry.result.as_text())
+}
+
+// Writes both formats and starts a new pair of files every `shard_size` positions:
+// <name>_0000.bin, <name>_0000.txt, <name>_0001.bin, ...
+pub struct DataWriter {
+    directory: PathBuf,
+    name: String,
+    shard_size: u64,
+    shard: u32,
+    shard_positions: u64,
+    binary: BufWriter<File>,
+    text: BufWriter<File>,
+}
+
+impl DataWriter {
+    pub fn new(directory: &Path, name: &str, shard_size: u64) -> io::Result<Self> {
+        let (binary, text) = open_shard(directory, name, 0)?;
+
+        Ok(DataWriter {
+            directory: directory.to_path_buf(),
+            name: name.to_string(),
+            shard_size,
+            shard: 0,
+            shard_positions: 0,
+            binary,
+            text,
+        })
+    }
+
+    pub fn write(&mut self, entry: &DataEntry) -> io::Result<()> {
+        if self.shard_positions >= self.shard_size {
+            self.flush()?;
+            self.shard += 1;
+            self.shard_positions = 0;
+            (self.binary, self.text) = open_shard(&self.directory, &self.name, self.shard)?;
+        }
+
+        self.binary.write_all(&encode_data_entry(entry))?;
+        writeln!(self.text, "{}", encode_text_entry(entry))?;
+        self.shard_positions += 1;
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.binary.flush()?;
+        self.text.flush()
+    }
+}
+
+fn open_shard(directory: &Path, name: &str, shard: u32) -> io::Result<(BufWriter<File>, BufWriter<File>)> {
+    let path = |extension: &str| directory.join(format!("{name}_{shard:04}.{extension}"));
+
+    Ok((
+        BufWriter::new(File::create(path(BINARY_FILE_EXTENSION))?),
+        BufWriter::new(File::create(path(TEXT_FILE_EXTENSION))?),
+    ))
+}
@@ -0,0 +1,293 @@
+﻿use crate::datagen::data_format::{DataEntry, DataWriter, GameResult};
+use crate::eval::classical_evaluator::ClassicalEvaluator;
+use crate::eval::eval_hash_table::{EvalHashTable, EVAL_HASH_TABLE_SIZE};
+use crate::eval::eval_is_mate::is_mate_score;
+use crate::eval::eval_params::EvalParams;
+use crate::search::is_draw::is_draw;
+use crate::search::mini_max::mini_max;
+use crate::search::search_thread::SearchThread;
+use crate::util::options::parse_option_pairs;
+use crate::util::random::xorshift64;
+use crate::search::transposition_table::TranspositionTable;
+use cozy_chess::{Board, Color, GameStatus, Move, Piece};
+use std::fs;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+
+// Openings that are already this lopsided after the random moves are thrown away.
+const MAX_OPENING_SCORE: i32 = 1000;
+const PROGRESS_INTERVAL: u64 = 10;
+
+pub struct DatagenOptions {
+    pub output_path: String,
+    pub games: u64,
+    pub threads: usize,
+    pub depth: u8,
+    pub nodes: u64, // Node limit per move, 0 searches the full depth
+    pub random_plies: usize, // Uniformly random moves played from the start position
+    pub shard_size: u64, // Positions per output file
+    pub hash_size: usize,
+    pub max_game_plies: usize, // Longer games are adjudicated as draws, counted after the opening
+
+    // A game is won once the score stays above win_score for win_plies plies in a row.
+    pub win_score: i32,
+    pub win_plies: u32,
+    // A game is drawn once the score stays within draw_score of 0 for draw_plies plies in a row,
+    // but not before draw_after plies after the opening.
+    pub draw_score: i32,
+    pub draw_plies: u32,
+    pub draw_after: usize,
+
+    pub seed: u64,
+    pub eval_params: Arc<EvalParams>, // Set with the --eval-params flag
+}
+
+impl DatagenOptions {
+    // datagen <output path> [games N] [threads N] [depth N] [nodes N] [random-plies N]
+    //         [shard-size N] [hash N] [max-plies N] [win-score CP] [win-plies N]
+    //         [draw-score CP] [draw-plies N] [draw-after N] [seed N]
+    pub fn from_args(args: &[String]) -> Result<Self, String> {
+        let mut options = DatagenOptions {
+            output_path: args.first().cloned().unwrap_or_else(|| "data".to_string()),
+            games: 100,
+            threads: 1,
+            depth: 8,
+            nodes: 5000,
+            random_plies: 8,
+            shard_size: 1_000_000,
+            hash_size: 1 << 18,
+            max_game_plies: 400,
+            win_score: 2000,
+            win_plies: 4,
+            draw_score: 10,
+            draw_plies: 10,
+            draw_after: 80,
+            seed: 0x2545_F491_4F6C_DD1D,
+            eval_params: Arc::new(EvalParams::default()),
+        };
+
+        parse_option_pairs(args.get(1..).unwrap_or_default(), |name, value| {
+            let number = || value.parse::<u64>().map_err(|_| ());
+
+            match name {
+                "games" => options.games = number()?,
+                "threads" => options.threads = number()?.max(1) as usize,
+                "depth" => options.depth = number()?.clamp(1, 64) as u8,
+                "nodes" => options.nodes = number()?,
+                "random-plies" => options.random_plies = number()? as usize,
+                "shard-size" => options.shard_size = number()?.max(1),
+                "hash" => options.hash_size = number()?.max(1) as usize,
+                "max-plies" => options.max_game_plies = number()? as usize,
+                "win-score" => options.win_score = number()? as i32,
+                "win-plies" => options.win_plies = number()? as u32,
+                "draw-score" => options.draw_score = number()? as i32,
+                "draw-plies" => options.draw_plies = number()? as u32,
+                "draw-after" => options.draw_after = number()? as usize,
+                "seed" => options.seed = number()?,
+                _ => return Ok(false),
+            }
+            Ok(true)
+        })?;
+
+        Ok(options)
+    }
+}
+
+// Plays options.games games spread over options.threads threads, every thread writes its own
+// shards named thread<N>_<shard>.
+pub fn run_datagen(options: &DatagenOptions) -> std::io::Result<()> {
+    fs::create_dir_all(&options.output_path)?;
+
+    let games_started = AtomicU64::new(0);
+    let games_finished = AtomicU64::new(0);
+    let positions = AtomicU64::new(0);
+    let start = Instant::now();
+    // Shared by all threads, the openings of different games often transpose.
+    let eval_hash_table = Arc::new(EvalHashTable::new(EVAL_HASH_TABLE_SIZE));
+
+    std::thread::scope(|scope| {
+        let handles: Vec<_> = (0..options.threads)
+            .map(|thread_index| {
+                let (games_started, games_finished, positions) = (&games_started, &games_finished, &positions);
+                let eval_hash_table = &eval_hash_table;
+
+                scope.spawn(move || -> std::io::Result<()> {
+                    let mut writer = DataWriter::new(
+                        Path::new(&options.output_path),
+                        &format!("thread{thread_index}"),
+                        options.shard_size,
+                    )?;
+                    let mut random_state = options.seed ^ (thread_index as u64 + 1).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+
+                    while games_started.fetch_add(1, Ordering::SeqCst) < options.games {
+                        let (entries, result) = play_game(options, eval_hash_table, &mut random_state);
+                        for (board, score) in entries {
+                            writer.write(&DataEntry { board, score, result })?;
+                            positions.fetch_add(1, Ordering::SeqCst);
+                        }
+
+                        let finished = games_finished.fetch_add(1, Ordering::SeqCst) + 1;
+                        if finished % PROGRESS_INTERVAL == 0 || finished == options.games {
+                            println!(
+                                "info string datagen games {finished}/{} positions {} time {}ms",
+                                options.games,
+                                positions.load(Ordering::SeqCst),
+                                start.elapsed().as_millis()
+                            );
+                        }
+                    }
+
+                    writer.flush()
+                })
+            })
+            .collect();
+
+        handles.into_iter().try_for_each(|handle| handle.join().unwrap())
+    })
+}
+
+// Returns the recorded positions with their white relative scores and the result of the game.
+fn play_game(
+    options: &DatagenOptions,
+    eval_hash_table: &Arc<EvalHashTable>,
+    random_state: &mut u64,
+) -> (Vec<(Board, i16)>, GameResult) {
+    let is_playing = Arc::new(AtomicBool::new(true));
+
+    'game: loop {
+        let Some(mut board) = random_opening(options.random_plies, random_state) else {
+            continue;
+        };
+
+        let mut transposition_table = TranspositionTable::new(options.hash_size);
+        let evaluator = ClassicalEvaluator::new(options.eval_params.clone());
+        let mut thread = SearchThread::new(is_playing.clone(), &[board.hash()], evaluator);
+        thread.eval_hash_table = Some(eval_hash_table.clone());
+        let mut hash_history = vec![board.hash()];
+        let mut entries = Vec::new();
+        let mut win_plies = 0;
+        let mut draw_plies = 0;
+
+        let result = loop {
+            match board.status() {
+                GameStatus::Won if board.side_to_move() == Color::White => break GameResult::BlackWin,
+                GameStatus::Won => break GameResult::WhiteWin,
+                GameStatus::Drawn => break GameResult::Draw,
+                GameStatus::Ongoing => {}
+            }
+            if is_draw(&board, &hash_history, 0) || hash_history.len() > options.max_game_plies {
+                break GameResult::Draw;
+            }
+
+            thread.hash_history.clone_from(&hash_history);
+            let Some((score, mv)) = search(&board, &mut thread, &mut transposition_table, options) else {
+                break GameResult::Draw;
+            };
+
+            if hash_history.len() == 1 && score.abs() > MAX_OPENING_SCORE {
+                continue 'game;
+            }
+
+            // Only quiet positions are recorded, the score of a position in the middle of a
+            // capture sequence says little about the position itself.
+            if is_quiet(&board, mv) && !is_mate_score(score) {
+                entries.push((board.clone(), score.clamp(i16::MIN as i32, i16::MAX as i32) as i16));
+            }
+
+            win_plies = if score.abs() >= options.win_score { win_plies + 1 } else { 0 };
+            if win_plies >= options.win_plies {
+                break if score > 0 { GameResult::WhiteWin } else { GameResult::BlackWin };
+            }
+
+            draw_plies = if hash_history.len() > options.draw_after && score.abs() <= options.draw_score {
+                draw_plies + 1
+            } else {
+                0
+            };
+            if draw_plies >= options.draw_plies {
+                break GameResult::Draw;
+            }
+
+            board.play_unchecked(mv);
+            hash_history.push(board.hash());
+        };
+
+        return (entries, result);
+    }
+}
+
+// Plays `plies` uniformly random legal moves from the start position, None if the game ended on
+// the way.
+fn random_opening(plies: usize, random_state: &mut u64) -> Option<Board> {
+    let mut board = Board::default();
+
+    for _ in 0..plies {
+        let mut moves: Vec<Move> = Vec::new();
+        board.generate_moves(|mvs| {
+            moves.extend(mvs);
+            false
+        });
+        if moves.is_empty() {
+            return None;
+        }
+
+        *random_state = xorshift64(*random_state);
+        board.play_unchecked(moves[(*random_state % moves.len() as u64) as usize]);
+    }
+
+    (board.status() == GameStatus::Ongoing).then_some(board)
+}
+
+// Iterative deepening up to the depth limit, stopping early at the node limit. The first
+// iteration always completes so there is a move to play.
+fn search(
+    board: &Board,
+    thread: &mut SearchThread<ClassicalEvaluator>,
+    transposition_table: &mut TranspositionTable,
+    options: &DatagenOptions,
+) -> Option<(i32, Move)> {
+    thread.node_count = 0;
+    thread.node_limit = u64::MAX;
+    let mut best = None;
+
+    for depth in 1..=options.depth {
+        thread.clear_killers();
+        let (score, mv, early_stop) = mini_max(
+            board,
+            thread,
+            transposition_table,
+            depth,
+            i32::MIN,
+            i32::MAX,
+            0,
+        );
+
+        if early_stop {
+            break;
+        }
+        if let Some(mv) = mv {
+            best = Some((score, mv));
+        }
+
+        if options.nodes > 0 {
+            thread.node_limit = options.nodes;
+            if thread.node_count >= options.nodes {
+                break;
+            }
+        }
+    }
+
+    best
+}
+
+fn is_quiet(board: &Board, best_move: Move) -> bool {
+    let is_en_passant = board.piece_on(best_move.from) == Some(Piece::Pawn)
+        && best_move.from.file() != best_move.to.file();
+
+    board.checkers().is_empty()
+        && board.color_on(best_move.to) != Some(!board.side_to_move())
+        && !is_en_passant
+        && best_move.promotion.is_none()
+}
@@ -0,0 +1,3 @@
+﻿pub mod data_format;
+#[allow(clippy::module_inception)]
+pub mod datagen;
\ No newline at end of file
@@ -0,0 +1,39 @@
+﻿use crate::eval::eval_pawn_structure::{A_FILE, H_FILE};
+use cozy_chess::{
+    get_bishop_moves, get_king_moves, get_knight_moves, get_rook_moves, BitBoard, Board, Color,
+    Piece,
+};
+
+// All squares attacked by the pawns of `color`.
+pub fn get_pawn_attacks_bb(board: &Board, color: Color) -> BitBoard {
+    let pawns = board.colored_pieces(color, Piece::Pawn).0;
+
+    let attacks = if color == Color::White {
+        ((pawns & !A_FILE) << 7) | ((pawns & !H_FILE) << 9)
+    } else {
+        ((pawns & !A_FILE) >> 9) | ((pawns & !H_FILE) >> 7)
+    };
+
+    BitBoard(attacks)
+}
+
+// All squares attacked by the pieces of `color`, including the king.
+pub fn get_attacks_bb(board: &Board, color: Color) -> BitBoard {
+    let occupied = board.occupied();
+    let mut attacks = get_pawn_attacks_bb(board, color) | get_king_moves(board.king(color));
+
+    for knight in board.colored_pieces(color, Piece::Knight) {
+        attacks |= get_knight_moves(knight);
+    }
+    for bishop in board.colored_pieces(color, Piece::Bishop) {
+        attacks |= get_bishop_moves(bishop, occupied);
+    }
+    for rook in board.colored_pieces(color, Piece::Rook) {
+        attacks |= get_rook_moves(rook, occupied);
+    }
+    for queen in board.colored_pieces(color, Piece::Queen) {
+        attacks |= get_bishop_moves(queen, occupied) | get_rook_moves(queen, occupied);
+    }
+
+    attacks
+}
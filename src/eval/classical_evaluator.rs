@@ -0,0 +1,58 @@
+﻿use crate::eval::eval::{evaluate, evaluate_lazy};
+use crate::eval::eval_params::EvalParams;
+use crate::eval::evaluator::{Evaluator, EvaluatorKind};
+use crate::eval::material_hash_table::{MaterialHashTable, MATERIAL_HASH_TABLE_SIZE};
+use crate::eval::pawn_hash_table::{PawnHashTable, PAWN_HASH_TABLE_SIZE};
+use cozy_chess::Board;
+use std::sync::Arc;
+
+// The handcrafted evaluation, with its own pawn and material hash tables.
+pub struct ClassicalEvaluator {
+    params: Arc<EvalParams>,
+    pawn_hash_table: PawnHashTable,
+    material_hash_table: MaterialHashTable,
+}
+
+impl ClassicalEvaluator {
+    pub fn new(params: Arc<EvalParams>) -> Self {
+        ClassicalEvaluator {
+            params,
+            pawn_hash_table: PawnHashTable::new(PAWN_HASH_TABLE_SIZE),
+            material_hash_table: MaterialHashTable::new(MATERIAL_HASH_TABLE_SIZE),
+        }
+    }
+}
+
+impl Default for ClassicalEvaluator {
+    fn default() -> Self {
+        ClassicalEvaluator::new(Arc::new(EvalParams::default()))
+    }
+}
+
+impl Evaluator for ClassicalEvaluator {
+    fn name(&self) -> &'static str {
+        EvaluatorKind::Classical.name()
+    }
+
+    fn evaluate(&mut self, board: &Board, distance_from_root: u8) -> i32 {
+        evaluate(
+            board,
+            &self.params,
+            &mut self.pawn_hash_table,
+            &mut self.material_hash_table,
+            distance_from_root,
+        )
+    }
+
+    fn evaluate_lazy(&mut self, board: &Board, distance_from_root: u8, alpha: i32, beta: i32) -> i32 {
+        evaluate_lazy(
+            board,
+            &self.params,
+            &mut self.pawn_hash_table,
+            &mut self.material_hash_table,
+            distance_from_root,
+            alpha,
+            beta,
+        )
+    }
+}
@@ -0,0 +1,381 @@
+﻿use crate::eval::eval_count_material::PIECE_SCORES;
+use crate::eval::eval_pawn_structure::{square_distance, ARR_FILES};
+use crate::eval::kpk_bitbase::probe_kpk;
+use cozy_chess::{BitBoard, Board, Color, File, Piece, Rank, Square};
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+// Scale factors are applied to the endgame half of the score, a factor of SCALE_FACTOR_NORMAL
+// leaves it as it is and 0 makes the position a dead draw.
+pub const SCALE_FACTOR_NORMAL: i32 = 64;
+pub const SCALE_FACTOR_DRAW: i32 = 0;
+
+// Added to the score of endgames that are won with correct play, large enough that the search
+// always goes for them but far away from the mate scores.
+pub const KNOWN_WIN: i32 = 10_000;
+
+const PUSH_TO_EDGE_WEIGHT: i32 = 20;
+const PUSH_CLOSE_WEIGHT: i32 = 10;
+const PUSH_TO_CORNER_WEIGHT: i32 = 30;
+const PUSH_PAWN_WEIGHT: i32 = 10;
+
+type EndgameEval = fn(&Board, Color) -> i32;
+type EndgameScale = fn(&Board, Color) -> i32;
+
+// A specialised evaluator gives the full score of the position, a scale factor only corrects the
+// score of the general evaluation. Both are called with the side that is expected to win.
+#[derive(Clone, Copy)]
+enum EndgameKind {
+    Evaluation(EndgameEval),
+    Scale(EndgameScale),
+}
+
+#[derive(Clone, Copy)]
+struct Endgame {
+    strong_side: Color,
+    kind: EndgameKind,
+}
+
+static ENDGAMES: OnceLock<HashMap<u64, Endgame>> = OnceLock::new();
+
+fn endgames() -> &'static HashMap<u64, Endgame> {
+    ENDGAMES.get_or_init(|| {
+        let mut endgames = HashMap::new();
+        add_endgame(&mut endgames, "KPvK", EndgameKind::Evaluation(eval_kpk));
+        add_endgame(&mut endgames, "KBNvK", EndgameKind::Evaluation(eval_kbnk));
+        add_endgame(&mut endgames, "KQvKR", EndgameKind::Evaluation(eval_kqkr));
+        add_endgame(&mut endgames, "KRvKP", EndgameKind::Evaluation(eval_krkp));
+        add_endgame(&mut endgames, "KRvKB", EndgameKind::Scale(scale_krkb));
+        add_endgame(&mut endgames, "KRvKN", EndgameKind::Scale(scale_krkn));
+        add_endgame(&mut endgames, "KNNvK", EndgameKind::Scale(scale_draw));
+        endgames
+    })
+}
+
+// Registers the endgame for both colours, `code` lists the pieces of the strong side first.
+fn add_endgame(endgames: &mut HashMap<u64, Endgame>, code: &str, kind: EndgameKind) {
+    for strong_side in [Color::White, Color::Black] {
+        let key = get_material_key_from_code(code, strong_side);
+        endgames.insert(key, Endgame { strong_side, kind });
+    }
+}
+
+// Material signature of a position, the number of pieces of every type and colour packed into
+// four bits each.
+pub fn get_material_key(board: &Board) -> u64 {
+    let mut key = 0;
+
+    for color in [Color::White, Color::Black] {
+        for piece in &Piece::ALL[..5] {
+            let count = board.colored_pieces(color, *piece).len() as u64;
+            key |= count << material_key_shift(color, *piece);
+        }
+    }
+
+    key
+}
+
+// Material key for a code like "KBNvK" with the pieces before the 'v' belonging to `strong_side`.
+fn get_material_key_from_code(code: &str, strong_side: Color) -> u64 {
+    let (strong, weak) = code.split_once('v').unwrap();
+    let mut key = 0;
+
+    for (pieces, color) in [(strong, strong_side), (weak, !strong_side)] {
+        for piece_char in pieces.chars().filter(|&piece_char| piece_char != 'K') {
+            let piece = Piece::try_from(piece_char.to_ascii_lowercase()).unwrap();
+            key += 1 << material_key_shift(color, piece);
+        }
+    }
+
+    key
+}
+
+fn material_key_shift(color: Color, piece: Piece) -> u64 {
+    (color as u64 * 5 + piece as u64) * 4
+}
+
+// White relative score of the position if a specialised evaluator knows this endgame.
+pub fn probe_endgame_eval(board: &Board) -> Option<i32> {
+    let (strong_side, score) = match endgames().get(&get_material_key(board)) {
+        Some(Endgame {
+            strong_side,
+            kind: EndgameKind::Evaluation(eval_endgame),
+        }) => (*strong_side, eval_endgame(board, *strong_side)),
+        _ => {
+            let strong_side = [Color::White, Color::Black]
+                .into_iter()
+                .find(|&color| is_kxk(board, color))?;
+            (strong_side, eval_kxk(board, strong_side))
+        }
+    };
+
+    Some(if strong_side == Color::White { score } else { -score })
+}
+
+// Scale factor for the endgame score, `eg_score` is the white relative endgame half of the
+// general evaluation and decides which side is trying to win.
+pub fn get_scale_factor(board: &Board, eg_score: i32) -> i32 {
+    let strong_side = if eg_score > 0 { Color::White } else { Color::Black };
+
+    if let Some(Endgame {
+        strong_side: endgame_strong_side,
+        kind: EndgameKind::Scale(scale_endgame),
+    }) = endgames().get(&get_material_key(board))
+    {
+        if *endgame_strong_side == strong_side {
+            return scale_endgame(board, strong_side);
+        }
+    }
+
+    if let Some(scale_factor) = scale_wrong_rook_pawn(board, strong_side) {
+        return scale_factor;
+    }
+    if let Some(scale_factor) = scale_opposite_bishops(board) {
+        return scale_factor;
+    }
+    if let Some(scale_factor) = scale_no_pawns(board, strong_side) {
+        return scale_factor;
+    }
+
+    SCALE_FACTOR_NORMAL
+}
+
+// KXK: a bare king against enough material to mate it.
+fn is_kxk(board: &Board, strong_side: Color) -> bool {
+    let weak_side = !strong_side;
+    if board.colors(weak_side) != board.king(weak_side).bitboard() {
+        return false;
+    }
+
+    let count = |piece| board.colored_pieces(strong_side, piece).len();
+    let bishops = board.colored_pieces(strong_side, Piece::Bishop);
+    let has_bishop_pair = !(bishops & BitBoard::DARK_SQUARES).is_empty()
+        && !(bishops & BitBoard::LIGHT_SQUARES).is_empty();
+
+    count(Piece::Queen) > 0
+        || count(Piece::Rook) > 0
+        || has_bishop_pair
+        || (count(Piece::Bishop) > 0 && count(Piece::Knight) > 0)
+}
+
+// Drive the weak king to the edge and bring the strong king closer to help mating it.
+// (see: https://www.chessprogramming.org/Mop-up_Evaluation)
+fn eval_kxk(board: &Board, strong_side: Color) -> i32 {
+    let strong_king = board.king(strong_side);
+    let weak_king = board.king(!strong_side);
+
+    KNOWN_WIN
+        + non_king_material(board, strong_side)
+        + push_to_edge(weak_king)
+        + push_close(strong_king, weak_king)
+}
+
+// KPK: exact win or draw from the bitbase, wins prefer the pawn further up the board.
+fn eval_kpk(board: &Board, strong_side: Color) -> i32 {
+    if !probe_kpk(board) {
+        return 0;
+    }
+
+    let pawn = board.colored_pieces(strong_side, Piece::Pawn).next_square().unwrap();
+    let rank = pawn.rank().relative_to(strong_side) as i32;
+
+    KNOWN_WIN + PIECE_SCORES[Piece::Pawn as usize].eg() + rank * PUSH_PAWN_WEIGHT
+}
+
+// KBNK: the king can only be mated in a corner the bishop controls, so drive it there.
+fn eval_kbnk(board: &Board, strong_side: Color) -> i32 {
+    let strong_king = board.king(strong_side);
+    let weak_king = board.king(!strong_side);
+    let bishop = board.colored_pieces(strong_side, Piece::Bishop).next_square().unwrap();
+
+    let corners = if BitBoard::DARK_SQUARES.has(bishop) {
+        [Square::A1, Square::H8]
+    } else {
+        [Square::A8, Square::H1]
+    };
+    let corner_distance = corners
+        .iter()
+        .map(|&corner| square_distance(weak_king, corner))
+        .min()
+        .unwrap();
+
+    KNOWN_WIN
+        + non_king_material(board, strong_side)
+        + (7 - corner_distance) * PUSH_TO_CORNER_WEIGHT
+        + push_close(strong_king, weak_king)
+}
+
+// KQKR: a win, but the rook can resist for a long time unless the king gets pushed to the edge.
+fn eval_kqkr(board: &Board, strong_side: Color) -> i32 {
+    let strong_king = board.king(strong_side);
+    let weak_king = board.king(!strong_side);
+
+    PIECE_SCORES[Piece::Queen as usize].eg() - PIECE_SCORES[Piece::Rook as usize].eg()
+        + push_to_edge(weak_king)
+        + push_close(strong_king, weak_king)
+}
+
+// KRKP: a win when the strong king stops the pawn or the weak king is too far away, otherwise
+// drawish depending on how far the pawn got with its king's help.
+fn eval_krkp(board: &Board, strong_side: Color) -> i32 {
+    let weak_side = !strong_side;
+    // Flip the board for black so the weak pawn always runs towards the first rank.
+    let relative = |square: Square| square.relative_to(strong_side);
+    let strong_king = relative(board.king(strong_side));
+    let weak_king = relative(board.king(weak_side));
+    let rook = relative(board.colored_pieces(strong_side, Piece::Rook).next_square().unwrap());
+    let pawn = relative(board.colored_pieces(weak_side, Piece::Pawn).next_square().unwrap());
+    let queening_square = Square::new(pawn.file(), Rank::First);
+    let pawn_stop = pawn.offset(0, -1);
+    let rook_value = PIECE_SCORES[Piece::Rook as usize].eg();
+
+    let strong_king_in_front = strong_king.file() == pawn.file() && strong_king.rank() < pawn.rank();
+    let weak_to_move = (board.side_to_move() == weak_side) as i32;
+    let strong_to_move = (board.side_to_move() == strong_side) as i32;
+
+    let weak_king_too_far = square_distance(weak_king, pawn) >= 3 + weak_to_move
+        && square_distance(weak_king, rook) >= 3;
+
+    if strong_king_in_front || weak_king_too_far {
+        rook_value - square_distance(strong_king, pawn)
+    } else if weak_king.rank() <= Rank::Third
+        && square_distance(weak_king, pawn) == 1
+        && strong_king.rank() >= Rank::Fourth
+        && square_distance(strong_king, pawn) > 2 + strong_to_move
+    {
+        80 - 8 * square_distance(strong_king, pawn)
+    } else {
+        200 - 8
+            * (square_distance(strong_king, pawn_stop)
+                - square_distance(weak_king, pawn_stop)
+                - square_distance(pawn, queening_square))
+    }
+}
+
+// Endgames that can't be won without help from the defender, like KNNK.
+fn scale_draw(_board: &Board, _strong_side: Color) -> i32 {
+    SCALE_FACTOR_DRAW
+}
+
+// KRKB is usually a draw, the defender just has to avoid the wrong corner.
+fn scale_krkb(_board: &Board, _strong_side: Color) -> i32 {
+    8
+}
+
+// KRKN is a draw as long as the knight stays close to its king.
+fn scale_krkn(board: &Board, strong_side: Color) -> i32 {
+    let weak_side = !strong_side;
+    let knight = board.colored_pieces(weak_side, Piece::Knight).next_square().unwrap();
+
+    if square_distance(knight, board.king(weak_side)) <= 2 {
+        8
+    } else {
+        24
+    }
+}
+
+// Bishop and rook pawns where the bishop doesn't control the promotion square can't win once the
+// defending king reaches the corner.
+fn scale_wrong_rook_pawn(board: &Board, strong_side: Color) -> Option<i32> {
+    let weak_side = !strong_side;
+    let pawns = board.colored_pieces(strong_side, Piece::Pawn);
+    let bishops = board.colored_pieces(strong_side, Piece::Bishop);
+    let others = board.colors(strong_side) & !pawns & !bishops & !board.pieces(Piece::King);
+    let weak_pieces = board.colors(weak_side) & !board.pieces(Piece::Pawn) & !board.pieces(Piece::King);
+
+    if pawns.is_empty() || bishops.is_empty() || !others.is_empty() || !weak_pieces.is_empty() {
+        return None;
+    }
+
+    let file = [File::A, File::H]
+        .into_iter()
+        .find(|&file| pawns.is_subset(BitBoard(ARR_FILES[file as usize])))?;
+    let promotion_square = Square::new(file, Rank::Eighth.relative_to(strong_side));
+    let promotion_color = if BitBoard::DARK_SQUARES.has(promotion_square) {
+        BitBoard::DARK_SQUARES
+    } else {
+        BitBoard::LIGHT_SQUARES
+    };
+
+    if bishops.is_disjoint(promotion_color)
+        && square_distance(board.king(weak_side), promotion_square) <= 1
+    {
+        Some(SCALE_FACTOR_DRAW)
+    } else {
+        None
+    }
+}
+
+// Bishops of opposite colours are very drawish, even more so without other pieces on the board.
+fn scale_opposite_bishops(board: &Board) -> Option<i32> {
+    let white_bishops = board.colored_pieces(Color::White, Piece::Bishop);
+    let black_bishops = board.colored_pieces(Color::Black, Piece::Bishop);
+    if white_bishops.len() != 1 || black_bishops.len() != 1 {
+        return None;
+    }
+
+    let white_on_dark = !white_bishops.is_disjoint(BitBoard::DARK_SQUARES);
+    let black_on_dark = !black_bishops.is_disjoint(BitBoard::DARK_SQUARES);
+    if white_on_dark == black_on_dark {
+        return None;
+    }
+
+    let only_bishops = (board.pieces(Piece::Knight)
+        | board.pieces(Piece::Rook)
+        | board.pieces(Piece::Queen))
+    .is_empty();
+
+    Some(if only_bishops { 16 } else { 46 })
+}
+
+// Without pawns a side needs more than a minor piece up to be able to win.
+fn scale_no_pawns(board: &Board, strong_side: Color) -> Option<i32> {
+    if !board.colored_pieces(strong_side, Piece::Pawn).is_empty() {
+        return None;
+    }
+
+    let strong_material = non_pawn_material(board, strong_side);
+    let weak_material = non_pawn_material(board, !strong_side);
+    let bishop_value = PIECE_SCORES[Piece::Bishop as usize].eg();
+    let rook_value = PIECE_SCORES[Piece::Rook as usize].eg();
+
+    if strong_material - weak_material > bishop_value {
+        None
+    } else if strong_material < rook_value {
+        Some(SCALE_FACTOR_DRAW)
+    } else if weak_material <= bishop_value {
+        Some(4)
+    } else {
+        Some(14)
+    }
+}
+
+fn non_pawn_material(board: &Board, color: Color) -> i32 {
+    non_king_material(board, color)
+        - board.colored_pieces(color, Piece::Pawn).len() as i32 * PIECE_SCORES[0].eg()
+}
+
+fn non_king_material(board: &Board, color: Color) -> i32 {
+    PIECE_SCORES
+        .iter()
+        .enumerate()
+        .map(|(index, score)| board.colored_pieces(color, Piece::index(index)).len() as i32 * score.eg())
+        .sum()
+}
+
+// Grows with the Manhattan distance of the king from the centre.
+fn push_to_edge(square: Square) -> i32 {
+    let file = square.file() as i32;
+    let rank = square.rank() as i32;
+    let center_distance = (3 - file).max(file - 4) + (3 - rank).max(rank - 4);
+
+    center_distance * PUSH_TO_EDGE_WEIGHT
+}
+
+// Grows as the kings get closer together.
+fn push_close(a: Square, b: Square) -> i32 {
+    let file_distance = (a.file() as i32 - b.file() as i32).abs();
+    let rank_distance = (a.rank() as i32 - b.rank() as i32).abs();
+
+    (14 - file_distance - rank_distance) * PUSH_CLOSE_WEIGHT
+}
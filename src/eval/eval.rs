@@ -1,34 +1,249 @@
-﻿use crate::eval::eval_count_material::{eval_count_material};
+﻿use crate::eval::eval_count_material::eval_count_material;
 use crate::eval::eval_is_mate::eval_is_mate;
-use crate::eval::eval_pst::{eval_pst_end, eval_pst_opening};
+use crate::eval::eval_params::EvalParams;
+use crate::eval::eval_pst::eval_pst;
 use crate::eval::game_phase::get_game_phase;
+use crate::eval::eval_imbalance::eval_imbalance;
+use crate::eval::material_hash_table::MaterialHashTable;
+use crate::eval::pawn_hash_table::PawnHashTable;
+use crate::eval::score::{s, Score};
 use cozy_chess::Board;
 use crate::eval::eval_pawn_structure::eval_pawn_structure;
+use crate::eval::eval_mobility::eval_mobility;
+use crate::eval::eval_king_safety::eval_king_safety;
+use crate::eval::eval_pieces::eval_pieces;
+use crate::eval::eval_threats::eval_threats;
+use crate::eval::endgame::{get_scale_factor, probe_endgame_eval, SCALE_FACTOR_NORMAL};
+use cozy_chess::Color;
 
-pub fn eval(board: &Board, distance_from_root: u8) -> i32 {
-    let mate_score = eval_is_mate(&board, distance_from_root);
+// Being on the move is worth something, the side to move can act on its threats first.
+pub const TEMPO_BONUS: Score = s(10, 5);
+
+// Lazy eval trusts material and the piece square tables when they are this far outside the
+// window, the other terms rarely add up to more than this.
+pub const LAZY_EVAL_MARGIN: i32 = 600;
+
+pub fn eval(
+    board: &Board,
+    params: &EvalParams,
+    pawn_hash_table: &mut PawnHashTable,
+    material_hash_table: &mut MaterialHashTable,
+    distance_from_root: u8,
+) -> i32 {
+    if let Some(score) = eval_game_over_or_endgame(board, distance_from_root) {
+        return score;
+    }
+
+    eval_scaled(eval_tapered(board, params, pawn_hash_table, material_hash_table), board)
+}
+
+// The same evaluation as eval but from the point of view of the side to move.
+pub fn evaluate(
+    board: &Board,
+    params: &EvalParams,
+    pawn_hash_table: &mut PawnHashTable,
+    material_hash_table: &mut MaterialHashTable,
+    distance_from_root: u8,
+) -> i32 {
+    to_side_to_move(eval(board, params, pawn_hash_table, material_hash_table, distance_from_root), board)
+}
+
+// Like evaluate, but returns a cheap estimate from material and the piece square tables when that
+// is more than LAZY_EVAL_MARGIN below alpha or above beta, a node that fails low or high by that
+// much doesn't need the exact score. The window is side to move relative.
+pub fn evaluate_lazy(
+    board: &Board,
+    params: &EvalParams,
+    pawn_hash_table: &mut PawnHashTable,
+    material_hash_table: &mut MaterialHashTable,
+    distance_from_root: u8,
+    alpha: i32,
+    beta: i32,
+) -> i32 {
+    if let Some(score) = eval_game_over_or_endgame(board, distance_from_root) {
+        return to_side_to_move(score, board);
+    }
+
+    let lazy_terms = eval_lazy_terms(board, params, material_hash_table);
+    let lazy_score = to_side_to_move(eval_scaled(lazy_terms, board), board);
+    if lazy_score.saturating_add(LAZY_EVAL_MARGIN) <= alpha || lazy_score.saturating_sub(LAZY_EVAL_MARGIN) >= beta {
+        return lazy_score;
+    }
+
+    let score = lazy_terms + eval_positional_terms(board, params, pawn_hash_table);
+    to_side_to_move(eval_scaled(score, board), board)
+}
+
+// Finished games and endgames with a specialised evaluator don't need the general terms at all.
+fn eval_game_over_or_endgame(board: &Board, distance_from_root: u8) -> Option<i32> {
+    let mate_score = eval_is_mate(board, distance_from_root);
     if mate_score != 0 {
-        return mate_score;
+        return Some(mate_score);
     }
 
-    let mut score_opening = eval_opening(board, distance_from_root);
-    let mut score_endgame = eval_endgame(board, distance_from_root);
+    probe_endgame_eval(board)
+}
 
+// Scales down the endgame half of drawish endgames and blends the halves by the game phase.
+fn eval_scaled(score: Score, board: &Board) -> i32 {
     let phase = get_game_phase(board);
+    let scale_factor = get_scale_factor(board, score.eg());
+    let score = Score::new(score.mg(), score.eg() * scale_factor / SCALE_FACTOR_NORMAL);
+
+    score.taper(phase)
+}
+
+// Converts a white relative score to the side to move's point of view, and back.
+pub fn to_side_to_move(score: i32, board: &Board) -> i32 {
+    if board.side_to_move() == Color::White {
+        score
+    } else {
+        -score
+    }
+}
+
+// White relative tempo bonus.
+fn get_tempo(board: &Board, params: &EvalParams) -> Score {
+    if board.side_to_move() == Color::White {
+        params.tempo
+    } else {
+        -params.tempo
+    }
+}
 
-    ((score_opening * (256 - phase)) + (score_endgame * phase)) / 256
+// Sum of all the terms with their middlegame and endgame values still separate.
+pub fn eval_tapered(
+    board: &Board,
+    params: &EvalParams,
+    pawn_hash_table: &mut PawnHashTable,
+    material_hash_table: &mut MaterialHashTable,
+) -> Score {
+    eval_lazy_terms(board, params, material_hash_table) + eval_positional_terms(board, params, pawn_hash_table)
 }
 
-pub fn eval_opening(board: &Board, distance_from_root: u8) -> i32 {
-    let mut score = eval_count_material(board);
-    score += eval_pst_opening(board) / 2;
-    score += eval_pawn_structure(board) / 4;
+// The cheap terms that lazy eval trusts on their own.
+fn eval_lazy_terms(board: &Board, params: &EvalParams, material_hash_table: &mut MaterialHashTable) -> Score {
+    let mut score = eval_count_material(board, params);
+    score += eval_imbalance(board, params, material_hash_table);
+    score += eval_pst(board, params) / 2;
+    score += get_tempo(board, params);
     score
 }
 
-pub fn eval_endgame(board: &Board, distance_from_root: u8) -> i32 {
-    let mut score = eval_count_material(board);
-    score += eval_pst_end(board) / 2;
-    score += eval_pawn_structure(board) / 4;
+fn eval_positional_terms(board: &Board, params: &EvalParams, pawn_hash_table: &mut PawnHashTable) -> Score {
+    let mut score = eval_pawn_structure(board, params, pawn_hash_table);
+    score += eval_mobility(board, params);
+    score += eval_king_safety(board, params);
+    score += eval_pieces(board, params);
+    score += eval_threats(board, params);
     score
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eval::material_hash_table::MATERIAL_HASH_TABLE_SIZE;
+    use crate::eval::pawn_hash_table::PAWN_HASH_TABLE_SIZE;
+
+    // Openings, middlegames with kings castled to different sides, en passant, castling rights on
+    // one side only and endgames that go through the scale factors and the specialised evaluators.
+    const SYMMETRY_POSITIONS: [&str; 14] = [
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        "r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4",
+        "r2q1rk1/pp2bppp/2n1pn2/3p4/2PP4/2N1PN2/PP2BPPP/R2QK2R w KQ - 0 9",
+        "2kr3r/ppp2ppp/2n1bn2/2b1p3/4P3/2NP1N2/PPP1BPPP/R1B2RK1 b - - 3 9",
+        "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+        "2r3k1/pp3ppp/4p3/3pP3/3P4/P4N2/1P3PPP/2R3K1 b - - 0 24",
+        "6k1/R4ppp/8/8/8/8/5PPP/6K1 w - - 0 1",
+        "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+        "8/8/4k3/3p4/3P4/4K3/8/8 w - - 0 1",
+        "6k1/5pp1/7p/8/8/1Q5P/5PP1/6K1 w - - 0 1",
+        "8/5k2/8/3P4/8/8/5K2/8 w - - 0 1",
+        "8/8/4k3/8/8/2B5/3KB3/8 b - - 0 1",
+        "4k3/8/8/3b4/8/8/3PK3/8 w - - 0 1",
+    ];
+
+    // Lazy eval windows, side to move relative.
+    const LAZY_WINDOWS: [(i32, i32); 4] = [(i32::MIN, i32::MAX), (-50, 50), (1000, 1001), (-2000, -1999)];
+
+    // The same position with the colours swapped: the board mirrored top to bottom, white pieces
+    // turned black and the other way around, and the other side to move.
+    fn flip_fen(fen: &str) -> String {
+        let fields: Vec<&str> = fen.split_whitespace().collect();
+
+        let placement = fields[0]
+            .split('/')
+            .rev()
+            .map(swap_case)
+            .collect::<Vec<_>>()
+            .join("/");
+        let side_to_move = if fields[1] == "w" { "b" } else { "w" };
+
+        // White's rights come first, K before Q.
+        let castling = if fields[2] == "-" {
+            "-".to_string()
+        } else {
+            let swapped = swap_case(fields[2]);
+            let white: String = swapped.chars().filter(char::is_ascii_uppercase).collect();
+            let black: String = swapped.chars().filter(char::is_ascii_lowercase).collect();
+            white + &black
+        };
+
+        let en_passant = match fields[3].as_bytes() {
+            [file, rank] => format!("{}{}", *file as char, (b'1' + b'8' - rank) as char),
+            _ => "-".to_string(),
+        };
+
+        format!("{placement} {side_to_move} {castling} {en_passant} {}", fields[4..].join(" "))
+    }
+
+    fn swap_case(text: &str) -> String {
+        text.chars()
+            .map(|c| if c.is_ascii_uppercase() { c.to_ascii_lowercase() } else { c.to_ascii_uppercase() })
+            .collect()
+    }
+
+    // The evaluation must not care which colour a position is seen from: the colour flipped
+    // position has the negated white relative score and the same side to move relative score.
+    #[test]
+    fn colour_flipped_positions_score_the_same() {
+        let params = EvalParams::default();
+        let mut pawn_hash_table = PawnHashTable::new(PAWN_HASH_TABLE_SIZE);
+        let mut material_hash_table = MaterialHashTable::new(MATERIAL_HASH_TABLE_SIZE);
+
+        for fen in SYMMETRY_POSITIONS {
+            let board = Board::from_fen(fen, false).unwrap();
+            let flipped = Board::from_fen(&flip_fen(fen), false).unwrap();
+
+            let white_score = eval(&board, &params, &mut pawn_hash_table, &mut material_hash_table, 0);
+            let flipped_white_score = eval(&flipped, &params, &mut pawn_hash_table, &mut material_hash_table, 0);
+            assert_eq!(flipped_white_score, -white_score, "white relative: {fen}");
+
+            let score = evaluate(&board, &params, &mut pawn_hash_table, &mut material_hash_table, 0);
+            let flipped_score = evaluate(&flipped, &params, &mut pawn_hash_table, &mut material_hash_table, 0);
+            assert_eq!(flipped_score, score, "side to move relative: {fen}");
+
+            for (alpha, beta) in LAZY_WINDOWS {
+                let lazy_score = evaluate_lazy(&board, &params, &mut pawn_hash_table, &mut material_hash_table, 0, alpha, beta);
+                let flipped_lazy_score = evaluate_lazy(&flipped, &params, &mut pawn_hash_table, &mut material_hash_table, 0, alpha, beta);
+                assert_eq!(flipped_lazy_score, lazy_score, "lazy window {alpha} {beta}: {fen}");
+            }
+        }
+    }
+
+    // With a window this wide lazy eval never gets to skip anything.
+    #[test]
+    fn lazy_eval_with_full_window_is_full_eval() {
+        let params = EvalParams::default();
+        let mut pawn_hash_table = PawnHashTable::new(PAWN_HASH_TABLE_SIZE);
+        let mut material_hash_table = MaterialHashTable::new(MATERIAL_HASH_TABLE_SIZE);
+
+        for fen in SYMMETRY_POSITIONS {
+            let board = Board::from_fen(fen, false).unwrap();
+            let score = evaluate(&board, &params, &mut pawn_hash_table, &mut material_hash_table, 0);
+            let full_window_score = evaluate_lazy(&board, &params, &mut pawn_hash_table, &mut material_hash_table, 0, i32::MIN, i32::MAX);
+            assert_eq!(full_window_score, score, "{fen}");
+        }
+    }
+}
@@ -1,32 +1,34 @@
-﻿use cozy_chess::{Board, Color, Piece};
+﻿use crate::eval::eval_params::EvalParams;
+use crate::eval::score::{s, Score};
+use cozy_chess::{Board, Color, Piece};
 
-pub fn eval_count_material(board: &Board) -> i32{
-    let mut score: i32 = 0;
-    let white_pawns = board.colored_pieces(Color::White, Piece::Pawn);
-    let white_knights = board.colored_pieces(Color::White, Piece::Knight);
-    let white_bishops = board.colored_pieces(Color::White, Piece::Bishop);
-    let white_rooks = board.colored_pieces(Color::White, Piece::Rook);
-    let white_queens = board.colored_pieces(Color::White, Piece::Queen);
-    let black_pawns = board.colored_pieces(Color::Black, Piece::Pawn);
-    let black_knights = board.colored_pieces(Color::Black, Piece::Knight);
-    let black_bishops = board.colored_pieces(Color::Black, Piece::Bishop);
-    let black_rooks = board.colored_pieces(Color::Black, Piece::Rook);
-    let black_queens = board.colored_pieces(Color::Black, Piece::Queen);
+// Material values used by the evaluation, pawns and rooks gain value as the board empties while
+// the minor pieces lose some.
+pub const PIECE_SCORES: [Score; 5] = [
+    s(100, 120), // Pawn
+    s(320, 290), // Knight
+    s(330, 310), // Bishop
+    s(500, 540), // Rook
+    s(900, 920), // Queen
+];
 
-    score += white_pawns.len() as i32 * get_piece_value(Piece::Pawn);
-    score += white_knights.len() as i32 * get_piece_value(Piece::Knight);
-    score += white_bishops.len() as i32 * get_piece_value(Piece::Bishop);
-    score += white_rooks.len() as i32 * get_piece_value(Piece::Rook);
-    score += white_queens.len() as i32 * get_piece_value(Piece::Queen);
-    score -= black_pawns.len() as i32 * get_piece_value(Piece::Pawn);
-    score -= black_knights.len() as i32 * get_piece_value(Piece::Knight);
-    score -= black_bishops.len() as i32 * get_piece_value(Piece::Bishop);
-    score -= black_rooks.len() as i32 * get_piece_value(Piece::Rook);
-    score -= black_queens.len() as i32 * get_piece_value(Piece::Queen);
+pub fn eval_count_material(board: &Board, params: &EvalParams) -> Score {
+    eval_count_material_for(board, params, Color::White)
+        - eval_count_material_for(board, params, Color::Black)
+}
+
+pub fn eval_count_material_for(board: &Board, params: &EvalParams, color: Color) -> Score {
+    let mut score = Score::ZERO;
+
+    for (piece_index, &piece_score) in params.piece_scores.iter().enumerate() {
+        let piece = Piece::index(piece_index);
+        score += piece_score * board.colored_pieces(color, piece).len() as i32;
+    }
 
     score
 }
 
+// Flat piece values, used by the search for move ordering and exchange evaluation.
 pub fn get_piece_value(piece: Piece) -> i32 {
     match piece {
         Piece::Pawn => 100,
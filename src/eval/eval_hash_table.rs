@@ -0,0 +1,60 @@
+﻿use std::sync::atomic::{AtomicU64, Ordering};
+
+// Default number of entries, set with the EvalHashSize option. The same positions come up again
+// and again through transpositions and the quiescence search, but far fewer than the TT holds.
+pub const EVAL_HASH_TABLE_SIZE: usize = 1 << 18;
+
+// Marks a slot as written, an empty slot would otherwise look like a score of 0 for hash 0.
+const VALID_BIT: u64 = 1 << 32;
+
+// Static evals by Zobrist hash, shared by every search without a lock. A slot is two atomics with
+// the key stored xored with the data, when two threads write the same slot at once the halves no
+// longer match the key and the slot reads as a miss instead of giving the wrong score.
+// (see: https://www.chessprogramming.org/Shared_Hash_Table#Lock-less)
+pub struct EvalHashTable {
+    table: Vec<EvalHashEntry>,
+    table_size: usize,
+}
+
+#[derive(Default)]
+struct EvalHashEntry {
+    key_xor_data: AtomicU64,
+    data: AtomicU64,
+}
+
+impl EvalHashTable {
+    // The size is rounded down to a power of two.
+    pub fn new(table_size: usize) -> Self {
+        let table_size = 1 << table_size.max(1).ilog2();
+
+        EvalHashTable {
+            table: (0..table_size).map(|_| EvalHashEntry::default()).collect(),
+            table_size,
+        }
+    }
+
+    pub fn get(&self, hash: u64) -> Option<i32> {
+        let entry = &self.table[(hash as usize) & (self.table_size - 1)];
+        let data = entry.data.load(Ordering::Relaxed);
+        let key = entry.key_xor_data.load(Ordering::Relaxed) ^ data;
+
+        (key == hash && data & VALID_BIT != 0).then_some(data as u32 as i32)
+    }
+
+    // Always replace, an eval is cheap to recompute.
+    pub fn insert(&self, hash: u64, score: i32) {
+        let entry = &self.table[(hash as usize) & (self.table_size - 1)];
+        let data = score as u32 as u64 | VALID_BIT;
+
+        entry.key_xor_data.store(hash ^ data, Ordering::Relaxed);
+        entry.data.store(data, Ordering::Relaxed);
+    }
+
+    // Cached evals go stale when the evaluation itself changes.
+    pub fn clear(&self) {
+        for entry in &self.table {
+            entry.key_xor_data.store(0, Ordering::Relaxed);
+            entry.data.store(0, Ordering::Relaxed);
+        }
+    }
+}
@@ -0,0 +1,84 @@
+﻿use crate::eval::endgame::get_material_key;
+use crate::eval::eval_params::EvalParams;
+use crate::eval::material_hash_table::{MaterialHashEntry, MaterialHashTable};
+use crate::eval::score::Score;
+use cozy_chess::{Board, Color, Piece};
+
+// The imbalance tables count pieces in this order, the first slot is always 1 so the tables can
+// also hold a value per piece that doesn't depend on anything else.
+const IMBALANCE_PIECES: usize = 6;
+pub const IMBALANCE_TABLE_SIZE: usize = IMBALANCE_PIECES * (IMBALANCE_PIECES + 1) / 2;
+
+// Second order material values in 1/16 centipawns, for every pair of piece types the value of one
+// piece of the row type for every piece of the column type. The tables are triangular, only the
+// columns up to the row count. The bishop pair is left to the piece evaluation.
+// (see: https://www.chessprogramming.org/Material#Imbalance)
+#[rustfmt::skip]
+pub const IMBALANCE_OURS: [i32; IMBALANCE_TABLE_SIZE] = [
+    //  base  pawn knight bishop rook queen
+           0,                                  // base
+           0,    0,                            // pawn
+        -500,  100,   -40,                     // knight: better with more pawns, centred on 5
+           0,    0,     0,     0,              // bishop
+        1000, -200,    30,    30, -160,        // rook: worse with more pawns, redundant rooks
+           0,    0,    60,    60, -120,    0,  // queen: works with minors, redundant with rooks
+];
+
+#[rustfmt::skip]
+pub const IMBALANCE_THEIRS: [i32; IMBALANCE_TABLE_SIZE] = [
+    //  base  pawn knight bishop rook queen
+           0,                                  // base
+           0,    0,                            // pawn
+           0,    0,     0,                     // knight
+           0,    0,     0,     0,              // bishop
+           0,    0,   -40,   -40,    0,        // rook: minors coordinate against a rook
+           0,    0,   -60,   -60,    0,    0,  // queen: and against a queen
+];
+
+// White relative imbalance, looked up by the material signature.
+pub fn eval_imbalance(board: &Board, params: &EvalParams, material_hash_table: &mut MaterialHashTable) -> Score {
+    let key = get_material_key(board);
+    if let Some(entry) = material_hash_table.get(key) {
+        return entry.imbalance;
+    }
+
+    let imbalance =
+        eval_imbalance_for(board, params, Color::White) - eval_imbalance_for(board, params, Color::Black);
+    material_hash_table.insert(MaterialHashEntry { key, imbalance });
+    imbalance
+}
+
+pub fn eval_imbalance_for(board: &Board, params: &EvalParams, color: Color) -> Score {
+    let mut value = 0;
+    get_imbalance_terms(board, color, |index, ours, theirs| {
+        value += params.imbalance_ours[index] * ours + params.imbalance_theirs[index] * theirs;
+    });
+
+    Score::new(value / 16, value / 16)
+}
+
+// Calls `on_term` with every table index and how many times the imbalance_ours and the
+// imbalance_theirs value at that index count for `color`, in 1/16 centipawns.
+pub fn get_imbalance_terms(board: &Board, color: Color, mut on_term: impl FnMut(usize, i32, i32)) {
+    let ours = get_piece_counts(board, color);
+    let theirs = get_piece_counts(board, !color);
+
+    for row in 1..IMBALANCE_PIECES {
+        if ours[row] == 0 {
+            continue;
+        }
+
+        for column in 0..=row {
+            let index = row * (row + 1) / 2 + column;
+            on_term(index, ours[row] * ours[column], ours[row] * theirs[column]);
+        }
+    }
+}
+
+fn get_piece_counts(board: &Board, color: Color) -> [i32; IMBALANCE_PIECES] {
+    let mut counts = [1; IMBALANCE_PIECES];
+    for (piece_index, piece) in Piece::ALL[..5].iter().enumerate() {
+        counts[piece_index + 1] = board.colored_pieces(color, *piece).len() as i32;
+    }
+    counts
+}
@@ -1,13 +1,34 @@
 ﻿use cozy_chess::{Board, Color, GameStatus};
 
+// Mate scores are MATE_SCORE minus the distance in plies from the root to the mate, any score
+// beyond MATE_THRESHOLD is a forced mate.
+pub const MATE_SCORE: i32 = 1_000_000;
+pub const MATE_THRESHOLD: i32 = 900_000;
+
 pub fn eval_is_mate(board: &Board, distance_from_root: u8) -> i32 {
     if board.status() == GameStatus::Won {
         if board.side_to_move() == Color::White {
-            -1000000 + (1000 * distance_from_root as i32) as i32
+            -MATE_SCORE + distance_from_root as i32
         } else {
-            1000000 - (1000 * distance_from_root as i32) as i32
+            MATE_SCORE - distance_from_root as i32
         }
     } else {
         0
     }
-}
\ No newline at end of file
+}
+
+pub fn is_mate_score(score: i32) -> bool {
+    score.abs() >= MATE_THRESHOLD
+}
+
+// Converts a mate score into the number of moves until mate, negative when getting mated.
+pub fn mate_in_moves(score: i32) -> i32 {
+    let plies = MATE_SCORE - score.abs();
+    let moves = (plies + 1) / 2;
+
+    if score > 0 {
+        moves
+    } else {
+        -moves
+    }
+}
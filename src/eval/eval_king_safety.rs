@@ -0,0 +1,210 @@
+﻿use crate::eval::attacks::get_attacks_bb;
+use crate::eval::eval_pawn_structure::ARR_FILES;
+use crate::eval::eval_params::EvalParams;
+use crate::eval::score::{s, Score};
+use cozy_chess::{
+    get_bishop_moves, get_king_moves, get_knight_moves, get_rook_moves, BitBoard, Board, Color,
+    Piece, Square,
+};
+
+// Indexed by how many ranks in front of the king the nearest own pawn on the file stands.
+pub const PAWN_SHIELD_BONUS: [Score; 3] = [s(0, 0), s(25, 5), s(12, 3)];
+pub const MISSING_SHIELD_PAWN_PENALTY: Score = s(20, 0);
+// Indexed by how many ranks in front of the king the nearest enemy pawn on the file stands, a
+// pawn right in front of the king is blocked by it.
+pub const PAWN_STORM_PENALTY: [Score; 5] = [s(0, 0), s(5, 0), s(30, 0), s(15, 0), s(5, 0)];
+pub const SEMI_OPEN_FILE_PENALTY: Score = s(20, 0);
+pub const OPEN_FILE_PENALTY: Score = s(35, 0);
+
+// Attack units for every king zone square a piece attacks.
+pub const KNIGHT_ATTACK_UNITS: i32 = 2;
+pub const BISHOP_ATTACK_UNITS: i32 = 2;
+pub const ROOK_ATTACK_UNITS: i32 = 3;
+pub const QUEEN_ATTACK_UNITS: i32 = 5;
+
+// Attack units for a piece that can give a check from a square we don't defend.
+pub const KNIGHT_CHECK_UNITS: i32 = 3;
+pub const BISHOP_CHECK_UNITS: i32 = 2;
+pub const ROOK_CHECK_UNITS: i32 = 3;
+pub const QUEEN_CHECK_UNITS: i32 = 4;
+
+// Attack units to penalty, a single attacker is harmless but the danger grows quickly once
+// several pieces join in. (see: https://www.chessprogramming.org/King_Safety)
+#[rustfmt::skip]
+pub const KING_SAFETY_TABLE: [i32; 100] = [
+      0,   0,   1,   2,   3,   5,   7,   9,  12,  15,
+     18,  22,  26,  30,  35,  39,  44,  50,  56,  62,
+     68,  75,  82,  85,  89,  97, 105, 113, 122, 131,
+    140, 150, 169, 180, 191, 202, 213, 225, 237, 248,
+    260, 272, 283, 295, 307, 319, 330, 342, 354, 366,
+    377, 389, 401, 412, 424, 436, 448, 459, 471, 483,
+    494, 500, 500, 500, 500, 500, 500, 500, 500, 500,
+    500, 500, 500, 500, 500, 500, 500, 500, 500, 500,
+    500, 500, 500, 500, 500, 500, 500, 500, 500, 500,
+    500, 500, 500, 500, 500, 500, 500, 500, 500, 500,
+];
+
+// King safety matters while there are pieces on the board to attack the king with, the
+// endgame halves are small so the terms fade out with the game phase.
+pub fn eval_king_safety(board: &Board, params: &EvalParams) -> Score {
+    eval_king_safety_for(board, params, Color::White) - eval_king_safety_for(board, params, Color::Black)
+}
+
+pub fn eval_king_safety_for(board: &Board, params: &EvalParams, color: Color) -> Score {
+    eval_pawn_shelter(board, params, color) - eval_king_attacks(board, params, color)
+}
+
+fn eval_pawn_shelter(board: &Board, params: &EvalParams, color: Color) -> Score {
+    let mut score = Score::ZERO;
+    get_shelter_terms(board, color, |term| score += get_shelter_term_score(params, term));
+    score
+}
+
+// Every pawn shelter term the evaluation applies. The evaluation turns them into scores, the
+// tuner counts them to find out how often each constant is used.
+#[derive(Clone, Copy)]
+pub enum ShelterTerm {
+    OpenFile,
+    SemiOpenFile,
+    // Indexed by how many ranks in front of the king the pawn stands
+    Shield(usize),
+    MissingShield,
+    Storm(usize),
+}
+
+pub fn get_shelter_term_score(params: &EvalParams, term: ShelterTerm) -> Score {
+    match term {
+        ShelterTerm::OpenFile => -params.open_file_penalty,
+        ShelterTerm::SemiOpenFile => -params.semi_open_file_penalty,
+        ShelterTerm::Shield(distance) => params.pawn_shield_bonus[distance],
+        ShelterTerm::MissingShield => -params.missing_shield_pawn_penalty,
+        ShelterTerm::Storm(distance) => -params.pawn_storm_penalty[distance],
+    }
+}
+
+// Calls `on_term` for the pawn shield, pawn storms and open files on the king's file and the
+// files next to it.
+pub fn get_shelter_terms(board: &Board, color: Color, mut on_term: impl FnMut(ShelterTerm)) {
+    let king = board.king(color);
+    let king_file = king.file() as usize;
+    let own_pawns = board.colored_pieces(color, Piece::Pawn);
+    let enemy_pawns = board.colored_pieces(!color, Piece::Pawn);
+
+    for &file in &ARR_FILES[king_file.saturating_sub(1)..=(king_file + 1).min(7)] {
+        let file_bb = BitBoard(file);
+        let own_pawns_on_file = own_pawns & file_bb;
+        let enemy_pawns_on_file = enemy_pawns & file_bb;
+
+        if own_pawns_on_file.is_empty() {
+            on_term(if enemy_pawns_on_file.is_empty() {
+                ShelterTerm::OpenFile
+            } else {
+                ShelterTerm::SemiOpenFile
+            });
+        }
+
+        match nearest_pawn_in_front(king, own_pawns_on_file, color) {
+            Some(distance) if distance < PAWN_SHIELD_BONUS.len() => on_term(ShelterTerm::Shield(distance)),
+            _ => on_term(ShelterTerm::MissingShield),
+        }
+
+        if let Some(distance) = nearest_pawn_in_front(king, enemy_pawns_on_file, color) {
+            if distance < PAWN_STORM_PENALTY.len() {
+                on_term(ShelterTerm::Storm(distance));
+            }
+        }
+    }
+}
+
+// Number of ranks between the king and the closest of `pawns` in front of it.
+fn nearest_pawn_in_front(king: Square, pawns: BitBoard, color: Color) -> Option<usize> {
+    let king_rank = king.rank().relative_to(color) as usize;
+
+    pawns
+        .into_iter()
+        .map(|pawn| pawn.rank().relative_to(color) as usize)
+        .filter(|&rank| rank > king_rank)
+        .map(|rank| rank - king_rank)
+        .min()
+}
+
+// Weighted enemy attacks on the squares around the king plus the safe checks the enemy has,
+// turned into a penalty by the king safety table.
+fn eval_king_attacks(board: &Board, params: &EvalParams, color: Color) -> Score {
+    let enemy = !color;
+    let king = board.king(color);
+    let occupied = board.occupied();
+
+    let king_area = get_king_moves(king) | king.bitboard();
+    let king_area_forward = if color == Color::White {
+        BitBoard(king_area.0 << 8)
+    } else {
+        BitBoard(king_area.0 >> 8)
+    };
+    let king_zone = king_area | king_area_forward;
+
+    // Squares the enemy can check from without the piece being taken.
+    let safe_squares = !(get_attacks_bb(board, color) | board.colors(enemy));
+    let knight_checks = get_knight_moves(king) & safe_squares;
+    let bishop_checks = get_bishop_moves(king, occupied) & safe_squares;
+    let rook_checks = get_rook_moves(king, occupied) & safe_squares;
+
+    let mut attacker_count = 0;
+    let mut attack_units = 0;
+    let mut check_units = 0;
+
+    let mut add_attacker = |attacks: BitBoard, checks: BitBoard, zone_units: i32, units: i32| {
+        let zone_attacks = (attacks & king_zone).len() as i32;
+        if zone_attacks > 0 {
+            attacker_count += 1;
+            attack_units += zone_attacks * zone_units;
+        }
+        if !(attacks & checks).is_empty() {
+            check_units += units;
+        }
+    };
+
+    for knight in board.colored_pieces(enemy, Piece::Knight) {
+        add_attacker(
+            get_knight_moves(knight),
+            knight_checks,
+            params.knight_attack_units,
+            params.knight_check_units,
+        );
+    }
+    for bishop in board.colored_pieces(enemy, Piece::Bishop) {
+        add_attacker(
+            get_bishop_moves(bishop, occupied),
+            bishop_checks,
+            params.bishop_attack_units,
+            params.bishop_check_units,
+        );
+    }
+    for rook in board.colored_pieces(enemy, Piece::Rook) {
+        add_attacker(
+            get_rook_moves(rook, occupied),
+            rook_checks,
+            params.rook_attack_units,
+            params.rook_check_units,
+        );
+    }
+    for queen in board.colored_pieces(enemy, Piece::Queen) {
+        let attacks = get_bishop_moves(queen, occupied) | get_rook_moves(queen, occupied);
+        add_attacker(
+            attacks,
+            bishop_checks | rook_checks,
+            params.queen_attack_units,
+            params.queen_check_units,
+        );
+    }
+
+    // A lone attacker can't do much against the king on its own.
+    if attacker_count < 2 {
+        attack_units = 0;
+    }
+
+    let units = (attack_units + check_units).min(params.king_safety_table.len() as i32 - 1);
+    let penalty = params.king_safety_table[units as usize];
+
+    Score::new(penalty, penalty / 8)
+}
@@ -0,0 +1,71 @@
+﻿use crate::eval::attacks::get_pawn_attacks_bb;
+use crate::eval::eval_params::EvalParams;
+use crate::eval::score::{s, Score};
+use cozy_chess::{get_bishop_moves, get_knight_moves, get_rook_moves, Board, Color, Piece};
+
+// Bonus by the number of safe squares a piece attacks, a safe square is not occupied by an own
+// piece and not attacked by an enemy pawn.
+#[rustfmt::skip]
+pub const KNIGHT_MOBILITY: [Score; 9] = [
+    s(-31,-40), s(-26,-28), s(-6,-15), s(-2,-8), s(2,4), s(6,6), s(10,8), s(14,10), s(18,13),
+];
+
+#[rustfmt::skip]
+pub const BISHOP_MOBILITY: [Score; 14] = [
+    s(-24,-30), s(-10,-12), s(8,-2), s(13,6), s(19,12), s(25,21), s(28,27),
+    s(31,28), s(31,32), s(34,36), s(40,39), s(40,43), s(45,44), s(49,48),
+];
+
+#[rustfmt::skip]
+pub const ROOK_MOBILITY: [Score; 15] = [
+    s(-30,-39), s(-10,-8), s(1,11), s(2,20), s(2,35), s(6,49), s(11,52), s(16,60),
+    s(20,67), s(20,70), s(21,79), s(24,82), s(28,84), s(28,85), s(31,86),
+];
+
+#[rustfmt::skip]
+pub const QUEEN_MOBILITY: [Score; 28] = [
+    s(-8,-12), s(-3,-8), s(-2,-2), s(-2,5), s(5,10), s(6,14), s(6,15), s(9,19),
+    s(10,20), s(13,24), s(16,24), s(16,25), s(16,30), s(17,32), s(17,33), s(17,33),
+    s(18,34), s(18,35), s(19,37), s(20,38), s(23,38), s(27,42), s(27,42), s(27,43),
+    s(28,46), s(29,46), s(29,48), s(29,55),
+];
+
+pub fn eval_mobility(board: &Board, params: &EvalParams) -> Score {
+    eval_mobility_for(board, params, Color::White) - eval_mobility_for(board, params, Color::Black)
+}
+
+pub fn eval_mobility_for(board: &Board, params: &EvalParams, color: Color) -> Score {
+    let mut score = Score::ZERO;
+
+    get_mobility_terms(board, color, |piece, count| {
+        score += match piece {
+            Piece::Knight => params.knight_mobility[count],
+            Piece::Bishop => params.bishop_mobility[count],
+            Piece::Rook => params.rook_mobility[count],
+            _ => params.queen_mobility[count],
+        };
+    });
+
+    score
+}
+
+// Calls `on_term` with the number of safe squares of every knight, bishop, rook and queen of
+// `color`. The tuner counts these to find out how often each mobility value is used.
+pub fn get_mobility_terms(board: &Board, color: Color, mut on_term: impl FnMut(Piece, usize)) {
+    let occupied = board.occupied();
+    let safe_squares = !(board.colors(color) | get_pawn_attacks_bb(board, !color));
+
+    for knight in board.colored_pieces(color, Piece::Knight) {
+        on_term(Piece::Knight, (get_knight_moves(knight) & safe_squares).len() as usize);
+    }
+    for bishop in board.colored_pieces(color, Piece::Bishop) {
+        on_term(Piece::Bishop, (get_bishop_moves(bishop, occupied) & safe_squares).len() as usize);
+    }
+    for rook in board.colored_pieces(color, Piece::Rook) {
+        on_term(Piece::Rook, (get_rook_moves(rook, occupied) & safe_squares).len() as usize);
+    }
+    for queen in board.colored_pieces(color, Piece::Queen) {
+        let attacks = get_rook_moves(queen, occupied) | get_bishop_moves(queen, occupied);
+        on_term(Piece::Queen, (attacks & safe_squares).len() as usize);
+    }
+}
@@ -0,0 +1,564 @@
+﻿use crate::eval::eval::TEMPO_BONUS;
+use crate::eval::eval_count_material::PIECE_SCORES;
+use crate::eval::eval_imbalance::{IMBALANCE_OURS, IMBALANCE_THEIRS, IMBALANCE_TABLE_SIZE};
+use crate::eval::eval_king_safety::{
+    BISHOP_ATTACK_UNITS, BISHOP_CHECK_UNITS, KING_SAFETY_TABLE, KNIGHT_ATTACK_UNITS,
+    KNIGHT_CHECK_UNITS, MISSING_SHIELD_PAWN_PENALTY, OPEN_FILE_PENALTY, PAWN_SHIELD_BONUS,
+    PAWN_STORM_PENALTY, QUEEN_ATTACK_UNITS, QUEEN_CHECK_UNITS, ROOK_ATTACK_UNITS,
+    ROOK_CHECK_UNITS, SEMI_OPEN_FILE_PENALTY,
+};
+use crate::eval::eval_mobility::{BISHOP_MOBILITY, KNIGHT_MOBILITY, QUEEN_MOBILITY, ROOK_MOBILITY};
+use crate::eval::eval_pawn_structure::{
+    BACKWARD_PAWN_PENALTY, CANDIDATE_PASSER_BONUS, ISOLATED_PAWN_PENALTY,
+    PASSED_PAWN_BLOCKED_PENALTY, PASSED_PAWN_BONUS, PASSED_PAWN_ENEMY_KING_DISTANCE,
+    PASSED_PAWN_FREE_PATH_BONUS, PASSED_PAWN_OWN_KING_DISTANCE, PASSED_PAWN_ROOK_BEHIND_BONUS,
+    PHALANX_PAWN_BONUS, STACKED_PAWN_PENALTY, SUPPORTED_PAWN_BONUS, UNSTOPPABLE_PASSER_BONUS,
+    WEAK_UNOPPOSED_PAWN_PENALTY,
+};
+use crate::eval::eval_pieces::{
+    BAD_BISHOP_PAWN_PENALTY, BISHOP_OUTPOST_BONUS, BISHOP_PAIR_BONUS, KNIGHT_OUTPOST_BONUS,
+    QUEEN_ON_SEVENTH_BONUS, QUEEN_OPEN_FILE_BONUS, QUEEN_SEMI_OPEN_FILE_BONUS, ROOK_ON_SEVENTH_BONUS,
+    ROOK_OPEN_FILE_BONUS, ROOK_SEMI_OPEN_FILE_BONUS, TRAPPED_BISHOP_PENALTY, TRAPPED_ROOK_PENALTY,
+};
+use crate::eval::eval_pst::get_default_pst;
+use crate::eval::eval_threats::{
+    HANGING_PIECE_BONUS, PAWN_PUSH_THREAT_BONUS, THREAT_BY_MINOR, THREAT_BY_PAWN,
+    THREAT_BY_ROOK_ON_QUEEN,
+};
+use crate::eval::score::Score;
+use std::fmt::Write as _;
+use std::{fs, io, slice};
+
+// Every weight of the handcrafted evaluation. The constants next to the eval terms are the
+// compiled-in defaults, a parameters file or setoption overrides them at runtime.
+//
+// Parameters are known by the snake case name of their field, the piece square tables are
+// pst_pawn to pst_king. Scores are written as a middlegame and an endgame value.
+#[derive(Clone)]
+pub struct EvalParams {
+    pub piece_scores: [Score; 5],
+    pub imbalance_ours: [i32; IMBALANCE_TABLE_SIZE],
+    pub imbalance_theirs: [i32; IMBALANCE_TABLE_SIZE],
+    pub pst: [[Score; 64]; 6], // Indexed by piece and then by square as the tables are written
+
+    pub stacked_pawn_penalty: Score,
+    pub isolated_pawn_penalty: Score,
+    pub backward_pawn_penalty: Score,
+    pub weak_unopposed_pawn_penalty: Score,
+    pub supported_pawn_bonus: [Score; 8],
+    pub phalanx_pawn_bonus: [Score; 8],
+    pub candidate_passer_bonus: [Score; 8],
+    pub passed_pawn_bonus: [Score; 8],
+    pub passed_pawn_blocked_penalty: Score,
+    pub passed_pawn_free_path_bonus: Score,
+    pub passed_pawn_own_king_distance: i32,
+    pub passed_pawn_enemy_king_distance: i32,
+    pub passed_pawn_rook_behind_bonus: Score,
+    pub unstoppable_passer_bonus: Score,
+
+    pub knight_mobility: [Score; 9],
+    pub bishop_mobility: [Score; 14],
+    pub rook_mobility: [Score; 15],
+    pub queen_mobility: [Score; 28],
+
+    pub pawn_shield_bonus: [Score; 3],
+    pub missing_shield_pawn_penalty: Score,
+    pub pawn_storm_penalty: [Score; 5],
+    pub semi_open_file_penalty: Score,
+    pub open_file_penalty: Score,
+    pub knight_attack_units: i32,
+    pub bishop_attack_units: i32,
+    pub rook_attack_units: i32,
+    pub queen_attack_units: i32,
+    pub knight_check_units: i32,
+    pub bishop_check_units: i32,
+    pub rook_check_units: i32,
+    pub queen_check_units: i32,
+    pub king_safety_table: [i32; 100],
+
+    pub bishop_pair_bonus: Score,
+    pub rook_open_file_bonus: Score,
+    pub rook_semi_open_file_bonus: Score,
+    pub queen_open_file_bonus: Score,
+    pub queen_semi_open_file_bonus: Score,
+    pub rook_on_seventh_bonus: Score,
+    pub queen_on_seventh_bonus: Score,
+    pub knight_outpost_bonus: Score,
+    pub bishop_outpost_bonus: Score,
+    pub bad_bishop_pawn_penalty: Score,
+    pub trapped_bishop_penalty: Score,
+    pub trapped_rook_penalty: Score,
+
+    pub threat_by_pawn: [Score; 5], // Indexed by the attacked piece
+    pub threat_by_minor: [Score; 5],
+    pub threat_by_rook_on_queen: Score,
+    pub hanging_piece_bonus: Score,
+    pub pawn_push_threat_bonus: Score,
+
+    pub tempo: Score, // Bonus for the side to move
+}
+
+impl Default for EvalParams {
+    fn default() -> Self {
+        EvalParams {
+            piece_scores: PIECE_SCORES,
+            imbalance_ours: IMBALANCE_OURS,
+            imbalance_theirs: IMBALANCE_THEIRS,
+            pst: get_default_pst(),
+
+            stacked_pawn_penalty: STACKED_PAWN_PENALTY,
+            isolated_pawn_penalty: ISOLATED_PAWN_PENALTY,
+            backward_pawn_penalty: BACKWARD_PAWN_PENALTY,
+            weak_unopposed_pawn_penalty: WEAK_UNOPPOSED_PAWN_PENALTY,
+            supported_pawn_bonus: SUPPORTED_PAWN_BONUS,
+            phalanx_pawn_bonus: PHALANX_PAWN_BONUS,
+            candidate_passer_bonus: CANDIDATE_PASSER_BONUS,
+            passed_pawn_bonus: PASSED_PAWN_BONUS,
+            passed_pawn_blocked_penalty: PASSED_PAWN_BLOCKED_PENALTY,
+            passed_pawn_free_path_bonus: PASSED_PAWN_FREE_PATH_BONUS,
+            passed_pawn_own_king_distance: PASSED_PAWN_OWN_KING_DISTANCE,
+            passed_pawn_enemy_king_distance: PASSED_PAWN_ENEMY_KING_DISTANCE,
+            passed_pawn_rook_behind_bonus: PASSED_PAWN_ROOK_BEHIND_BONUS,
+            unstoppable_passer_bonus: UNSTOPPABLE_PASSER_BONUS,
+
+            knight_mobility: KNIGHT_MOBILITY,
+            bishop_mobility: BISHOP_MOBILITY,
+            rook_mobility: ROOK_MOBILITY,
+            queen_mobility: QUEEN_MOBILITY,
+
+            pawn_shield_bonus: PAWN_SHIELD_BONUS,
+            missing_shield_pawn_penalty: MISSING_SHIELD_PAWN_PENALTY,
+            pawn_storm_penalty: PAWN_STORM_PENALTY,
+            semi_open_file_penalty: SEMI_OPEN_FILE_PENALTY,
+            open_file_penalty: OPEN_FILE_PENALTY,
+            knight_attack_units: KNIGHT_ATTACK_UNITS,
+            bishop_attack_units: BISHOP_ATTACK_UNITS,
+            rook_attack_units: ROOK_ATTACK_UNITS,
+            queen_attack_units: QUEEN_ATTACK_UNITS,
+            knight_check_units: KNIGHT_CHECK_UNITS,
+            bishop_check_units: BISHOP_CHECK_UNITS,
+            rook_check_units: ROOK_CHECK_UNITS,
+            queen_check_units: QUEEN_CHECK_UNITS,
+            king_safety_table: KING_SAFETY_TABLE,
+
+            bishop_pair_bonus: BISHOP_PAIR_BONUS,
+            rook_open_file_bonus: ROOK_OPEN_FILE_BONUS,
+            rook_semi_open_file_bonus: ROOK_SEMI_OPEN_FILE_BONUS,
+            queen_open_file_bonus: QUEEN_OPEN_FILE_BONUS,
+            queen_semi_open_file_bonus: QUEEN_SEMI_OPEN_FILE_BONUS,
+            rook_on_seventh_bonus: ROOK_ON_SEVENTH_BONUS,
+            queen_on_seventh_bonus: QUEEN_ON_SEVENTH_BONUS,
+            knight_outpost_bonus: KNIGHT_OUTPOST_BONUS,
+            bishop_outpost_bonus: BISHOP_OUTPOST_BONUS,
+            bad_bishop_pawn_penalty: BAD_BISHOP_PAWN_PENALTY,
+            trapped_bishop_penalty: TRAPPED_BISHOP_PENALTY,
+            trapped_rook_penalty: TRAPPED_ROOK_PENALTY,
+
+            threat_by_pawn: THREAT_BY_PAWN,
+            threat_by_minor: THREAT_BY_MINOR,
+            threat_by_rook_on_queen: THREAT_BY_ROOK_ON_QUEEN,
+            hanging_piece_bonus: HANGING_PIECE_BONUS,
+            pawn_push_threat_bonus: PAWN_PUSH_THREAT_BONUS,
+
+            tempo: TEMPO_BONUS,
+        }
+    }
+}
+
+pub enum ParamValues<'a> {
+    Scores(&'a mut [Score]),
+    Ints(&'a mut [i32]),
+}
+
+impl ParamValues<'_> {
+    // Number of plain numbers the parameter is written as.
+    fn number_count(&self) -> usize {
+        match self {
+            ParamValues::Scores(scores) => scores.len() * 2,
+            ParamValues::Ints(values) => values.len(),
+        }
+    }
+
+    fn numbers(&self) -> Vec<i32> {
+        match self {
+            ParamValues::Scores(scores) => scores.iter().flat_map(|score| [score.mg(), score.eg()]).collect(),
+            ParamValues::Ints(values) => values.to_vec(),
+        }
+    }
+
+    fn set_number(&mut self, index: usize, value: i32) {
+        match self {
+            ParamValues::Scores(scores) => {
+                let score = &mut scores[index / 2];
+                *score = if index.is_multiple_of(2) {
+                    Score::new(value, score.eg())
+                } else {
+                    Score::new(score.mg(), value)
+                };
+            }
+            ParamValues::Ints(values) => values[index] = value,
+        }
+    }
+}
+
+impl EvalParams {
+    // Every parameter by name, in the order they are written to a file.
+    pub fn params_mut(&mut self) -> Vec<(&'static str, ParamValues<'_>)> {
+        use ParamValues::{Ints, Scores};
+
+        let [pst_pawn, pst_knight, pst_bishop, pst_rook, pst_queen, pst_king] = &mut self.pst;
+
+        vec![
+            ("piece_scores", Scores(&mut self.piece_scores)),
+            ("imbalance_ours", Ints(&mut self.imbalance_ours)),
+            ("imbalance_theirs", Ints(&mut self.imbalance_theirs)),
+            ("pst_pawn", Scores(pst_pawn)),
+            ("pst_knight", Scores(pst_knight)),
+            ("pst_bishop", Scores(pst_bishop)),
+            ("pst_rook", Scores(pst_rook)),
+            ("pst_queen", Scores(pst_queen)),
+            ("pst_king", Scores(pst_king)),
+            ("stacked_pawn_penalty", Scores(slice::from_mut(&mut self.stacked_pawn_penalty))),
+            ("isolated_pawn_penalty", Scores(slice::from_mut(&mut self.isolated_pawn_penalty))),
+            ("backward_pawn_penalty", Scores(slice::from_mut(&mut self.backward_pawn_penalty))),
+            ("weak_unopposed_pawn_penalty", Scores(slice::from_mut(&mut self.weak_unopposed_pawn_penalty))),
+            ("supported_pawn_bonus", Scores(&mut self.supported_pawn_bonus)),
+            ("phalanx_pawn_bonus", Scores(&mut self.phalanx_pawn_bonus)),
+            ("candidate_passer_bonus", Scores(&mut self.candidate_passer_bonus)),
+            ("passed_pawn_bonus", Scores(&mut self.passed_pawn_bonus)),
+            ("passed_pawn_blocked_penalty", Scores(slice::from_mut(&mut self.passed_pawn_blocked_penalty))),
+            ("passed_pawn_free_path_bonus", Scores(slice::from_mut(&mut self.passed_pawn_free_path_bonus))),
+            ("passed_pawn_own_king_distance", Ints(slice::from_mut(&mut self.passed_pawn_own_king_distance))),
+            ("passed_pawn_enemy_king_distance", Ints(slice::from_mut(&mut self.passed_pawn_enemy_king_distance))),
+            ("passed_pawn_rook_behind_bonus", Scores(slice::from_mut(&mut self.passed_pawn_rook_behind_bonus))),
+            ("unstoppable_passer_bonus", Scores(slice::from_mut(&mut self.unstoppable_passer_bonus))),
+            ("knight_mobility", Scores(&mut self.knight_mobility)),
+            ("bishop_mobility", Scores(&mut self.bishop_mobility)),
+            ("rook_mobility", Scores(&mut self.rook_mobility)),
+            ("queen_mobility", Scores(&mut self.queen_mobility)),
+            ("pawn_shield_bonus", Scores(&mut self.pawn_shield_bonus)),
+            ("missing_shield_pawn_penalty", Scores(slice::from_mut(&mut self.missing_shield_pawn_penalty))),
+            ("pawn_storm_penalty", Scores(&mut self.pawn_storm_penalty)),
+            ("semi_open_file_penalty", Scores(slice::from_mut(&mut self.semi_open_file_penalty))),
+            ("open_file_penalty", Scores(slice::from_mut(&mut self.open_file_penalty))),
+            ("knight_attack_units", Ints(slice::from_mut(&mut self.knight_attack_units))),
+            ("bishop_attack_units", Ints(slice::from_mut(&mut self.bishop_attack_units))),
+            ("rook_attack_units", Ints(slice::from_mut(&mut self.rook_attack_units))),
+            ("queen_attack_units", Ints(slice::from_mut(&mut self.queen_attack_units))),
+            ("knight_check_units", Ints(slice::from_mut(&mut self.knight_check_units))),
+            ("bishop_check_units", Ints(slice::from_mut(&mut self.bishop_check_units))),
+            ("rook_check_units", Ints(slice::from_mut(&mut self.rook_check_units))),
+            ("queen_check_units", Ints(slice::from_mut(&mut self.queen_check_units))),
+            ("king_safety_table", Ints(&mut self.king_safety_table)),
+            ("bishop_pair_bonus", Scores(slice::from_mut(&mut self.bishop_pair_bonus))),
+            ("rook_open_file_bonus", Scores(slice::from_mut(&mut self.rook_open_file_bonus))),
+            ("rook_semi_open_file_bonus", Scores(slice::from_mut(&mut self.rook_semi_open_file_bonus))),
+            ("queen_open_file_bonus", Scores(slice::from_mut(&mut self.queen_open_file_bonus))),
+            ("queen_semi_open_file_bonus", Scores(slice::from_mut(&mut self.queen_semi_open_file_bonus))),
+            ("rook_on_seventh_bonus", Scores(slice::from_mut(&mut self.rook_on_seventh_bonus))),
+            ("queen_on_seventh_bonus", Scores(slice::from_mut(&mut self.queen_on_seventh_bonus))),
+            ("knight_outpost_bonus", Scores(slice::from_mut(&mut self.knight_outpost_bonus))),
+            ("bishop_outpost_bonus", Scores(slice::from_mut(&mut self.bishop_outpost_bonus))),
+            ("bad_bishop_pawn_penalty", Scores(slice::from_mut(&mut self.bad_bishop_pawn_penalty))),
+            ("trapped_bishop_penalty", Scores(slice::from_mut(&mut self.trapped_bishop_penalty))),
+            ("trapped_rook_penalty", Scores(slice::from_mut(&mut self.trapped_rook_penalty))),
+            ("threat_by_pawn", Scores(&mut self.threat_by_pawn)),
+            ("threat_by_minor", Scores(&mut self.threat_by_minor)),
+            ("threat_by_rook_on_queen", Scores(slice::from_mut(&mut self.threat_by_rook_on_queen))),
+            ("hanging_piece_bonus", Scores(slice::from_mut(&mut self.hanging_piece_bonus))),
+            ("pawn_push_threat_bonus", Scores(slice::from_mut(&mut self.pawn_push_threat_bonus))),
+            ("tempo", Scores(slice::from_mut(&mut self.tempo))),
+        ]
+    }
+
+    // Sets a single number by its flat name, this is what the hidden setoptions for SPSA use:
+    //   <name>_mg, <name>_eg           single scores
+    //   <name>_<i>_mg, <name>_<i>_eg   score arrays
+    //   <name>                         single values
+    //   <name>_<i>                     value arrays
+    // Returns false when there is no such parameter.
+    pub fn set_param(&mut self, name: &str, value: i32) -> bool {
+        for (param_name, mut values) in self.params_mut() {
+            let Some(suffix) = name.strip_prefix(param_name) else {
+                continue;
+            };
+            if let Some(index) = get_flat_index(&values, suffix) {
+                values.set_number(index, value);
+                return true;
+            }
+        }
+
+        false
+    }
+
+    // Reads parameters from a plain text or a JSON file, anything the file doesn't mention keeps
+    // its default. The text format is a name followed by its numbers, `#` starts a comment:
+    //   piece_scores 100 120 320 290 330 310 500 540 900 920
+    // and JSON has the same names as keys with numbers or nested arrays of numbers as values:
+    //   { "piece_scores": [[100, 120], [320, 290], [330, 310], [500, 540], [900, 920]] }
+    pub fn load(path: &str) -> Result<EvalParams, String> {
+        let text = fs::read_to_string(path).map_err(|error| error.to_string())?;
+        let entries = if text.trim_start().starts_with('{') {
+            parse_json(&text)?
+        } else {
+            parse_text(&text)?
+        };
+
+        let mut params = EvalParams::default();
+        for (name, numbers) in entries {
+            let mut params_by_name = params.params_mut();
+            let Some((_, values)) = params_by_name.iter_mut().find(|(param_name, _)| *param_name == name) else {
+                return Err(format!("unknown parameter {name}"));
+            };
+            if numbers.len() != values.number_count() {
+                return Err(format!(
+                    "{name} needs {} values but has {}",
+                    values.number_count(),
+                    numbers.len()
+                ));
+            }
+            for (index, &value) in numbers.iter().enumerate() {
+                values.set_number(index, value);
+            }
+        }
+
+        Ok(params)
+    }
+
+    // Writes all parameters in the text format load reads, `header` becomes a comment on top.
+    pub fn save(&self, path: &str, header: &str) -> io::Result<()> {
+        // params_mut needs a mutable borrow, writing works on a copy.
+        let mut params = self.clone();
+        let mut text = String::new();
+        writeln!(text, "# {header}").unwrap();
+
+        for (name, values) in params.params_mut() {
+            let numbers = values.numbers();
+            // Scores stay together as pairs, the tables get a row per rank.
+            let per_line = match values {
+                ParamValues::Scores(_) => 16,
+                ParamValues::Ints(_) => 10,
+            };
+
+            if numbers.len() <= per_line {
+                let numbers = numbers.iter().map(i32::to_string).collect::<Vec<_>>().join(" ");
+                writeln!(text, "{name} {numbers}").unwrap();
+            } else {
+                writeln!(text, "{name}").unwrap();
+                for line in numbers.chunks(per_line) {
+                    // Every value gets a separator, wide negative values would run together otherwise.
+                    let numbers = line.iter().map(|value| format!(" {value:>4}")).collect::<String>();
+                    writeln!(text, "  {numbers}").unwrap();
+                }
+            }
+        }
+
+        fs::write(path, text)
+    }
+}
+
+// Position of the number a flat name suffix points at, `suffix` is the flat name with the
+// parameter name taken off.
+fn get_flat_index(values: &ParamValues, suffix: &str) -> Option<usize> {
+    let (index, phase) = match values {
+        ParamValues::Scores(scores) => {
+            let (rest, phase) = if let Some(rest) = suffix.strip_suffix("_mg") {
+                (rest, 0)
+            } else {
+                (suffix.strip_suffix("_eg")?, 1)
+            };
+            let index = get_array_index(rest, scores.len())?;
+            (index * 2, phase)
+        }
+        ParamValues::Ints(ints) => (get_array_index(suffix, ints.len())?, 0),
+    };
+
+    Some(index + phase)
+}
+
+// Single values have no index in their name.
+fn get_array_index(suffix: &str, len: usize) -> Option<usize> {
+    if len == 1 {
+        return suffix.is_empty().then_some(0);
+    }
+
+    let index = suffix.strip_prefix('_')?.parse::<usize>().ok()?;
+    (index < len).then_some(index)
+}
+
+// Names followed by their numbers, the numbers of a parameter may go over several lines.
+fn parse_text(text: &str) -> Result<Vec<(String, Vec<i32>)>, String> {
+    let mut entries: Vec<(String, Vec<i32>)> = Vec::new();
+
+    for line in text.lines() {
+        let line = line.split('#').next().unwrap_or_default();
+
+        for token in line.split_whitespace() {
+            match parse_number(token) {
+                Some(value) => match entries.last_mut() {
+                    Some((_, numbers)) => numbers.push(value),
+                    None => return Err(format!("value {token} before the first parameter name")),
+                },
+                None => entries.push((token.to_string(), Vec::new())),
+            }
+        }
+    }
+
+    Ok(entries)
+}
+
+// Tuners write fractional values, these are rounded to the nearest integer.
+fn parse_number(token: &str) -> Option<i32> {
+    let value = token.parse::<f64>().ok()?;
+    value.is_finite().then(|| value.round() as i32)
+}
+
+// Just enough JSON for a flat object of numbers and (nested) arrays of numbers, the arrays are
+// flattened in order.
+fn parse_json(text: &str) -> Result<Vec<(String, Vec<i32>)>, String> {
+    let mut parser = JsonParser {
+        bytes: text.as_bytes(),
+        position: 0,
+    };
+    let mut entries = Vec::new();
+
+    parser.expect(b'{')?;
+    if parser.peek() == Some(b'}') {
+        return Ok(entries);
+    }
+
+    loop {
+        let name = parser.parse_string()?;
+        parser.expect(b':')?;
+        let mut numbers = Vec::new();
+        parser.parse_numbers(&mut numbers)?;
+        entries.push((name, numbers));
+
+        match parser.next() {
+            Some(b',') => continue,
+            Some(b'}') => break,
+            _ => return Err(parser.error("expected , or }")),
+        }
+    }
+
+    Ok(entries)
+}
+
+struct JsonParser<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl JsonParser<'_> {
+    // The next byte that isn't whitespace.
+    fn peek(&mut self) -> Option<u8> {
+        while self.position < self.bytes.len() && self.bytes[self.position].is_ascii_whitespace() {
+            self.position += 1;
+        }
+        self.bytes.get(self.position).copied()
+    }
+
+    fn next(&mut self) -> Option<u8> {
+        let byte = self.peek()?;
+        self.position += 1;
+        Some(byte)
+    }
+
+    fn expect(&mut self, expected: u8) -> Result<(), String> {
+        if self.next() == Some(expected) {
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected {}", expected as char)))
+        }
+    }
+
+    fn error(&self, message: &str) -> String {
+        format!("{message} at byte {}", self.position)
+    }
+
+    fn parse_string(&mut self) -> Result<String, String> {
+        self.expect(b'"')?;
+        let start = self.position;
+        while self.position < self.bytes.len() && self.bytes[self.position] != b'"' {
+            self.position += 1;
+        }
+        if self.position == self.bytes.len() {
+            return Err(self.error("unterminated string"));
+        }
+
+        let string = String::from_utf8_lossy(&self.bytes[start..self.position]).into_owned();
+        self.position += 1;
+        Ok(string)
+    }
+
+    fn parse_numbers(&mut self, numbers: &mut Vec<i32>) -> Result<(), String> {
+        if self.peek() != Some(b'[') {
+            let start = self.position;
+            while self.position < self.bytes.len() && b"+-.eE0123456789".contains(&self.bytes[self.position]) {
+                self.position += 1;
+            }
+            let token = String::from_utf8_lossy(&self.bytes[start..self.position]);
+            let value = parse_number(&token).ok_or_else(|| self.error("expected a number"))?;
+            numbers.push(value);
+            return Ok(());
+        }
+
+        self.position += 1;
+        if self.peek() == Some(b']') {
+            self.position += 1;
+            return Ok(());
+        }
+
+        loop {
+            self.parse_numbers(numbers)?;
+            match self.next() {
+                Some(b',') => continue,
+                Some(b']') => return Ok(()),
+                _ => return Err(self.error("expected , or ]")),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_numbers(params: &EvalParams) -> Vec<(&'static str, Vec<i32>)> {
+        let mut params = params.clone();
+        params.params_mut().into_iter().map(|(name, values)| (name, values.numbers())).collect()
+    }
+
+    fn save_and_load(params: &EvalParams, file_name: &str) -> EvalParams {
+        let path = std::env::temp_dir().join(format!("{file_name}_{}.txt", std::process::id()));
+        let path = path.to_str().unwrap();
+        params.save(path, "test").unwrap();
+        let loaded = EvalParams::load(path);
+        fs::remove_file(path).unwrap();
+        loaded.unwrap()
+    }
+
+    #[test]
+    fn default_params_round_trip() {
+        let params = EvalParams::default();
+        assert_eq!(get_numbers(&save_and_load(&params, "default_params")), get_numbers(&params));
+    }
+
+    #[test]
+    fn wide_negative_values_round_trip() {
+        let mut params = EvalParams::default();
+        for (name, mut values) in params.params_mut() {
+            for index in 0..values.number_count() {
+                let value = match index % 3 {
+                    0 => -100 - index as i32,
+                    1 => -1000 - index as i32,
+                    _ => 1000 + name.len() as i32,
+                };
+                values.set_number(index, value);
+            }
+        }
+
+        let loaded = save_and_load(&params, "wide_params");
+        assert_eq!(get_numbers(&loaded), get_numbers(&params));
+    }
+}
@@ -1,4 +1,8 @@
-﻿use cozy_chess::{BitBoard, Board, Color, Piece};
+﻿use crate::eval::attacks::{get_attacks_bb, get_pawn_attacks_bb};
+use crate::eval::eval_params::EvalParams;
+use crate::eval::pawn_hash_table::{get_pawn_key, PawnHashEntry, PawnHashTable};
+use crate::eval::score::{s, Score};
+use cozy_chess::{get_pawn_attacks, get_rook_moves, BitBoard, Board, Color, Piece, Rank, Square};
 
 pub const A_FILE: u64 = 0x101010101010101;
 pub const B_FILE: u64 = 0x202020202020202;
@@ -23,77 +27,281 @@ pub const ARR_NEIGHBOR_FILES: [u64; 8] = [
     G_FILE,
 ];
 
-pub const STACKED_PAWN_PENALTY: i32 = 25;
-pub const ISOLATED_PAWN_PENALTY: i32 = 50;
-pub const PASSED_PAWN_BONUS: i32 = 175;
-pub const PASSED_PAWN_RANK_BONUS: i32 = 25;
+pub const STACKED_PAWN_PENALTY: Score = s(8, 14);
+pub const ISOLATED_PAWN_PENALTY: Score = s(12, 10);
+// A pawn that has fallen behind its neighbours and can't advance without being taken.
+pub const BACKWARD_PAWN_PENALTY: Score = s(8, 8);
+// Extra penalty for an isolated or backward pawn that enemy rooks can attack down the file.
+pub const WEAK_UNOPPOSED_PAWN_PENALTY: Score = s(6, 2);
 
-pub fn eval_pawn_structure(board: &Board) -> i32 {
-    let mut score = 0;
+// Indexed by the rank of the pawn from its own side.
+#[rustfmt::skip]
+pub const SUPPORTED_PAWN_BONUS: [Score; 8] = [
+    s(0, 0), s(2, 2), s(4, 3), s(6, 5), s(12, 10), s(20, 20), s(35, 35), s(0, 0),
+];
+#[rustfmt::skip]
+pub const PHALANX_PAWN_BONUS: [Score; 8] = [
+    s(0, 0), s(3, 2), s(5, 4), s(8, 6), s(15, 12), s(25, 25), s(45, 45), s(0, 0),
+];
+// A pawn on a half open file that has at least as many friendly pawns to help it through as
+// there are enemy pawns in the way.
+#[rustfmt::skip]
+pub const CANDIDATE_PASSER_BONUS: [Score; 8] = [
+    s(0, 0), s(2, 5), s(3, 6), s(6, 12), s(12, 24), s(20, 40), s(0, 0), s(0, 0),
+];
+// Passed pawns become much more valuable in the endgame when there are fewer pieces left to
+// stop them.
+#[rustfmt::skip]
+pub const PASSED_PAWN_BONUS: [Score; 8] = [
+    s(0, 0), s(25, 40), s(28, 45), s(33, 55), s(43, 75), s(58, 105), s(75, 140), s(0, 0),
+];
+
+// The passed pawn factors below are multiplied by how far the pawn has advanced past its 3rd
+// rank, a pawn that is still at home doesn't care about the squares in front of it yet.
+pub const PASSED_PAWN_BLOCKED_PENALTY: Score = s(2, 8);
+pub const PASSED_PAWN_FREE_PATH_BONUS: Score = s(5, 15);
+pub const PASSED_PAWN_OWN_KING_DISTANCE: i32 = 2;
+pub const PASSED_PAWN_ENEMY_KING_DISTANCE: i32 = 5;
+pub const PASSED_PAWN_ROOK_BEHIND_BONUS: Score = s(5, 20);
+// A pawn the enemy king can't catch in a pawn endgame will promote.
+pub const UNSTOPPABLE_PASSER_BONUS: Score = s(0, 400);
+
+pub fn eval_pawn_structure(board: &Board, params: &EvalParams, pawn_hash_table: &mut PawnHashTable) -> Score {
+    let key = get_pawn_key(board);
+    let entry = match pawn_hash_table.get(key) {
+        Some(entry) => *entry,
+        None => {
+            let entry = eval_pawn_hash_entry(board, params, key);
+            pawn_hash_table.insert(entry);
+            entry
+        }
+    };
+
+    entry.score + eval_passed_pawns(board, params, entry.passed_pawns, Color::White)
+        - eval_passed_pawns(board, params, entry.passed_pawns, Color::Black)
+}
+
+// The pawn structure of one side without going through the pawn hash table.
+pub fn eval_pawn_structure_for(board: &Board, params: &EvalParams, color: Color) -> Score {
+    let (score, passed_pawns) = eval_pawns(board, params, color);
+    score + eval_passed_pawns(board, params, passed_pawns, color)
+}
+
+// Every pawn structure term the evaluation applies. The evaluation turns them into scores, the
+// tuner counts them to find out how often each constant is used.
+#[derive(Clone, Copy)]
+pub enum PawnTerm {
+    Stacked,
+    Isolated,
+    Backward,
+    WeakUnopposed,
+    // Indexed by the relative rank of the pawn
+    Supported(usize),
+    Phalanx(usize),
+    CandidatePasser(usize),
+    Passed(usize),
+    // Multiplied by how far the pawn has advanced past its 3rd rank
+    PassedBlocked(i32),
+    PassedFreePath(i32),
+    // King distances to the promotion square, already multiplied by the advancement weight
+    PassedKingDistance { own: i32, enemy: i32 },
+    PassedRookBehind,
+    UnstoppablePasser,
+}
+
+pub fn get_pawn_term_score(params: &EvalParams, term: PawnTerm) -> Score {
+    match term {
+        PawnTerm::Stacked => -params.stacked_pawn_penalty,
+        PawnTerm::Isolated => -params.isolated_pawn_penalty,
+        PawnTerm::Backward => -params.backward_pawn_penalty,
+        PawnTerm::WeakUnopposed => -params.weak_unopposed_pawn_penalty,
+        PawnTerm::Supported(rank) => params.supported_pawn_bonus[rank],
+        PawnTerm::Phalanx(rank) => params.phalanx_pawn_bonus[rank],
+        PawnTerm::CandidatePasser(rank) => params.candidate_passer_bonus[rank],
+        PawnTerm::Passed(rank) => params.passed_pawn_bonus[rank],
+        PawnTerm::PassedBlocked(weight) => -(params.passed_pawn_blocked_penalty * weight),
+        PawnTerm::PassedFreePath(weight) => params.passed_pawn_free_path_bonus * weight,
+        PawnTerm::PassedKingDistance { own, enemy } => Score::new(
+            0,
+            enemy * params.passed_pawn_enemy_king_distance - own * params.passed_pawn_own_king_distance,
+        ),
+        PawnTerm::PassedRookBehind => params.passed_pawn_rook_behind_bonus,
+        PawnTerm::UnstoppablePasser => params.unstoppable_passer_bonus,
+    }
+}
+
+// Everything that only depends on the pawns, this is what gets stored in the pawn hash table.
+fn eval_pawn_hash_entry(board: &Board, params: &EvalParams, key: u64) -> PawnHashEntry {
+    let (white_score, white_passed_pawns) = eval_pawns(board, params, Color::White);
+    let (black_score, black_passed_pawns) = eval_pawns(board, params, Color::Black);
+
+    PawnHashEntry {
+        key,
+        score: white_score - black_score,
+        passed_pawns: white_passed_pawns | black_passed_pawns,
+    }
+}
+
+fn eval_pawns(board: &Board, params: &EvalParams, color: Color) -> (Score, BitBoard) {
+    let mut score = Score::ZERO;
+    let passed_pawns = get_pawn_terms(board, color, |term| score += get_pawn_term_score(params, term));
+    (score, passed_pawns)
+}
+
+fn eval_passed_pawns(board: &Board, params: &EvalParams, passed_pawns: BitBoard, color: Color) -> Score {
+    let mut score = Score::ZERO;
+    get_passed_pawn_terms(board, passed_pawns, color, |term| score += get_pawn_term_score(params, term));
+    score
+}
+
+// Calls `on_term` for every pawn term of `color` that only depends on the pawns and returns the
+// passed pawns.
+pub fn get_pawn_terms(board: &Board, color: Color, mut on_term: impl FnMut(PawnTerm)) -> BitBoard {
+    let own_pawns = board.colored_pieces(color, Piece::Pawn);
+    let enemy_pawns = board.colored_pieces(!color, Piece::Pawn);
+    let enemy_pawn_attacks = get_pawn_attacks_bb(board, !color);
+    let mut passed_pawns = BitBoard::EMPTY;
 
-    let white_pawns = board.colored_pieces(Color::White, Piece::Pawn);
-    let black_pawns = board.colored_pieces(Color::Black, Piece::Pawn);
+    for pawn in own_pawns {
+        let rank = pawn.rank().relative_to(color) as usize;
+        let file_bb = BitBoard(ARR_FILES[pawn.file() as usize]);
+        let neighbor_files = BitBoard(ARR_NEIGHBOR_FILES[pawn.file() as usize]);
+        let in_front = squares_in_front(pawn, color);
+        let stop_square = forward_square(pawn, color);
+
+        let is_opposed = !(enemy_pawns & file_bb & in_front).is_empty();
+        let is_supported = !(own_pawns & get_pawn_attacks(pawn, !color)).is_empty();
+        let is_phalanx = !(own_pawns & neighbor_files & pawn.rank().bitboard()).is_empty();
+        // Neighbours on the same rank or behind, these can still come up to protect the pawn.
+        let helpers = own_pawns & neighbor_files & !in_front;
+        let sentries = enemy_pawns & neighbor_files & in_front;
+
+        // Detect doubled / stacked pawns, only the rear pawn is penalized
+        let is_stacked = !(own_pawns & file_bb & in_front).is_empty();
+        if is_stacked {
+            on_term(PawnTerm::Stacked);
+        }
 
-    // Eval white pawns
-    for pawn in white_pawns {
-        // Detect doubled / stacked pawns
-        let file = pawn as i32 % 8;
-        let pawns_on_file = (BitBoard(ARR_FILES[file as usize]) & white_pawns).len();
-        if pawns_on_file > 1 {
-            score -= (pawns_on_file as i32 - 1) * STACKED_PAWN_PENALTY;
+        // Detect isolated and backward pawns
+        let is_isolated = (own_pawns & neighbor_files).is_empty();
+        let is_backward =
+            !is_isolated && helpers.is_empty() && enemy_pawn_attacks.has(stop_square);
+        if is_isolated {
+            on_term(PawnTerm::Isolated);
+        } else if is_backward {
+            on_term(PawnTerm::Backward);
+        }
+        if (is_isolated || is_backward) && !is_opposed {
+            on_term(PawnTerm::WeakUnopposed);
         }
 
-        // Detect isolated pawns
-        let is_isolated = (BitBoard(ARR_NEIGHBOR_FILES[file as usize]) & white_pawns).len() == 0;
-        if (is_isolated) {
-            score -= ISOLATED_PAWN_PENALTY;
+        // Detect connected pawns
+        if is_supported {
+            on_term(PawnTerm::Supported(rank));
+        }
+        if is_phalanx {
+            on_term(PawnTerm::Phalanx(rank));
         }
 
-        // Detect passed pawns
-        let rank = pawn as i32 / 8;
-        let passed_mask = if rank < 7 {
-            let rank_mask = u64::MAX << ((rank + 1) * 8);
-            (ARR_FILES[file as usize] | ARR_NEIGHBOR_FILES[file as usize]) & rank_mask
-        } else {
-            0
-        };
-
-        if (BitBoard(passed_mask) & black_pawns).len() == 0 {
-            score += PASSED_PAWN_BONUS;
-            score += rank * PASSED_PAWN_RANK_BONUS;
+        // Detect passed pawns and pawns that can become passed
+        if !is_opposed && sentries.is_empty() && !is_stacked {
+            passed_pawns |= pawn.bitboard();
+            on_term(PawnTerm::Passed(rank));
+        } else if !is_opposed && helpers.len() >= sentries.len() {
+            on_term(PawnTerm::CandidatePasser(rank));
         }
     }
 
-    // Eval black pawns
-    for pawn in black_pawns {
-        // Detect doubled / stacked pawns
-        let file = pawn as i32 % 8;
-        let pawns_on_file = (BitBoard(ARR_FILES[file as usize]) & black_pawns).len();
-        if pawns_on_file > 1 {
-            score += (pawns_on_file as i32 - 1) * STACKED_PAWN_PENALTY;
+    passed_pawns
+}
+
+// The passed pawn terms that also depend on the pieces and kings, these can't be cached in the
+// pawn hash table.
+pub fn get_passed_pawn_terms(
+    board: &Board,
+    passed_pawns: BitBoard,
+    color: Color,
+    mut on_term: impl FnMut(PawnTerm),
+) {
+    let pawns = passed_pawns & board.colored_pieces(color, Piece::Pawn);
+    if pawns.is_empty() {
+        return;
+    }
+
+    let occupied = board.occupied();
+    let own_king = board.king(color);
+    let enemy_king = board.king(!color);
+    let own_rooks = board.colored_pieces(color, Piece::Rook);
+    let enemy_attacks = get_attacks_bb(board, !color);
+    let enemy_has_pieces =
+        board.colors(!color) != board.colored_pieces(!color, Piece::Pawn) | enemy_king.bitboard();
+
+    for pawn in pawns {
+        let rank = pawn.rank().relative_to(color) as i32;
+        let weight = (rank - 2).max(0);
+        let file_bb = BitBoard(ARR_FILES[pawn.file() as usize]);
+        let path = file_bb & squares_in_front(pawn, color);
+        let promotion_square = Square::new(pawn.file(), Rank::Eighth.relative_to(color));
+
+        // Blocked pawns and pawns that can walk to promotion without being attacked
+        if occupied.has(forward_square(pawn, color)) {
+            on_term(PawnTerm::PassedBlocked(weight));
+        } else if (path & (occupied | enemy_attacks)).is_empty() {
+            on_term(PawnTerm::PassedFreePath(weight));
         }
 
-        // Detect isolated pawns
-        let is_isolated = (BitBoard(ARR_NEIGHBOR_FILES[file as usize]) & black_pawns).len() == 0;
-        if (is_isolated) {
-            score += ISOLATED_PAWN_PENALTY;
+        // Kings close to the promotion square escort or stop the pawn
+        let own_king_distance = square_distance(own_king, promotion_square);
+        let enemy_king_distance = square_distance(enemy_king, promotion_square);
+        on_term(PawnTerm::PassedKingDistance {
+            own: own_king_distance * weight,
+            enemy: enemy_king_distance * weight,
+        });
+
+        // A rook behind the pawn protects it all the way up the board
+        let behind = file_bb & !path & !pawn.bitboard();
+        if !(get_rook_moves(pawn, occupied) & behind & own_rooks).is_empty() {
+            on_term(PawnTerm::PassedRookBehind);
         }
 
-        // Detect passed pawns
-        let rank = pawn as i32 / 8;
-        let passed_mask = if rank > 0 {
-            // All squares on ranks strictly less than the pawn’s rank.
-            let rank_mask = (1u64 << (rank * 8)) - 1;
-            (ARR_FILES[file as usize] | ARR_NEIGHBOR_FILES[file as usize]) & rank_mask
-        } else {
-            0
-        };
-
-        if (BitBoard(passed_mask) & white_pawns).len() == 0 {
-            score -= PASSED_PAWN_BONUS;
-            score -= (7 - rank) * PASSED_PAWN_RANK_BONUS;
+        // Rule of the square, only the king can stop the pawn when the enemy has no pieces left
+        // (see: https://www.chessprogramming.org/Rule_of_the_Square)
+        if !enemy_has_pieces && (path & occupied).is_empty() {
+            let mut pawn_distance = 7 - rank;
+            if rank == 1 {
+                pawn_distance -= 1;
+            }
+            let mut king_distance = enemy_king_distance;
+            if board.side_to_move() != color {
+                king_distance -= 1;
+            }
+            if king_distance > pawn_distance {
+                on_term(PawnTerm::UnstoppablePasser);
+            }
         }
     }
+}
 
-    score
+// All squares on the ranks in front of `square` from the point of view of `color`.
+pub fn squares_in_front(square: Square, color: Color) -> BitBoard {
+    let rank = square.rank() as u32;
+    match color {
+        Color::White if rank < 7 => BitBoard(u64::MAX << ((rank + 1) * 8)),
+        Color::Black => BitBoard((1u64 << (rank * 8)) - 1),
+        _ => BitBoard::EMPTY,
+    }
+}
+
+// The square a pawn of `color` on `square` moves to, pawns never stand on the last rank.
+fn forward_square(square: Square, color: Color) -> Square {
+    match color {
+        Color::White => square.offset(0, 1),
+        Color::Black => square.offset(0, -1),
+    }
+}
+
+pub fn square_distance(a: Square, b: Square) -> i32 {
+    let file_distance = (a.file() as i32 - b.file() as i32).abs();
+    let rank_distance = (a.rank() as i32 - b.rank() as i32).abs();
+    file_distance.max(rank_distance)
 }
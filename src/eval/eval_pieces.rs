@@ -0,0 +1,253 @@
+﻿use crate::eval::eval_params::EvalParams;
+use crate::eval::eval_pawn_structure::{squares_in_front, ARR_FILES, ARR_NEIGHBOR_FILES};
+use crate::eval::score::{s, Score};
+use cozy_chess::{
+    get_pawn_attacks, get_rook_moves, BitBoard, Board, Color, File, Piece, Rank, Square,
+};
+
+pub const BISHOP_PAIR_BONUS: Score = s(30, 50);
+pub const ROOK_OPEN_FILE_BONUS: Score = s(25, 10);
+pub const ROOK_SEMI_OPEN_FILE_BONUS: Score = s(12, 6);
+pub const QUEEN_OPEN_FILE_BONUS: Score = s(6, 4);
+pub const QUEEN_SEMI_OPEN_FILE_BONUS: Score = s(3, 2);
+pub const ROOK_ON_SEVENTH_BONUS: Score = s(20, 40);
+pub const QUEEN_ON_SEVENTH_BONUS: Score = s(10, 20);
+pub const KNIGHT_OUTPOST_BONUS: Score = s(30, 20);
+pub const BISHOP_OUTPOST_BONUS: Score = s(15, 10);
+// Per own pawn standing on the same square colour as the bishop.
+pub const BAD_BISHOP_PAWN_PENALTY: Score = s(3, 7);
+pub const TRAPPED_BISHOP_PENALTY: Score = s(100, 100);
+pub const TRAPPED_ROOK_PENALTY: Score = s(50, 10);
+
+// A rook that can reach this many squares or fewer next to its own king counts as boxed in.
+const TRAPPED_ROOK_MAX_MOBILITY: u32 = 3;
+
+pub fn eval_pieces(board: &Board, params: &EvalParams) -> Score {
+    eval_pieces_for(board, params, Color::White) - eval_pieces_for(board, params, Color::Black)
+}
+
+pub fn eval_pieces_for(board: &Board, params: &EvalParams, color: Color) -> Score {
+    let mut score = Score::ZERO;
+    get_piece_terms(board, color, |term| score += get_piece_term_score(params, term));
+    score
+}
+
+// Every piece term the evaluation applies. The evaluation turns them into scores, the tuner
+// counts them to find out how often each constant is used.
+#[derive(Clone, Copy)]
+pub enum PieceTerm {
+    BishopPair,
+    // For a rook or a queen
+    OpenFile(Piece),
+    SemiOpenFile(Piece),
+    SeventhRank(Piece),
+    // For a knight or a bishop
+    Outpost(Piece),
+    // Number of own pawns on the squares of the bishop's colour
+    BadBishopPawns(i32),
+    TrappedBishop,
+    TrappedRook,
+}
+
+pub fn get_piece_term_score(params: &EvalParams, term: PieceTerm) -> Score {
+    match term {
+        PieceTerm::BishopPair => params.bishop_pair_bonus,
+        PieceTerm::OpenFile(Piece::Rook) => params.rook_open_file_bonus,
+        PieceTerm::OpenFile(_) => params.queen_open_file_bonus,
+        PieceTerm::SemiOpenFile(Piece::Rook) => params.rook_semi_open_file_bonus,
+        PieceTerm::SemiOpenFile(_) => params.queen_semi_open_file_bonus,
+        PieceTerm::SeventhRank(Piece::Rook) => params.rook_on_seventh_bonus,
+        PieceTerm::SeventhRank(_) => params.queen_on_seventh_bonus,
+        PieceTerm::Outpost(Piece::Knight) => params.knight_outpost_bonus,
+        PieceTerm::Outpost(_) => params.bishop_outpost_bonus,
+        PieceTerm::BadBishopPawns(count) => -(params.bad_bishop_pawn_penalty * count),
+        PieceTerm::TrappedBishop => -params.trapped_bishop_penalty,
+        PieceTerm::TrappedRook => -params.trapped_rook_penalty,
+    }
+}
+
+// Calls `on_term` for every piece term of `color`.
+pub fn get_piece_terms(board: &Board, color: Color, mut on_term: impl FnMut(PieceTerm)) {
+    get_bishop_pair_terms(board, color, &mut on_term);
+    get_rook_and_queen_terms(board, color, &mut on_term);
+    get_outpost_terms(board, color, &mut on_term);
+    get_bad_bishop_terms(board, color, &mut on_term);
+    get_trapped_bishop_terms(board, color, &mut on_term);
+    get_trapped_rook_terms(board, color, &mut on_term);
+}
+
+fn get_bishop_pair_terms(board: &Board, color: Color, on_term: &mut dyn FnMut(PieceTerm)) {
+    if board.colored_pieces(color, Piece::Bishop).len() >= 2 {
+        on_term(PieceTerm::BishopPair);
+    }
+}
+
+// Rooks and queens on open or semi-open files and on the 7th rank. The 7th rank only counts
+// when there are enemy pawns to attack there or the enemy king is cut off on the 8th rank.
+fn get_rook_and_queen_terms(board: &Board, color: Color, on_term: &mut dyn FnMut(PieceTerm)) {
+    let own_pawns = board.colored_pieces(color, Piece::Pawn);
+    let enemy_pawns = board.colored_pieces(!color, Piece::Pawn);
+    let seventh_rank = Rank::Seventh.relative_to(color).bitboard();
+    let eighth_rank = Rank::Eighth.relative_to(color).bitboard();
+    let seventh_rank_matters =
+        !(enemy_pawns & seventh_rank).is_empty() || eighth_rank.has(board.king(!color));
+
+    for piece in [Piece::Rook, Piece::Queen] {
+        for square in board.colored_pieces(color, piece) {
+            let file_bb = BitBoard(ARR_FILES[square.file() as usize]);
+            if (own_pawns & file_bb).is_empty() {
+                on_term(if (enemy_pawns & file_bb).is_empty() {
+                    PieceTerm::OpenFile(piece)
+                } else {
+                    PieceTerm::SemiOpenFile(piece)
+                });
+            }
+
+            if seventh_rank_matters && seventh_rank.has(square) {
+                on_term(PieceTerm::SeventhRank(piece));
+            }
+        }
+    }
+}
+
+// Knights and bishops in the enemy half that are protected by a pawn and can never be chased
+// away by an enemy pawn. (see: https://www.chessprogramming.org/Outposts)
+fn get_outpost_terms(board: &Board, color: Color, on_term: &mut dyn FnMut(PieceTerm)) {
+    let own_pawns = board.colored_pieces(color, Piece::Pawn);
+    let enemy_pawns = board.colored_pieces(!color, Piece::Pawn);
+
+    for piece in [Piece::Knight, Piece::Bishop] {
+        for square in board.colored_pieces(color, piece) {
+            let rank = square.rank().relative_to(color);
+            if rank < Rank::Fourth || rank > Rank::Sixth {
+                continue;
+            }
+
+            let is_supported = !(get_pawn_attacks(square, !color) & own_pawns).is_empty();
+            let attack_span =
+                BitBoard(ARR_NEIGHBOR_FILES[square.file() as usize]) & squares_in_front(square, color);
+
+            if is_supported && (attack_span & enemy_pawns).is_empty() {
+                on_term(PieceTerm::Outpost(piece));
+            }
+        }
+    }
+}
+
+// A bishop is hemmed in by its own pawns when they stand on the squares it moves on.
+fn get_bad_bishop_terms(board: &Board, color: Color, on_term: &mut dyn FnMut(PieceTerm)) {
+    let own_pawns = board.colored_pieces(color, Piece::Pawn);
+
+    for bishop in board.colored_pieces(color, Piece::Bishop) {
+        let bishop_squares = if BitBoard::DARK_SQUARES.has(bishop) {
+            BitBoard::DARK_SQUARES
+        } else {
+            BitBoard::LIGHT_SQUARES
+        };
+
+        on_term(PieceTerm::BadBishopPawns((own_pawns & bishop_squares).len() as i32));
+    }
+}
+
+// A bishop that took the a7 or h7 pawn gets shut in by the pawn on b6 or g6.
+fn get_trapped_bishop_terms(board: &Board, color: Color, on_term: &mut dyn FnMut(PieceTerm)) {
+    let bishops = board.colored_pieces(color, Piece::Bishop);
+    let enemy_pawns = board.colored_pieces(!color, Piece::Pawn);
+
+    for (bishop_square, pawn_square) in [(Square::A7, Square::B6), (Square::H7, Square::G6)] {
+        if bishops.has(bishop_square.relative_to(color))
+            && enemy_pawns.has(pawn_square.relative_to(color))
+        {
+            on_term(PieceTerm::TrappedBishop);
+        }
+    }
+}
+
+// A rook in the corner behind a king that walked over without castling has no way out.
+fn get_trapped_rook_terms(board: &Board, color: Color, on_term: &mut dyn FnMut(PieceTerm)) {
+    let king = board.king(color);
+    let back_rank = Rank::First.relative_to(color);
+    if king.rank() != back_rank {
+        return;
+    }
+
+    let castle_rights = board.castle_rights(color);
+    let is_king_side = king.file() >= File::E;
+    let can_castle = if is_king_side {
+        castle_rights.short.is_some()
+    } else {
+        castle_rights.long.is_some()
+    };
+    if can_castle {
+        return;
+    }
+
+    for rook in board.colored_pieces(color, Piece::Rook) & back_rank.bitboard() {
+        let is_behind_king = if is_king_side {
+            rook.file() > king.file()
+        } else {
+            rook.file() < king.file()
+        };
+        let mobility = (get_rook_moves(rook, board.occupied()) & !board.colors(color)).len();
+
+        if is_behind_king && mobility <= TRAPPED_ROOK_MAX_MOBILITY {
+            on_term(PieceTerm::TrappedRook);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // One group of piece terms for a single side.
+    type PieceTerms = fn(&Board, Color, &mut dyn FnMut(PieceTerm));
+
+    // Hand-picked positions for the piece evaluation terms, expected scores are white-relative.
+    fn piece_cases() -> Vec<(&'static str, &'static str, PieceTerms, Score)> {
+        vec![
+            ("bishop pair", "4k3/8/8/8/8/8/8/2B1KB2 w - - 0 1", get_bishop_pair_terms, BISHOP_PAIR_BONUS),
+            ("bishop pair both sides", "2b1kb2/8/8/8/8/8/8/2B1KB2 w - - 0 1", get_bishop_pair_terms, Score::ZERO),
+            ("black bishop pair", "2b1kb2/8/8/8/8/8/8/2B1K3 w - - 0 1", get_bishop_pair_terms, -BISHOP_PAIR_BONUS),
+            ("rook open file", "6k1/pppp1ppp/8/8/8/8/PPPP1PPP/4RK2 w - - 0 1", get_rook_and_queen_terms, ROOK_OPEN_FILE_BONUS),
+            ("rook semi-open file", "4k3/pppppppp/8/8/8/8/PPP1PPPP/3RK3 w - - 0 1", get_rook_and_queen_terms, ROOK_SEMI_OPEN_FILE_BONUS),
+            ("rook closed file", "4k3/pppppppp/8/8/8/8/PPPPPPPP/3RK3 w - - 0 1", get_rook_and_queen_terms, Score::ZERO),
+            ("queen open file", "4k3/ppp2ppp/8/8/8/8/PPP2PPP/3QK3 w - - 0 1", get_rook_and_queen_terms, QUEEN_OPEN_FILE_BONUS),
+            ("queen semi-open file", "4k3/pppppppp/8/8/8/8/PPP1PPPP/3QK3 w - - 0 1", get_rook_and_queen_terms, QUEEN_SEMI_OPEN_FILE_BONUS),
+            ("black rook semi-open file", "3rk3/ppp1pppp/8/8/8/8/PPPPPPPP/4K3 w - - 0 1", get_rook_and_queen_terms, -ROOK_SEMI_OPEN_FILE_BONUS),
+            ("rook on seventh", "6k1/R4ppp/8/8/8/8/5PPP/6K1 w - - 0 1", get_rook_and_queen_terms, ROOK_OPEN_FILE_BONUS + ROOK_ON_SEVENTH_BONUS),
+            ("rook on empty seventh", "8/R7/6k1/8/8/8/8/6K1 w - - 0 1", get_rook_and_queen_terms, ROOK_OPEN_FILE_BONUS),
+            ("queen on seventh", "6k1/Q4ppp/8/8/8/8/5PPP/6K1 w - - 0 1", get_rook_and_queen_terms, QUEEN_OPEN_FILE_BONUS + QUEEN_ON_SEVENTH_BONUS),
+            ("knight outpost", "4k3/pp3ppp/8/3N4/4P3/8/PP3PPP/4K3 w - - 0 1", get_outpost_terms, KNIGHT_OUTPOST_BONUS),
+            ("knight can be chased", "4k3/ppp2ppp/8/3N4/4P3/8/PP3PPP/4K3 w - - 0 1", get_outpost_terms, Score::ZERO),
+            ("knight unsupported", "4k3/pp3ppp/8/3N4/8/8/PP3PPP/4K3 w - - 0 1", get_outpost_terms, Score::ZERO),
+            ("bishop outpost", "4k3/pp3ppp/8/3B4/4P3/8/PP3PPP/4K3 w - - 0 1", get_outpost_terms, BISHOP_OUTPOST_BONUS),
+            ("black knight outpost", "4k3/pp3ppp/3p4/4n3/8/8/PP4PP/4K3 w - - 0 1", get_outpost_terms, -KNIGHT_OUTPOST_BONUS),
+            ("bad bishop", "4k3/8/8/8/3P4/2P1P3/8/2B1K3 w - - 0 1", get_bad_bishop_terms, -(BAD_BISHOP_PAWN_PENALTY * 3)),
+            ("good bishop", "4k3/8/8/8/3P4/2P1P3/8/3BK3 w - - 0 1", get_bad_bishop_terms, Score::ZERO),
+            ("trapped bishop a7", "4k3/B7/1p6/8/8/8/8/4K3 w - - 0 1", get_trapped_bishop_terms, -TRAPPED_BISHOP_PENALTY),
+            ("free bishop a7", "4k3/B7/8/8/8/8/8/4K3 w - - 0 1", get_trapped_bishop_terms, Score::ZERO),
+            ("trapped bishop h2", "4k3/8/8/8/8/6P1/7b/4K3 w - - 0 1", get_trapped_bishop_terms, TRAPPED_BISHOP_PENALTY),
+            ("trapped rook", "4k3/8/8/8/8/8/5PPP/5KR1 w - - 0 1", get_trapped_rook_terms, -TRAPPED_ROOK_PENALTY),
+            ("rook with castling rights", "4k3/8/8/8/8/8/5PPP/4K2R w K - 0 1", get_trapped_rook_terms, Score::ZERO),
+            ("castled rook", "4k3/8/8/8/8/8/5PPP/5RK1 w - - 0 1", get_trapped_rook_terms, Score::ZERO),
+            ("black trapped rook", "1rk5/ppp5/8/8/8/8/8/4K3 w - - 0 1", get_trapped_rook_terms, TRAPPED_ROOK_PENALTY),
+        ]
+    }
+
+    #[test]
+    fn piece_terms_score_hand_picked_positions() {
+        let params = EvalParams::default();
+
+        for (name, fen, terms, expected) in piece_cases() {
+            let board = Board::from_fen(fen, false).unwrap();
+            let score_for = |color| {
+                let mut score = Score::ZERO;
+                terms(&board, color, &mut |term| score += get_piece_term_score(&params, term));
+                score
+            };
+            let score = score_for(Color::White) - score_for(Color::Black);
+            assert_eq!(score, expected, "{name}: {fen}");
+        }
+    }
+}
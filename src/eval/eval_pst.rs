@@ -1,116 +1,52 @@
-﻿use cozy_chess::{Board, Color, Piece};
-
-pub fn eval_pst_opening(board: &Board) -> i32 {
-    let white_pawns = board.colored_pieces(Color::White, Piece::Pawn);
-    let white_knights = board.colored_pieces(Color::White, Piece::Knight);
-    let white_bishops = board.colored_pieces(Color::White, Piece::Bishop);
-    let white_rooks = board.colored_pieces(Color::White, Piece::Rook);
-    let white_queens = board.colored_pieces(Color::White, Piece::Queen);
-    let black_pawns = board.colored_pieces(Color::Black, Piece::Pawn);
-    let black_knights = board.colored_pieces(Color::Black, Piece::Knight);
-    let black_bishops = board.colored_pieces(Color::Black, Piece::Bishop);
-    let black_rooks = board.colored_pieces(Color::Black, Piece::Rook);
-    let black_queens = board.colored_pieces(Color::Black, Piece::Queen);
-
-    let mut score: i32 = 0;
-
-    for pawn in white_pawns {
-        score += PAWN_OPENING_PST[63 - pawn as usize];
-    }
-    for knight in white_knights {
-        score += KNIGHT_OPENING_PST[63 - knight as usize];
-    }
-    for bishop in white_bishops {
-        score += BISHOP_OPENING_PST[63 - bishop as usize];
-    }
-    for rook in white_rooks {
-        score += ROOK_OPENING_PST[63 - rook as usize];
-    }
-    for queen in white_queens {
-        score += QUEEN_OPENING_PST[63 - queen as usize];
-    }
+﻿use crate::eval::eval_params::EvalParams;
+use crate::eval::score::Score;
+use cozy_chess::{Board, Color, Piece, Square};
 
-    for pawn in black_pawns {
-        score -= PAWN_OPENING_PST[pawn as usize];
-    }
-    for knight in black_knights {
-        score -= KNIGHT_OPENING_PST[knight as usize];
-    }
-    for bishop in black_bishops {
-        score -= BISHOP_OPENING_PST[bishop as usize];
-    }
-    for rook in black_rooks {
-        score -= ROOK_OPENING_PST[rook as usize];
-    }
-    for queen in black_queens {
-        score -= QUEEN_OPENING_PST[queen as usize];
-    }
+pub fn eval_pst(board: &Board, params: &EvalParams) -> Score {
+    eval_pst_for(board, params, Color::White) - eval_pst_for(board, params, Color::Black)
+}
 
-    let white_king = board.king(Color::White);
-    let black_king = board.king(Color::Black);
+pub fn eval_pst_for(board: &Board, params: &EvalParams, color: Color) -> Score {
+    let mut score = Score::ZERO;
 
-    score += KING_OPENING_PST[63 - white_king as usize];
-    score -= KING_OPENING_PST[black_king as usize];
+    for &piece in &Piece::ALL {
+        for square in board.colored_pieces(color, piece) {
+            score += get_pst_score(params, piece, color, square);
+        }
+    }
 
     score
 }
 
-pub fn eval_pst_end(board: &Board) -> i32 {
-    let white_pawns = board.colored_pieces(Color::White, Piece::Pawn);
-    let white_knights = board.colored_pieces(Color::White, Piece::Knight);
-    let white_bishops = board.colored_pieces(Color::White, Piece::Bishop);
-    let white_rooks = board.colored_pieces(Color::White, Piece::Rook);
-    let white_queens = board.colored_pieces(Color::White, Piece::Queen);
-    let black_pawns = board.colored_pieces(Color::Black, Piece::Pawn);
-    let black_knights = board.colored_pieces(Color::Black, Piece::Knight);
-    let black_bishops = board.colored_pieces(Color::Black, Piece::Bishop);
-    let black_rooks = board.colored_pieces(Color::Black, Piece::Rook);
-    let black_queens = board.colored_pieces(Color::Black, Piece::Queen);
-
-    let mut score: i32 = 0;
-
-    for pawn in white_pawns {
-        score += PAWN_OPENING_PST[63 - pawn as usize];
-    }
-    for knight in white_knights {
-        score += KNIGHT_OPENING_PST[63 - knight as usize];
-    }
-    for bishop in white_bishops {
-        score += BISHOP_OPENING_PST[63 - bishop as usize];
-    }
-    for rook in white_rooks {
-        score += ROOK_OPENING_PST[63 - rook as usize];
-    }
-    for queen in white_queens {
-        score += QUEEN_OPENING_PST[63 - queen as usize];
-    }
-
-    for pawn in black_pawns {
-        score -= PAWN_OPENING_PST[pawn as usize];
-    }
-    for knight in black_knights {
-        score -= KNIGHT_OPENING_PST[knight as usize];
-    }
-    for bishop in black_bishops {
-        score -= BISHOP_OPENING_PST[bishop as usize];
-    }
-    for rook in black_rooks {
-        score -= ROOK_OPENING_PST[rook as usize];
+// The tables are written from white's point of view with rank 8 on the first row, so white
+// squares are flipped vertically and black squares can be used as they are.
+pub fn get_pst_index(color: Color, square: Square) -> usize {
+    if color == Color::White {
+        square as usize ^ 56
+    } else {
+        square as usize
     }
-    for queen in black_queens {
-        score -= QUEEN_OPENING_PST[queen as usize];
-    }
-
-    let white_king = board.king(Color::White);
-    let black_king = board.king(Color::Black);
+}
 
-    score += KING_END_PST[white_king as usize];
-    score -= KING_END_PST[black_king as usize];
+pub fn get_pst_score(params: &EvalParams, piece: Piece, color: Color, square: Square) -> Score {
+    params.pst[piece as usize][get_pst_index(color, square)]
+}
 
-    score
+// The tables below packed into scores, indexed by piece and then by square as written.
+pub fn get_default_pst() -> [[Score; 64]; 6] {
+    let tables = [
+        (&PAWN_OPENING_PST, &PAWN_END_PST),
+        (&KNIGHT_OPENING_PST, &KNIGHT_END_PST),
+        (&BISHOP_OPENING_PST, &BISHOP_END_PST),
+        (&ROOK_OPENING_PST, &ROOK_END_PST),
+        (&QUEEN_OPENING_PST, &QUEEN_END_PST),
+        (&KING_OPENING_PST, &KING_END_PST),
+    ];
+
+    tables.map(|(opening_pst, end_pst)| std::array::from_fn(|index| Score::new(opening_pst[index], end_pst[index])))
 }
 
-// Tables taken from https://www.chessprogramming.org/Simplified_Evaluation_Function
+// Opening tables taken from https://www.chessprogramming.org/Simplified_Evaluation_Function
 
 #[rustfmt::skip]
 const PAWN_OPENING_PST: [i32; 64] = [
@@ -184,6 +120,68 @@ const KING_OPENING_PST: [i32; 64] = [
      20, 30, 10,  0,  0, 10, 30, 20
 ];
 
+// Endgame tables for pawns to queens based on https://www.chessprogramming.org/PeSTO%27s_Evaluation_Function
+
+#[rustfmt::skip]
+const PAWN_END_PST: [i32; 64] = [
+      0,   0,   0,   0,   0,   0,   0,   0,
+    178, 173, 158, 134, 147, 132, 165, 187,
+     94, 100,  85,  67,  56,  53,  82,  84,
+     32,  24,  13,   5,  -2,   4,  17,  17,
+     13,   9,  -3,  -7,  -7,  -8,   3,  -1,
+      4,   7,  -6,   1,   0,  -5,  -1,  -8,
+     13,   8,   8,  10,  13,   0,   2,  -7,
+      0,   0,   0,   0,   0,   0,   0,   0
+];
+
+#[rustfmt::skip]
+const KNIGHT_END_PST: [i32; 64] = [
+    -58,-38,-13,-28,-31,-27,-63,-99,
+    -25, -8,-25, -2, -9,-25,-24,-52,
+    -24,-20, 10,  9, -1, -9,-19,-41,
+    -17,  3, 22, 22, 22, 11,  8,-18,
+    -18, -6, 16, 25, 16, 17,  4,-18,
+    -23, -3, -1, 15, 10, -3,-20,-22,
+    -42,-20,-10, -5, -2,-20,-23,-44,
+    -29,-51,-23,-15,-22,-18,-50,-64
+];
+
+#[rustfmt::skip]
+const BISHOP_END_PST: [i32; 64] = [
+    -14,-21,-11, -8, -7, -9,-17,-24,
+     -8, -4,  7,-12, -3,-13, -4,-14,
+      2, -8,  0, -1, -2,  6,  0,  4,
+     -3,  9, 12,  9, 14, 10,  3,  2,
+     -6,  3, 13, 19,  7, 10, -3, -9,
+    -12, -3,  8, 10, 13,  3, -7,-15,
+    -14,-18, -7, -1,  4, -9,-15,-27,
+    -23, -9,-23, -5, -9,-16, -5,-17
+];
+
+#[rustfmt::skip]
+const ROOK_END_PST: [i32; 64] = [
+     13, 10, 18, 15, 12, 12,  8,  5,
+     11, 13, 13, 11, -3,  3,  8,  3,
+      7,  7,  7,  5,  4, -3, -5, -3,
+      4,  3, 13,  1,  2,  1, -1,  2,
+      3,  5,  8,  4, -5, -6, -8,-11,
+     -4,  0, -5, -1, -7,-12, -8,-16,
+     -6, -6,  0,  2, -9, -9,-11, -3,
+     -9,  2,  3, -1, -5,-13,  4,-20
+];
+
+#[rustfmt::skip]
+const QUEEN_END_PST: [i32; 64] = [
+     -9, 22, 22, 27, 27, 19, 10, 20,
+    -17, 20, 32, 41, 58, 25, 30,  0,
+    -20,  6,  9, 49, 47, 35, 19,  9,
+      3, 22, 24, 45, 57, 40, 57, 36,
+    -18, 28, 19, 47, 31, 34, 39, 23,
+    -16,-27, 15,  6,  9, 17, 10,  5,
+    -22,-23,-30,-16,-16,-23,-36,-32,
+    -33,-28,-22,-43, -5,-32,-20,-41
+];
+
 #[rustfmt::skip]
 const KING_END_PST: [i32; 64] = [
     -50,-40,-30,-20,-20,-30,-40,-50,
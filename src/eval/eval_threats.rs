@@ -0,0 +1,115 @@
+﻿use crate::eval::attacks::{get_attacks_bb, get_pawn_attacks_bb};
+use crate::eval::eval_params::EvalParams;
+use crate::eval::score::{s, Score};
+use cozy_chess::{
+    get_bishop_moves, get_knight_moves, get_pawn_attacks, get_rook_moves, BitBoard, Board, Color,
+    Piece, Rank, Square,
+};
+
+// Indexed by the piece that is attacked, pawns and kings are never counted as victims.
+#[rustfmt::skip]
+pub const THREAT_BY_PAWN: [Score; 5] = [s(0, 0), s(55, 35), s(55, 35), s(75, 45), s(80, 50)];
+#[rustfmt::skip]
+pub const THREAT_BY_MINOR: [Score; 5] = [s(0, 0), s(25, 25), s(25, 25), s(45, 30), s(45, 35)];
+pub const THREAT_BY_ROOK_ON_QUEEN: Score = s(45, 15);
+// A piece that is attacked and not defended at all.
+pub const HANGING_PIECE_BONUS: Score = s(30, 20);
+// A pawn that can be pushed safely to a square where it attacks a piece.
+pub const PAWN_PUSH_THREAT_BONUS: Score = s(15, 10);
+
+pub fn eval_threats(board: &Board, params: &EvalParams) -> Score {
+    eval_threats_for(board, params, Color::White) - eval_threats_for(board, params, Color::Black)
+}
+
+// Threats made by `color` against the pieces of the other side.
+pub fn eval_threats_for(board: &Board, params: &EvalParams, color: Color) -> Score {
+    let mut score = Score::ZERO;
+    get_threat_terms(board, color, |term| score += get_threat_term_score(params, term));
+    score
+}
+
+// Every threat term the evaluation applies, each with the number of pieces it counts. The
+// evaluation turns them into scores, the tuner counts them to find out how often each constant
+// is used.
+#[derive(Clone, Copy)]
+pub enum ThreatTerm {
+    // Indexed by the attacked piece
+    ByPawn(usize),
+    ByMinor(usize),
+    RookOnQueen(i32),
+    Hanging(i32),
+    PawnPush(i32),
+}
+
+pub fn get_threat_term_score(params: &EvalParams, term: ThreatTerm) -> Score {
+    match term {
+        ThreatTerm::ByPawn(victim) => params.threat_by_pawn[victim],
+        ThreatTerm::ByMinor(victim) => params.threat_by_minor[victim],
+        ThreatTerm::RookOnQueen(count) => params.threat_by_rook_on_queen * count,
+        ThreatTerm::Hanging(count) => params.hanging_piece_bonus * count,
+        ThreatTerm::PawnPush(count) => params.pawn_push_threat_bonus * count,
+    }
+}
+
+// Calls `on_term` for the threats made by `color` against the pieces of the other side.
+pub fn get_threat_terms(board: &Board, color: Color, mut on_term: impl FnMut(ThreatTerm)) {
+    let enemy = !color;
+    let occupied = board.occupied();
+    let enemy_pieces = board.colors(enemy) & !board.pieces(Piece::Pawn) & !board.pieces(Piece::King);
+    if enemy_pieces.is_empty() {
+        return;
+    }
+
+    let pawn_attacks = get_pawn_attacks_bb(board, color);
+    let mut minor_attacks = BitBoard::EMPTY;
+    for knight in board.colored_pieces(color, Piece::Knight) {
+        minor_attacks |= get_knight_moves(knight);
+    }
+    for bishop in board.colored_pieces(color, Piece::Bishop) {
+        minor_attacks |= get_bishop_moves(bishop, occupied);
+    }
+    let mut rook_attacks = BitBoard::EMPTY;
+    for rook in board.colored_pieces(color, Piece::Rook) {
+        rook_attacks |= get_rook_moves(rook, occupied);
+    }
+    let our_attacks = get_attacks_bb(board, color);
+    let enemy_attacks = get_attacks_bb(board, enemy);
+
+    // Pieces attacked by pawns and pieces attacked by minors
+    for victim in enemy_pieces & pawn_attacks {
+        on_term(ThreatTerm::ByPawn(piece_index(board, victim)));
+    }
+    for victim in enemy_pieces & minor_attacks {
+        on_term(ThreatTerm::ByMinor(piece_index(board, victim)));
+    }
+
+    // Queens attacked by rooks
+    let enemy_queens = board.colored_pieces(enemy, Piece::Queen);
+    on_term(ThreatTerm::RookOnQueen((enemy_queens & rook_attacks).len() as i32));
+
+    // Pieces that are attacked and not defended
+    on_term(ThreatTerm::Hanging((enemy_pieces & our_attacks & !enemy_attacks).len() as i32));
+
+    // Pawn pushes that attack a piece, the pushed pawn must not just get taken
+    let own_pawns = board.colored_pieces(color, Piece::Pawn);
+    let (single_pushes, double_pushes) = if color == Color::White {
+        let single_pushes = BitBoard(own_pawns.0 << 8) & !occupied;
+        let double_pushes = BitBoard((single_pushes & Rank::Third.bitboard()).0 << 8) & !occupied;
+        (single_pushes, double_pushes)
+    } else {
+        let single_pushes = BitBoard(own_pawns.0 >> 8) & !occupied;
+        let double_pushes = BitBoard((single_pushes & Rank::Sixth.bitboard()).0 >> 8) & !occupied;
+        (single_pushes, double_pushes)
+    };
+    let safe_squares = !get_pawn_attacks_bb(board, enemy) & (our_attacks | !enemy_attacks);
+
+    let mut push_attacks = BitBoard::EMPTY;
+    for square in (single_pushes | double_pushes) & safe_squares {
+        push_attacks |= get_pawn_attacks(square, color);
+    }
+    on_term(ThreatTerm::PawnPush((enemy_pieces & push_attacks & !pawn_attacks).len() as i32));
+}
+
+fn piece_index(board: &Board, square: Square) -> usize {
+    board.piece_on(square).map_or(0, |piece| piece as usize)
+}
@@ -0,0 +1,102 @@
+﻿use crate::eval::endgame::{get_scale_factor, probe_endgame_eval, SCALE_FACTOR_NORMAL};
+use crate::eval::eval::{eval, eval_tapered};
+use crate::eval::eval_count_material::eval_count_material_for;
+use crate::eval::eval_imbalance::eval_imbalance_for;
+use crate::eval::eval_is_mate::eval_is_mate;
+use crate::eval::eval_king_safety::eval_king_safety_for;
+use crate::eval::eval_mobility::eval_mobility_for;
+use crate::eval::eval_params::EvalParams;
+use crate::eval::eval_pawn_structure::eval_pawn_structure_for;
+use crate::eval::eval_pieces::eval_pieces_for;
+use crate::eval::eval_pst::eval_pst_for;
+use crate::eval::eval_threats::eval_threats_for;
+use crate::eval::game_phase::get_game_phase;
+use crate::eval::material_hash_table::{MaterialHashTable, MATERIAL_HASH_TABLE_SIZE};
+use crate::eval::pawn_hash_table::{PawnHashTable, PAWN_HASH_TABLE_SIZE};
+use crate::eval::score::Score;
+use cozy_chess::{Board, Color};
+
+// A term of the handcrafted evaluation for one side.
+type EvalTermFor = fn(&Board, &EvalParams, Color) -> Score;
+
+// Every term eval_tapered adds up, in the same order and with the same weight. A new term needs a
+// row here as well to show up in the trace.
+const EVAL_TERMS: [(&str, EvalTermFor, i32); 8] = [
+    ("Material", eval_count_material_for, 1),
+    ("Imbalance", eval_imbalance_for, 1),
+    ("PST", eval_pst_for, 2),
+    ("Pawns", eval_pawn_structure_for, 1),
+    ("Mobility", eval_mobility_for, 1),
+    ("King safety", eval_king_safety_for, 1),
+    ("Pieces", eval_pieces_for, 1),
+    ("Threats", eval_threats_for, 1),
+];
+
+pub struct EvalTraceRow {
+    pub name: &'static str,
+    pub white: Score,
+    pub black: Score,
+    pub total: Score, // What the term adds to the white relative evaluation
+}
+
+// Everything that goes into the handcrafted evaluation of a position, all white relative.
+pub struct EvalTrace {
+    pub rows: Vec<EvalTraceRow>,
+    pub total: Score,
+    pub phase: i32,
+    pub scale_factor: i32,
+    pub tapered: i32, // The total blended by the game phase after scaling the endgame half
+    pub mate_score: Option<i32>,
+    pub endgame_score: Option<i32>, // Set when a specialised endgame evaluator replaces the terms
+    pub eval: i32, // What eval returns for the position
+}
+
+pub fn get_eval_trace(board: &Board, params: &EvalParams) -> EvalTrace {
+    let mut rows = Vec::new();
+
+    for (name, term, divisor) in EVAL_TERMS {
+        let white = term(board, params, Color::White);
+        let black = term(board, params, Color::Black);
+        // Weighted terms are divided after taking the difference like eval_tapered does, so the
+        // total can be off by one from the weighted sides.
+        rows.push(EvalTraceRow {
+            name,
+            white: white / divisor,
+            black: black / divisor,
+            total: (white - black) / divisor,
+        });
+    }
+
+    let (white_tempo, black_tempo) = if board.side_to_move() == Color::White {
+        (params.tempo, Score::ZERO)
+    } else {
+        (Score::ZERO, params.tempo)
+    };
+    rows.push(EvalTraceRow {
+        name: "Tempo",
+        white: white_tempo,
+        black: black_tempo,
+        total: white_tempo - black_tempo,
+    });
+
+    let mut pawn_hash_table = PawnHashTable::new(PAWN_HASH_TABLE_SIZE);
+    let mut material_hash_table = MaterialHashTable::new(MATERIAL_HASH_TABLE_SIZE);
+    let total = eval_tapered(board, params, &mut pawn_hash_table, &mut material_hash_table);
+    debug_assert_eq!(rows.iter().fold(Score::ZERO, |sum, row| sum + row.total), total);
+
+    let phase = get_game_phase(board);
+    let scale_factor = get_scale_factor(board, total.eg());
+    let scaled = Score::new(total.mg(), total.eg() * scale_factor / SCALE_FACTOR_NORMAL);
+    let mate_score = eval_is_mate(board, 0);
+
+    EvalTrace {
+        rows,
+        total,
+        phase,
+        scale_factor,
+        tapered: scaled.taper(phase),
+        mate_score: (mate_score != 0).then_some(mate_score),
+        endgame_score: probe_endgame_eval(board),
+        eval: eval(board, params, &mut pawn_hash_table, &mut material_hash_table, 0),
+    }
+}
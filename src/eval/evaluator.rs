@@ -0,0 +1,47 @@
+﻿use cozy_chess::{Board, Move};
+
+// A static evaluation the search can run with. Scores are centipawns from the point of view of
+// the side to move like eval::evaluate, and have to include mate scores for checkmated positions.
+// The search reports every move it makes and takes back, so incremental evaluators can follow the
+// tree.
+pub trait Evaluator {
+    fn name(&self) -> &'static str;
+
+    fn evaluate(&mut self, board: &Board, distance_from_root: u8) -> i32;
+
+    // May return an estimate when the score is far outside the side to move relative window
+    // alpha..beta. Evaluators without a cheap estimate return the exact score.
+    fn evaluate_lazy(&mut self, board: &Board, distance_from_root: u8, _alpha: i32, _beta: i32) -> i32 {
+        self.evaluate(board, distance_from_root)
+    }
+
+    // `new_board` is `board` after `mv`.
+    fn on_make(&mut self, _board: &Board, _mv: Move, _new_board: &Board) {}
+
+    // Takes back the last move passed to on_make.
+    fn on_unmake(&mut self) {}
+}
+
+// The evaluators the Evaluator UCI option can pick from.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum EvaluatorKind {
+    Classical,
+    Material,
+    Nnue,
+}
+
+impl EvaluatorKind {
+    pub const ALL: [EvaluatorKind; 3] = [EvaluatorKind::Classical, EvaluatorKind::Material, EvaluatorKind::Nnue];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            EvaluatorKind::Classical => "Classical",
+            EvaluatorKind::Material => "Material",
+            EvaluatorKind::Nnue => "NNUE",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|kind| kind.name().eq_ignore_ascii_case(name))
+    }
+}
@@ -0,0 +1,210 @@
+﻿use cozy_chess::{get_king_moves, get_pawn_attacks, Board, Color, File, Piece, Rank, Square};
+use std::sync::OnceLock;
+
+// King and pawn against king bitbase, one bit per position telling whether the side with the pawn
+// wins. The pawn is always white and mirrored to the a-d files, so a position is indexed by the
+// side to move, both kings and one of the 24 pawn squares.
+// (see: https://www.chessprogramming.org/KPK)
+const MAX_INDEX: usize = 2 * 24 * 64 * 64;
+
+// Results of the positions while the bitbase is generated, a bitset so several results can be
+// combined with `|` while classifying.
+const INVALID: u8 = 0;
+const UNKNOWN: u8 = 1;
+const DRAW: u8 = 2;
+const WIN: u8 = 4;
+
+static KPK_BITBASE: OnceLock<Vec<u64>> = OnceLock::new();
+
+fn index(side_to_move: Color, black_king: Square, white_king: Square, pawn: Square) -> usize {
+    white_king as usize
+        | (black_king as usize) << 6
+        | (side_to_move as usize) << 12
+        | (pawn.file() as usize) << 13
+        | (Rank::Seventh as usize - pawn.rank() as usize) << 15
+}
+
+pub fn is_kpk(board: &Board) -> bool {
+    board.occupied().len() == 3 && board.pieces(Piece::Pawn).len() == 1
+}
+
+// Whether the side with the pawn wins, `board` must have nothing but the two kings and one pawn.
+pub fn probe_kpk(board: &Board) -> bool {
+    let pawn_square = board.pieces(Piece::Pawn).next_square().unwrap();
+    let strong_side = board.color_on(pawn_square).unwrap();
+
+    // Look at the position from white's side with the pawn on the queen side.
+    let normalize = |square: Square| {
+        let square = square.relative_to(strong_side);
+        if pawn_square.relative_to(strong_side).file() >= File::E {
+            square.flip_file()
+        } else {
+            square
+        }
+    };
+    let side_to_move = if board.side_to_move() == strong_side {
+        Color::White
+    } else {
+        Color::Black
+    };
+
+    let index = index(
+        side_to_move,
+        normalize(board.king(!strong_side)),
+        normalize(board.king(strong_side)),
+        normalize(pawn_square),
+    );
+
+    let bitbase = KPK_BITBASE.get_or_init(generate_kpk_bitbase);
+    bitbase[index / 64] & (1 << (index % 64)) != 0
+}
+
+// Retrograde analysis: positions that are mate in one step or a dead draw are known right away,
+// the others are resolved by looking at the positions one move away until nothing changes.
+fn generate_kpk_bitbase() -> Vec<u64> {
+    let mut results: Vec<u8> = (0..MAX_INDEX).map(init_position).collect();
+
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for index in 0..MAX_INDEX {
+            if results[index] == UNKNOWN {
+                let result = classify_position(&results, index);
+                if result != UNKNOWN {
+                    results[index] = result;
+                    changed = true;
+                }
+            }
+        }
+    }
+
+    let mut bitbase = vec![0u64; MAX_INDEX / 64];
+    for (index, &result) in results.iter().enumerate() {
+        if result == WIN {
+            bitbase[index / 64] |= 1 << (index % 64);
+        }
+    }
+
+    bitbase
+}
+
+fn decode_index(index: usize) -> (Color, Square, Square, Square) {
+    let white_king = Square::index(index & 0x3F);
+    let black_king = Square::index((index >> 6) & 0x3F);
+    let side_to_move = Color::index((index >> 12) & 0x01);
+    let pawn = Square::new(
+        File::index((index >> 13) & 0x03),
+        Rank::index(Rank::Seventh as usize - ((index >> 15) & 0x07)),
+    );
+
+    (side_to_move, black_king, white_king, pawn)
+}
+
+fn init_position(index: usize) -> u8 {
+    let (side_to_move, black_king, white_king, pawn) = decode_index(index);
+    let white_king_moves = get_king_moves(white_king);
+    let black_king_moves = get_king_moves(black_king);
+    let pawn_attacks = get_pawn_attacks(pawn, Color::White);
+
+    if white_king_moves.has(black_king)
+        || white_king == black_king
+        || white_king == pawn
+        || black_king == pawn
+        || (side_to_move == Color::White && pawn_attacks.has(black_king))
+    {
+        return INVALID;
+    }
+
+    if side_to_move == Color::White && pawn.rank() == Rank::Seventh {
+        // The pawn promotes and the queen can't be taken.
+        let promotion_square = pawn.offset(0, 1);
+        if white_king != promotion_square
+            && black_king != promotion_square
+            && (!get_king_moves(black_king).has(promotion_square)
+                || white_king_moves.has(promotion_square))
+        {
+            return WIN;
+        }
+    }
+
+    if side_to_move == Color::Black {
+        // Stalemate, or the black king takes the undefended pawn.
+        let safe_squares = black_king_moves & !(white_king_moves | pawn_attacks);
+        if safe_squares.is_empty()
+            || (black_king_moves.has(pawn) && !white_king_moves.has(pawn))
+        {
+            return DRAW;
+        }
+    }
+
+    UNKNOWN
+}
+
+// White wins if any move leads to a win, black draws if any move leads to a draw.
+fn classify_position(results: &[u8], index: usize) -> u8 {
+    let (side_to_move, black_king, white_king, pawn) = decode_index(index);
+    let (good, bad) = if side_to_move == Color::White {
+        (WIN, DRAW)
+    } else {
+        (DRAW, WIN)
+    };
+    let mut result = INVALID;
+
+    if side_to_move == Color::White {
+        for square in get_king_moves(white_king) {
+            result |= results[self::index(Color::Black, black_king, square, pawn)];
+        }
+
+        if pawn.rank() < Rank::Seventh {
+            let push_square = pawn.offset(0, 1);
+            result |= results[self::index(Color::Black, black_king, white_king, push_square)];
+
+            let double_push_square = pawn.offset(0, 2);
+            if pawn.rank() == Rank::Second && push_square != white_king && push_square != black_king {
+                result |= results[self::index(Color::Black, black_king, white_king, double_push_square)];
+            }
+        }
+    } else {
+        for square in get_king_moves(black_king) {
+            result |= results[self::index(Color::White, square, white_king, pawn)];
+        }
+    }
+
+    if result & good != 0 {
+        good
+    } else if result & UNKNOWN != 0 {
+        UNKNOWN
+    } else {
+        bad
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Known KPK positions and whether the side with the pawn wins, includes black pawns and pawns on
+    // the king side to check that positions are mirrored correctly.
+    const KPK_POSITIONS: [(&str, bool); 12] = [
+        ("4k3/8/4K3/4P3/8/8/8/8 w - - 0 1", true),
+        ("4k3/8/4K3/4P3/8/8/8/8 b - - 0 1", true),
+        ("4k3/4P3/4K3/8/8/8/8/8 w - - 0 1", true),
+        ("4k3/4P3/4K3/8/8/8/8/8 b - - 0 1", false),
+        ("k7/8/1K6/P7/8/8/8/8 w - - 0 1", false),
+        ("7k/8/6K1/7P/8/8/8/8 w - - 0 1", false),
+        ("8/8/8/8/8/8/4P3/k3K3 w - - 0 1", true),
+        ("6k1/8/6K1/6P1/8/8/8/8 w - - 0 1", true),
+        ("8/8/8/8/4p3/4k3/8/4K3 b - - 0 1", true),
+        ("8/8/8/8/4p3/4k3/8/4K3 w - - 0 1", true),
+        ("8/8/8/8/8/4k3/4p3/4K3 w - - 0 1", false),
+        ("7K/8/8/8/8/8/P7/k7 b - - 0 1", false),
+    ];
+
+    #[test]
+    fn bitbase_matches_known_positions() {
+        for (fen, expected_win) in KPK_POSITIONS {
+            let board = Board::from_fen(fen, false).unwrap();
+            assert_eq!(probe_kpk(&board), expected_win, "{fen}");
+        }
+    }
+}
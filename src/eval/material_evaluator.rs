@@ -0,0 +1,37 @@
+﻿use crate::eval::eval::to_side_to_move;
+use crate::eval::eval_count_material::eval_count_material;
+use crate::eval::eval_is_mate::eval_is_mate;
+use crate::eval::eval_params::EvalParams;
+use crate::eval::evaluator::{Evaluator, EvaluatorKind};
+use crate::eval::game_phase::get_game_phase;
+use cozy_chess::Board;
+use std::sync::Arc;
+
+// Only counts material with the piece values of the eval parameters, a baseline to measure the
+// other evaluators against.
+pub struct MaterialEvaluator {
+    params: Arc<EvalParams>,
+}
+
+impl MaterialEvaluator {
+    pub fn new(params: Arc<EvalParams>) -> Self {
+        MaterialEvaluator { params }
+    }
+}
+
+impl Evaluator for MaterialEvaluator {
+    fn name(&self) -> &'static str {
+        EvaluatorKind::Material.name()
+    }
+
+    fn evaluate(&mut self, board: &Board, distance_from_root: u8) -> i32 {
+        let mate_score = eval_is_mate(board, distance_from_root);
+        let score = if mate_score != 0 {
+            mate_score
+        } else {
+            eval_count_material(board, &self.params).taper(get_game_phase(board))
+        };
+
+        to_side_to_move(score, board)
+    }
+}
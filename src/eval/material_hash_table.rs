@@ -0,0 +1,42 @@
+﻿use crate::eval::score::Score;
+
+// Number of entries, must be a power of two. Only a handful of material combinations come up in
+// a search.
+pub const MATERIAL_HASH_TABLE_SIZE: usize = 1 << 10;
+
+pub struct MaterialHashTable {
+    table: Vec<Option<MaterialHashEntry>>,
+    table_size: usize,
+}
+
+impl MaterialHashTable {
+    pub fn new(table_size: usize) -> Self {
+        MaterialHashTable {
+            table: vec![None; table_size],
+            table_size,
+        }
+    }
+
+    pub fn get(&self, key: u64) -> Option<&MaterialHashEntry> {
+        self.table[self.index(key)].as_ref().filter(|entry| entry.key == key)
+    }
+
+    // Always replace, an entry is cheap to recompute.
+    pub fn insert(&mut self, entry: MaterialHashEntry) {
+        let index = self.index(entry.key);
+        self.table[index] = Some(entry);
+    }
+
+    // Material keys are piece counts packed into a few bits each, they have to be mixed before
+    // the low bits make a good index.
+    fn index(&self, key: u64) -> usize {
+        (key.wrapping_mul(0x9E37_79B9_7F4A_7C15) >> 32) as usize & (self.table_size - 1)
+    }
+}
+
+// Everything that only depends on the material signature from get_material_key.
+#[derive(Clone, Copy)]
+pub struct MaterialHashEntry {
+    pub key: u64,
+    pub imbalance: Score, // White relative
+}
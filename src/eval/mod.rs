@@ -1,6 +1,24 @@
 ﻿pub mod eval_count_material;
+#[allow(clippy::module_inception)]
 pub mod eval;
-mod eval_is_mate;
-mod game_phase;
-mod eval_pst;
-mod eval_pawn_structure;
\ No newline at end of file
+pub mod eval_is_mate;
+pub mod game_phase;
+pub mod eval_pst;
+pub mod eval_pawn_structure;
+pub mod score;
+mod attacks;
+pub mod eval_mobility;
+pub mod eval_king_safety;
+pub mod eval_pieces;
+pub mod pawn_hash_table;
+pub mod eval_threats;
+pub mod endgame;
+pub mod kpk_bitbase;
+pub mod eval_params;
+pub mod eval_trace;
+pub mod eval_hash_table;
+pub mod eval_imbalance;
+pub mod material_hash_table;
+pub mod evaluator;
+pub mod classical_evaluator;
+pub mod material_evaluator;
\ No newline at end of file
@@ -0,0 +1,72 @@
+﻿use crate::eval::score::Score;
+use crate::util::random::xorshift64;
+use cozy_chess::{BitBoard, Board, Color, Piece};
+
+// Number of entries, must be a power of two. Pawn structures change rarely during a search so a
+// small table already gets almost every lookup.
+pub const PAWN_HASH_TABLE_SIZE: usize = 1 << 14;
+
+// Random keys for a pawn of each colour on each square, generated at compile time with
+// xorshift64 so the keys are the same on every run.
+const PAWN_KEYS: [[u64; 64]; 2] = generate_pawn_keys();
+
+const fn generate_pawn_keys() -> [[u64; 64]; 2] {
+    let mut keys = [[0; 64]; 2];
+    let mut state: u64 = 0x9E37_79B9_7F4A_7C15;
+    let mut index = 0;
+
+    while index < 128 {
+        state = xorshift64(state);
+        keys[index / 64][index % 64] = state;
+        index += 1;
+    }
+
+    keys
+}
+
+// Zobrist key of only the pawns, positions with the same pawns share a pawn hash entry.
+pub fn get_pawn_key(board: &Board) -> u64 {
+    let mut key = 0;
+
+    for color in [Color::White, Color::Black] {
+        for pawn in board.colored_pieces(color, Piece::Pawn) {
+            key ^= PAWN_KEYS[color as usize][pawn as usize];
+        }
+    }
+
+    key
+}
+
+pub struct PawnHashTable {
+    table: Vec<Option<PawnHashEntry>>,
+    table_size: usize,
+}
+
+impl PawnHashTable {
+    pub fn new(table_size: usize) -> Self {
+        PawnHashTable {
+            table: vec![None; table_size],
+            table_size,
+        }
+    }
+
+    pub fn get(&self, key: u64) -> Option<&PawnHashEntry> {
+        let index = (key as usize) & (self.table_size - 1);
+        self.table[index].as_ref().filter(|entry| entry.key == key)
+    }
+
+    // Always replace, an entry is cheap to recompute.
+    pub fn insert(&mut self, entry: PawnHashEntry) {
+        let index = (entry.key as usize) & (self.table_size - 1);
+        self.table[index] = Some(entry);
+    }
+}
+
+// The white relative score of everything that only depends on the pawns, and the passed pawns
+// of both sides so the terms that also look at the pieces don't have to find them again.
+#[derive(Clone, Copy)]
+pub struct PawnHashEntry {
+    pub key: u64,
+    pub score: Score,
+    pub passed_pawns: BitBoard,
+}
@@ -0,0 +1,86 @@
+﻿use std::ops::{Add, AddAssign, Div, Mul, Neg, Sub, SubAssign};
+
+// A middlegame and an endgame value packed into one integer, the endgame value in the low 16 bits
+// and the middlegame value in the high 16 bits. Adding packed scores adds both halves at once.
+// (see: https://www.chessprogramming.org/Tapered_Eval)
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Score(i32);
+
+// Shorthand for writing tables of scores.
+pub const fn s(mg: i32, eg: i32) -> Score {
+    Score::new(mg, eg)
+}
+
+impl Score {
+    pub const ZERO: Score = Score(0);
+
+    pub const fn new(mg: i32, eg: i32) -> Self {
+        Score(mg.wrapping_shl(16).wrapping_add(eg))
+    }
+
+    pub const fn mg(self) -> i32 {
+        (self.0.wrapping_add(0x8000) >> 16) as i16 as i32
+    }
+
+    pub const fn eg(self) -> i32 {
+        self.0 as i16 as i32
+    }
+
+    // Blends the two halves by the game phase from `get_game_phase`, 0 = opening, 256 = endgame.
+    pub fn taper(self, phase: i32) -> i32 {
+        (self.mg() * (256 - phase) + self.eg() * phase) / 256
+    }
+}
+
+impl Add for Score {
+    type Output = Score;
+
+    fn add(self, other: Score) -> Score {
+        Score(self.0.wrapping_add(other.0))
+    }
+}
+
+impl AddAssign for Score {
+    fn add_assign(&mut self, other: Score) {
+        *self = *self + other;
+    }
+}
+
+impl Sub for Score {
+    type Output = Score;
+
+    fn sub(self, other: Score) -> Score {
+        Score(self.0.wrapping_sub(other.0))
+    }
+}
+
+impl SubAssign for Score {
+    fn sub_assign(&mut self, other: Score) {
+        *self = *self - other;
+    }
+}
+
+impl Neg for Score {
+    type Output = Score;
+
+    fn neg(self) -> Score {
+        Score(self.0.wrapping_neg())
+    }
+}
+
+impl Mul<i32> for Score {
+    type Output = Score;
+
+    fn mul(self, factor: i32) -> Score {
+        Score(self.0.wrapping_mul(factor))
+    }
+}
+
+// Division can't be done on the packed value, the halves are divided separately.
+impl Div<i32> for Score {
+    type Output = Score;
+
+    fn div(self, divisor: i32) -> Score {
+        Score::new(self.mg() / divisor, self.eg() / divisor)
+    }
+}
@@ -1,10 +1,95 @@
-﻿use crate::uci::uci_loop::do_uci_loop;
+﻿use crate::datagen::datagen::{run_datagen, DatagenOptions};
+use crate::eval::eval_params::EvalParams;
+use crate::tablebase::tb_generate::generate_tablebases;
+use crate::tablebase::tb_position::MAX_TB_PIECES;
+use crate::tune::tuner::{run_tune, TuneOptions};
+use crate::uci::uci_loop::do_uci_loop;
+use crate::wdl::wdl_fit::{run_fit_wdl, FitWdlOptions};
+use std::sync::Arc;
 
 mod uci;
 mod perft;
 mod search;
 mod eval;
+mod tablebase;
+mod nnue;
+mod datagen;
+mod tune;
+mod wdl;
+mod util;
 
 fn main() {
-    do_uci_loop();
+    let mut args: Vec<String> = std::env::args().collect();
+
+    // --eval-params <path> can go anywhere and sets the eval weights of every mode.
+    let eval_params = match args.iter().position(|arg| arg == "--eval-params") {
+        Some(index) => {
+            let Some(path) = args.get(index + 1).cloned() else {
+                eprintln!("--eval-params needs a path");
+                std::process::exit(1);
+            };
+            args.drain(index..index + 2);
+
+            EvalParams::load(&path).unwrap_or_else(|error| {
+                eprintln!("failed to load eval parameters from {path}: {error}");
+                std::process::exit(1);
+            })
+        }
+        None => EvalParams::default(),
+    };
+
+    match args.get(1).map(String::as_str) {
+        // gen-tb <path> [max pieces]
+        Some("gen-tb") => {
+            let path = args.get(2).map_or("tablebases", String::as_str);
+            let max_pieces = args
+                .get(3)
+                .and_then(|pieces| pieces.parse::<usize>().ok())
+                .unwrap_or(MAX_TB_PIECES);
+
+            if let Err(error) = generate_tablebases(path, max_pieces) {
+                eprintln!("gen-tb failed: {error}");
+                std::process::exit(1);
+            }
+        }
+        // datagen <output path> [option value]..., see DatagenOptions for the options
+        Some("datagen") => {
+            let mut options = DatagenOptions::from_args(&args[2..]).unwrap_or_else(|error| {
+                eprintln!("datagen: {error}");
+                std::process::exit(1);
+            });
+            options.eval_params = Arc::new(eval_params);
+
+            if let Err(error) = run_datagen(&options) {
+                eprintln!("datagen failed: {error}");
+                std::process::exit(1);
+            }
+        }
+        // tune <positions file> [option value]..., see TuneOptions for the options
+        Some("tune") => {
+            let mut options = TuneOptions::from_args(&args[2..]).unwrap_or_else(|error| {
+                eprintln!("tune: {error}");
+                std::process::exit(1);
+            });
+            options.eval_params = eval_params;
+
+            if let Err(error) = run_tune(&options) {
+                eprintln!("tune failed: {error}");
+                std::process::exit(1);
+            }
+        }
+        // fit-wdl <datagen text file> [option value]..., see FitWdlOptions for the options
+        Some("fit-wdl") => {
+            let options = FitWdlOptions::from_args(&args[2..]).unwrap_or_else(|error| {
+                eprintln!("fit-wdl: {error}");
+                std::process::exit(1);
+            });
+
+            if let Err(error) = run_fit_wdl(&options) {
+                eprintln!("fit-wdl failed: {error}");
+                std::process::exit(1);
+            }
+        }
+        _ => do_uci_loop(Arc::new(eval_params)),
+    }
 }
\ No newline at end of file
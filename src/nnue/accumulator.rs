@@ -0,0 +1,264 @@
+﻿use crate::nnue::network::Network;
+use crate::nnue::simd::update_accumulator;
+use crate::search::pv_table::MAX_PLY;
+use cozy_chess::{Board, Color, File, Move, Piece, Square};
+use std::sync::Arc;
+
+// Castling adds and removes two features, any other move at most one added and two removed.
+const MAX_CHANGES: usize = 2;
+
+// Hidden layer values of both perspectives, indexed by color.
+#[derive(Clone)]
+pub struct Accumulator {
+    pub values: [Vec<i16>; 2],
+}
+
+type Feature = (Color, Piece, Square);
+
+struct FeatureChanges {
+    adds: [Feature; MAX_CHANGES],
+    add_count: usize,
+    subs: [Feature; MAX_CHANGES],
+    sub_count: usize,
+}
+
+impl FeatureChanges {
+    fn add(&mut self, feature: Feature) {
+        self.adds[self.add_count] = feature;
+        self.add_count += 1;
+    }
+
+    fn sub(&mut self, feature: Feature) {
+        self.subs[self.sub_count] = feature;
+        self.sub_count += 1;
+    }
+}
+
+// One accumulator per ply. Making a move computes the child from its parent with only the
+// features that changed, unmaking a move just steps back down the stack.
+pub struct AccumulatorStack {
+    pub network: Arc<Network>,
+    stack: Vec<Accumulator>,
+    top: usize,
+}
+
+impl AccumulatorStack {
+    pub fn new(network: Arc<Network>, board: &Board) -> Self {
+        let empty = Accumulator {
+            values: [vec![0; network.hidden_size], vec![0; network.hidden_size]],
+        };
+        let mut stack = AccumulatorStack {
+            stack: vec![empty; MAX_PLY + 1],
+            network,
+            top: 0,
+        };
+        stack.refresh(board);
+        stack
+    }
+
+    pub fn current(&self) -> &Accumulator {
+        &self.stack[self.top]
+    }
+
+    // Recomputes the accumulator of the current ply from scratch.
+    pub fn refresh(&mut self, board: &Board) {
+        for perspective in [Color::White, Color::Black] {
+            refresh_perspective(&self.network, board, perspective, &mut self.stack[self.top].values[perspective as usize]);
+        }
+    }
+
+    // `board` is the position before `mv` and `new_board` the one after.
+    pub fn push(&mut self, board: &Board, mv: Move, new_board: &Board) {
+        let (parents, children) = self.stack.split_at_mut(self.top + 1);
+        let parent = &parents[self.top];
+        let child = &mut children[0];
+        self.top += 1;
+
+        let us = board.side_to_move();
+        let moved = board.piece_on(mv.from).unwrap();
+        let changes = get_feature_changes(board, mv);
+
+        for perspective in [Color::White, Color::Black] {
+            let output = &mut child.values[perspective as usize];
+
+            // A king move into another bucket or across the middle changes every feature.
+            if moved == Piece::King && perspective == us {
+                let old_king = self.network.king_bucket(us, mv.from);
+                let new_king = self.network.king_bucket(us, new_board.king(us));
+                if old_king != new_king {
+                    refresh_perspective(&self.network, new_board, perspective, output);
+                    continue;
+                }
+            }
+
+            let king = new_board.king(perspective);
+            let row = |(color, piece, square): Feature| {
+                self.network.feature_weights(self.network.feature_index(perspective, king, color, piece, square))
+            };
+            let adds = changes.adds.map(row);
+            let subs = changes.subs.map(row);
+
+            update_accumulator(
+                output,
+                &parent.values[perspective as usize],
+                &adds[..changes.add_count],
+                &subs[..changes.sub_count],
+            );
+        }
+    }
+
+    pub fn pop(&mut self) {
+        self.top -= 1;
+    }
+
+    // Score in centipawns for the side to move.
+    pub fn evaluate(&self, board: &Board) -> i32 {
+        let accumulator = self.current();
+        let us = board.side_to_move();
+
+        self.network.output(
+            &accumulator.values[us as usize],
+            &accumulator.values[!us as usize],
+        )
+    }
+}
+
+fn refresh_perspective(network: &Network, board: &Board, perspective: Color, output: &mut [i16]) {
+    let king = board.king(perspective);
+    output.copy_from_slice(&network.feature_bias);
+
+    for color in [Color::White, Color::Black] {
+        for piece in Piece::ALL {
+            for square in board.colored_pieces(color, piece) {
+                let weights = network.feature_weights(network.feature_index(perspective, king, color, piece, square));
+                for (value, &weight) in output.iter_mut().zip(weights) {
+                    *value = value.wrapping_add(weight);
+                }
+            }
+        }
+    }
+}
+
+// Features added and removed by a move.
+fn get_feature_changes(board: &Board, mv: Move) -> FeatureChanges {
+    let us = board.side_to_move();
+    let them = !us;
+    let moved = board.piece_on(mv.from).unwrap();
+    let mut changes = FeatureChanges {
+        adds: [(us, moved, mv.from); MAX_CHANGES],
+        add_count: 0,
+        subs: [(us, moved, mv.from); MAX_CHANGES],
+        sub_count: 0,
+    };
+
+    // Castling is encoded as the king capturing its own rook.
+    if moved == Piece::King && board.color_on(mv.to) == Some(us) {
+        let (king_file, rook_file) = if mv.to.file() > mv.from.file() {
+            (File::G, File::F)
+        } else {
+            (File::C, File::D)
+        };
+        let rank = mv.from.rank();
+
+        changes.sub((us, Piece::King, mv.from));
+        changes.sub((us, Piece::Rook, mv.to));
+        changes.add((us, Piece::King, Square::new(king_file, rank)));
+        changes.add((us, Piece::Rook, Square::new(rook_file, rank)));
+        return changes;
+    }
+
+    changes.sub((us, moved, mv.from));
+    changes.add((us, mv.promotion.unwrap_or(moved), mv.to));
+
+    if let Some(captured) = board.piece_on(mv.to) {
+        changes.sub((them, captured, mv.to));
+    } else if moved == Piece::Pawn && mv.from.file() != mv.to.file() {
+        changes.sub((them, Piece::Pawn, Square::new(mv.to.file(), mv.from.rank())));
+    }
+
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::random::xorshift64;
+
+    // Positions with castling, en passant and promotions close by, so the random games below go
+    // through every kind of accumulator update.
+    const NNUE_TEST_POSITIONS: [&str; 6] = [
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+        "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+        "8/2P1k3/8/8/8/8/1p2K3/8 w - - 0 1",
+        "4k3/8/8/8/8/8/8/R3K2R b KQ - 0 1",
+    ];
+
+    const GAMES_PER_POSITION: u64 = 8;
+    const MAX_GAME_PLIES: usize = 60;
+    const RANDOM_NETWORK_HIDDEN_SIZE: usize = 64;
+
+    fn changes_extra_features(board: &Board, mv: Move) -> bool {
+        board.piece_on(mv.to).is_some()
+            || mv.promotion.is_some()
+            || (board.piece_on(mv.from) == Some(Piece::Pawn) && mv.from.file() != mv.to.file())
+    }
+
+    fn assert_matches_refresh(network: &Network, accumulator: &Accumulator, board: &Board, what: &str) {
+        let mut refreshed = vec![0; network.hidden_size];
+
+        for perspective in [Color::White, Color::Black] {
+            refresh_perspective(network, board, perspective, &mut refreshed);
+            assert!(refreshed == accumulator.values[perspective as usize], "{what}: {board}");
+        }
+    }
+
+    // Plays random games and checks after every move and every unmake that the incrementally
+    // updated accumulator is the same as one recomputed from scratch.
+    #[test]
+    fn incremental_updates_match_refresh() {
+        let network = Arc::new(Network::random(RANDOM_NETWORK_HIDDEN_SIZE, 0x2545_F491_4F6C_DD1D));
+
+        let mut state: u64 = 0x9E37_79B9_7F4A_7C15;
+        for fen in NNUE_TEST_POSITIONS {
+            for _ in 0..GAMES_PER_POSITION {
+                let root = Board::from_fen(fen, false).unwrap();
+                let mut accumulators = AccumulatorStack::new(network.clone(), &root);
+                let mut boards = vec![root];
+
+                while boards.len() <= MAX_GAME_PLIES {
+                    let board = boards.last().unwrap();
+                    let mut moves: Vec<Move> = Vec::new();
+                    board.generate_moves(|mvs| {
+                        moves.extend(mvs);
+                        false
+                    });
+                    if moves.is_empty() {
+                        break;
+                    }
+
+                    // Half the moves come from the captures, promotions and castling moves when
+                    // there are any, a uniform pick would hardly ever play en passant.
+                    let special: Vec<Move> = moves.iter().copied().filter(|&mv| changes_extra_features(board, mv)).collect();
+                    state = xorshift64(state);
+                    let choices = if !special.is_empty() && state.is_multiple_of(2) { &special } else { &moves };
+                    let mv = choices[(state / 2 % choices.len() as u64) as usize];
+
+                    let mut new_board = board.clone();
+                    new_board.play_unchecked(mv);
+                    accumulators.push(board, mv, &new_board);
+                    assert_matches_refresh(&network, accumulators.current(), &new_board, "incremental update");
+                    boards.push(new_board);
+                }
+
+                // Unmaking has to give back exactly the accumulators of the earlier positions.
+                while boards.len() > 1 {
+                    boards.pop();
+                    accumulators.pop();
+                    assert_matches_refresh(&network, accumulators.current(), boards.last().unwrap(), "unmake");
+                }
+            }
+        }
+    }
+}
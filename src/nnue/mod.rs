@@ -0,0 +1,4 @@
+﻿pub mod accumulator;
+pub mod network;
+pub mod nnue_evaluator;
+pub mod simd;
\ No newline at end of file
@@ -0,0 +1,160 @@
+﻿use crate::nnue::simd::screlu_dot;
+#[cfg(test)]
+use crate::util::random::xorshift64;
+use cozy_chess::{Color, File, Piece, Square};
+use std::fs;
+use std::io;
+
+// Quantisation of the trained floats: feature weights by QA, output weights by QB. The output is
+// scaled from the sigmoid domain to centipawns by EVAL_SCALE.
+pub const QA: i32 = 255;
+pub const QB: i32 = 64;
+pub const EVAL_SCALE: i32 = 400;
+
+// Inputs per king bucket: 2 sides * 6 pieces * 64 squares.
+pub const INPUTS_PER_BUCKET: usize = 768;
+
+// Hidden layer sizes have to be a multiple of this so the SIMD paths never need a scalar tail.
+pub const HIDDEN_SIZE_ALIGNMENT: usize = 16;
+
+// File layout, everything little endian:
+//   magic "CNN1", hidden size (u32), king bucket for each square (64 bytes),
+//   feature weights (buckets * 768 * hidden i16), feature biases (hidden i16),
+//   output weights (2 * hidden i16, side to move first), output bias (i32).
+const NETWORK_MAGIC: &[u8; 4] = b"CNN1";
+const NETWORK_HEADER_SIZE: usize = 4 + 4 + 64;
+
+// A (buckets * 768) -> hidden x2 -> 1 network with SCReLU activation. Each perspective sees the
+// board from its own side with its king mirrored onto files a-d, and the king square picks the
+// bucket of feature weights.
+pub struct Network {
+    pub hidden_size: usize,
+    pub bucket_count: usize,
+    king_buckets: [u8; 64],
+    feature_weights: Vec<i16>,
+    pub feature_bias: Vec<i16>,
+    output_weights: Vec<i16>,
+    output_bias: i32,
+}
+
+impl Network {
+    pub fn load(path: &str) -> io::Result<Self> {
+        let bytes = fs::read(path)?;
+        let invalid = |reason: &str| io::Error::new(io::ErrorKind::InvalidData, format!("invalid network file {path}: {reason}"));
+
+        if bytes.len() < NETWORK_HEADER_SIZE || &bytes[..4] != NETWORK_MAGIC {
+            return Err(invalid("bad header"));
+        }
+
+        let hidden_size = u32::from_le_bytes(bytes[4..8].try_into().unwrap()) as usize;
+        if hidden_size == 0 || !hidden_size.is_multiple_of(HIDDEN_SIZE_ALIGNMENT) {
+            return Err(invalid("hidden size must be a multiple of 16"));
+        }
+
+        let mut king_buckets = [0u8; 64];
+        king_buckets.copy_from_slice(&bytes[8..NETWORK_HEADER_SIZE]);
+        let bucket_count = *king_buckets.iter().max().unwrap() as usize + 1;
+
+        let feature_count = bucket_count * INPUTS_PER_BUCKET * hidden_size;
+        let expected_size = NETWORK_HEADER_SIZE + (feature_count + 3 * hidden_size) * 2 + 4;
+        if bytes.len() != expected_size {
+            return Err(invalid("size doesn't match the header"));
+        }
+
+        let mut offset = NETWORK_HEADER_SIZE;
+        let mut read_i16s = |count: usize| {
+            let values = bytes[offset..offset + count * 2]
+                .chunks_exact(2)
+                .map(|chunk| i16::from_le_bytes([chunk[0], chunk[1]]))
+                .collect::<Vec<i16>>();
+            offset += count * 2;
+            values
+        };
+
+        let feature_weights = read_i16s(feature_count);
+        let feature_bias = read_i16s(hidden_size);
+        let output_weights = read_i16s(2 * hidden_size);
+        let output_bias = i32::from_le_bytes(bytes[expected_size - 4..].try_into().unwrap());
+
+        Ok(Network {
+            hidden_size,
+            bucket_count,
+            king_buckets,
+            feature_weights,
+            feature_bias,
+            output_weights,
+            output_bias,
+        })
+    }
+
+    // A network with random weights, for testing the accumulator code without a trained network.
+    // Four buckets: the king's home rank, the next two ranks, the rest of the board, with the
+    // queen side of the home rank split off.
+    #[cfg(test)]
+    pub fn random(hidden_size: usize, seed: u64) -> Self {
+        let mut state = seed | 1;
+        let mut next = |range: i32| {
+            state = xorshift64(state);
+            ((state % (2 * range as u64 + 1)) as i32 - range) as i16
+        };
+
+        let mut king_buckets = [0u8; 64];
+        for (square, bucket) in king_buckets.iter_mut().enumerate() {
+            *bucket = match square / 8 {
+                0 if square % 8 < 2 => 0,
+                0 => 1,
+                1 | 2 => 2,
+                _ => 3,
+            };
+        }
+        let bucket_count = 4;
+
+        Network {
+            hidden_size,
+            bucket_count,
+            king_buckets,
+            feature_weights: (0..bucket_count * INPUTS_PER_BUCKET * hidden_size).map(|_| next(64)).collect(),
+            feature_bias: (0..hidden_size).map(|_| next(32)).collect(),
+            output_weights: (0..2 * hidden_size).map(|_| next(64)).collect(),
+            output_bias: next(1000) as i32,
+        }
+    }
+
+    // Bucket and mirroring for a perspective whose king is on `king`. A king move that changes
+    // either needs a full refresh of that perspective.
+    pub fn king_bucket(&self, perspective: Color, king: Square) -> (usize, bool) {
+        let king = king.relative_to(perspective);
+        let mirrored = king.file() >= File::E;
+        let king = if mirrored { king.flip_file() } else { king };
+
+        (self.king_buckets[king as usize] as usize, mirrored)
+    }
+
+    pub fn feature_index(
+        &self,
+        perspective: Color,
+        king: Square,
+        color: Color,
+        piece: Piece,
+        square: Square,
+    ) -> usize {
+        let (bucket, mirrored) = self.king_bucket(perspective, king);
+        let square = square.relative_to(perspective);
+        let square = if mirrored { square.flip_file() } else { square };
+        let side = (color != perspective) as usize;
+
+        bucket * INPUTS_PER_BUCKET + (side * 6 + piece as usize) * 64 + square as usize
+    }
+
+    pub fn feature_weights(&self, index: usize) -> &[i16] {
+        &self.feature_weights[index * self.hidden_size..(index + 1) * self.hidden_size]
+    }
+
+    // Score in centipawns for the side to move given both halves of the accumulator.
+    pub fn output(&self, us: &[i16], them: &[i16]) -> i32 {
+        let (our_weights, their_weights) = self.output_weights.split_at(self.hidden_size);
+        let sum = screlu_dot(us, our_weights).wrapping_add(screlu_dot(them, their_weights));
+
+        ((sum / QA + self.output_bias) as i64 * EVAL_SCALE as i64 / (QA * QB) as i64) as i32
+    }
+}
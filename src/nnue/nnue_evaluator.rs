@@ -0,0 +1,46 @@
+﻿use crate::eval::eval::to_side_to_move;
+use crate::eval::eval_is_mate::eval_is_mate;
+use crate::eval::evaluator::{Evaluator, EvaluatorKind};
+use crate::nnue::accumulator::AccumulatorStack;
+use crate::nnue::network::Network;
+use cozy_chess::{Board, Move};
+use std::sync::Arc;
+
+// Evaluates with a network, the accumulators are updated incrementally as the search makes and
+// takes back moves.
+pub struct NnueEvaluator {
+    accumulators: AccumulatorStack,
+}
+
+impl NnueEvaluator {
+    // `board` is the root of the search.
+    pub fn new(network: Arc<Network>, board: &Board) -> Self {
+        NnueEvaluator {
+            accumulators: AccumulatorStack::new(network, board),
+        }
+    }
+}
+
+impl Evaluator for NnueEvaluator {
+    fn name(&self) -> &'static str {
+        EvaluatorKind::Nnue.name()
+    }
+
+    // The network only replaces the positional judgement, mates are still found by the rules.
+    fn evaluate(&mut self, board: &Board, distance_from_root: u8) -> i32 {
+        let mate_score = eval_is_mate(board, distance_from_root);
+        if mate_score != 0 {
+            return to_side_to_move(mate_score, board);
+        }
+
+        self.accumulators.evaluate(board)
+    }
+
+    fn on_make(&mut self, board: &Board, mv: Move, new_board: &Board) {
+        self.accumulators.push(board, mv, new_board);
+    }
+
+    fn on_unmake(&mut self) {
+        self.accumulators.pop();
+    }
+}
@@ -0,0 +1,100 @@
+﻿use crate::nnue::network::QA;
+
+// Sum of clamp(x, 0, QA)^2 * w over both slices. All the paths wrap on overflow the same way, so
+// they agree bit for bit whatever the weights are.
+pub fn screlu_dot(input: &[i16], weights: &[i16]) -> i32 {
+    #[cfg(target_arch = "x86_64")]
+    if std::arch::is_x86_feature_detected!("avx2") {
+        // Safety: AVX2 support was just checked.
+        return unsafe { screlu_dot_avx2(input, weights) };
+    }
+
+    screlu_dot_scalar(input, weights)
+}
+
+fn screlu_dot_scalar(input: &[i16], weights: &[i16]) -> i32 {
+    input.iter().zip(weights).fold(0i32, |sum, (&x, &w)| {
+        let x = (x as i32).clamp(0, QA);
+        sum.wrapping_add((x * x).wrapping_mul(w as i32))
+    })
+}
+
+// The slices have to be a multiple of 16 long, the network loader makes sure of that.
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2")]
+unsafe fn screlu_dot_avx2(input: &[i16], weights: &[i16]) -> i32 {
+    use std::arch::x86_64::*;
+
+    let zero = _mm256_setzero_si256();
+    let qa = _mm256_set1_epi16(QA as i16);
+    let mut sum = _mm256_setzero_si256();
+
+    for (x, w) in input.chunks_exact(16).zip(weights.chunks_exact(16)) {
+        let x = _mm256_loadu_si256(x.as_ptr() as *const __m256i);
+        let w = _mm256_loadu_si256(w.as_ptr() as *const __m256i);
+        let x = _mm256_min_epi16(_mm256_max_epi16(x, zero), qa);
+
+        // Widen to 32 bits, x^2 doesn't fit in an i16.
+        let x_low = _mm256_cvtepi16_epi32(_mm256_castsi256_si128(x));
+        let x_high = _mm256_cvtepi16_epi32(_mm256_extracti128_si256::<1>(x));
+        let w_low = _mm256_cvtepi16_epi32(_mm256_castsi256_si128(w));
+        let w_high = _mm256_cvtepi16_epi32(_mm256_extracti128_si256::<1>(w));
+
+        sum = _mm256_add_epi32(sum, _mm256_mullo_epi32(_mm256_mullo_epi32(x_low, x_low), w_low));
+        sum = _mm256_add_epi32(sum, _mm256_mullo_epi32(_mm256_mullo_epi32(x_high, x_high), w_high));
+    }
+
+    let mut lanes = [0i32; 8];
+    _mm256_storeu_si256(lanes.as_mut_ptr() as *mut __m256i, sum);
+    lanes.iter().fold(0i32, |total, &lane| total.wrapping_add(lane))
+}
+
+// output = input + sum(adds) - sum(subs). Written as plain loops over whole rows so the compiler
+// vectorises them for whatever target it builds for.
+pub fn update_accumulator(output: &mut [i16], input: &[i16], adds: &[&[i16]], subs: &[&[i16]]) {
+    output.copy_from_slice(input);
+
+    for add in adds {
+        for (value, &weight) in output.iter_mut().zip(add.iter()) {
+            *value = value.wrapping_add(weight);
+        }
+    }
+    for sub in subs {
+        for (value, &weight) in output.iter_mut().zip(sub.iter()) {
+            *value = value.wrapping_sub(weight);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::random::xorshift64;
+
+    // Inputs cover the clamp at both ends and weights the full i16 range, so the sums overflow
+    // and the wrapping has to match too.
+    #[cfg(target_arch = "x86_64")]
+    #[test]
+    fn avx2_matches_scalar() {
+        if !std::arch::is_x86_feature_detected!("avx2") {
+            return;
+        }
+
+        let mut state: u64 = 0x9E37_79B9_7F4A_7C15;
+        let mut next = || {
+            state = xorshift64(state);
+            state as i16
+        };
+
+        for length in [16, 64, 256, 1024] {
+            for _ in 0..16 {
+                let input: Vec<i16> = (0..length).map(|_| next() % (2 * QA as i16)).collect();
+                let weights: Vec<i16> = (0..length).map(|_| next()).collect();
+
+                // Safety: AVX2 support was just checked.
+                let avx2 = unsafe { screlu_dot_avx2(&input, &weights) };
+                assert_eq!(avx2, screlu_dot_scalar(&input, &weights), "length {length}");
+            }
+        }
+    }
+}
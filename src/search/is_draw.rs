@@ -0,0 +1,66 @@
+﻿use cozy_chess::{BitBoard, Board, GameStatus, Piece};
+
+const LIGHT_SQUARES: u64 = 0x55AA55AA55AA55AA;
+
+// Returns true if the position is drawn by the 50 move rule, repetition or insufficient material.
+// The last entry of `hash_history` must be the hash of `board`.
+pub fn is_draw(board: &Board, hash_history: &[u64], distance_from_root: u8) -> bool {
+    // A checkmate delivered on the 100th ply still wins.
+    if board.halfmove_clock() >= 100 && board.status() != GameStatus::Won {
+        return true;
+    }
+
+    is_repetition(hash_history, board.halfmove_clock(), distance_from_root)
+        || is_insufficient_material(board)
+}
+
+// Positions can only repeat since the last irreversible move, so only that far back is scanned.
+// A repetition of a position inside the search tree is scored as a draw straight away since the
+// side to move could have avoided it, positions from before the root need a threefold.
+pub fn is_repetition(hash_history: &[u64], halfmove_clock: u8, distance_from_root: u8) -> bool {
+    let length = hash_history.len();
+    if length == 0 {
+        return false;
+    }
+
+    let current_hash = hash_history[length - 1];
+    let max_distance = (halfmove_clock as usize).min(length - 1);
+    let mut repetitions = 0;
+
+    // Same side to move only, and a position can't repeat in less than 4 plies.
+    let mut distance = 4;
+    while distance <= max_distance {
+        if hash_history[length - 1 - distance] == current_hash {
+            if distance < distance_from_root as usize {
+                return true;
+            }
+
+            repetitions += 1;
+            if repetitions >= 2 {
+                return true;
+            }
+        }
+        distance += 2;
+    }
+
+    false
+}
+
+// Dead positions where neither side can ever mate: KvK, KNvK, KBvK and any number of bishops
+// that all stand on the same square colour.
+pub fn is_insufficient_material(board: &Board) -> bool {
+    let majors_and_pawns =
+        board.pieces(Piece::Pawn) | board.pieces(Piece::Rook) | board.pieces(Piece::Queen);
+    if !majors_and_pawns.is_empty() {
+        return false;
+    }
+
+    let knights = board.pieces(Piece::Knight);
+    let bishops = board.pieces(Piece::Bishop);
+    if (knights | bishops).len() <= 1 {
+        return true;
+    }
+
+    knights.is_empty()
+        && (bishops.is_subset(BitBoard(LIGHT_SQUARES)) || bishops.is_disjoint(BitBoard(LIGHT_SQUARES)))
+}
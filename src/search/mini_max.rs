@@ -1,121 +1,162 @@
-﻿use crate::eval::eval::eval;
-use crate::search::is_threefold::is_threefold;
+﻿use crate::eval::eval_is_mate::{MATE_SCORE, MATE_THRESHOLD};
+use crate::eval::evaluator::Evaluator;
+use crate::eval::kpk_bitbase::{is_kpk, probe_kpk};
+use crate::search::is_draw::is_draw;
 use crate::search::order_moves::order_moves;
+use crate::search::probe_tablebase::{get_tablebase_move, probe_tablebase, tablebase_score};
+use crate::search::pv_table::MAX_PLY;
 use crate::search::quiescence::quiescence;
+use crate::search::see::is_capture;
+use crate::search::search_thread::SearchThread;
 use crate::search::transposition_table::{
-    TranspositionTable, TranspositionTableEntry, TranspositionTableEntryType,
+    score_from_tt, score_to_tt, TranspositionTable, TranspositionTableEntry,
+    TranspositionTableEntryType,
 };
-use cozy_chess::{BitBoard, Board, BoardBuilder, Color, GameStatus, Move};
-use std::collections::HashMap;
-use std::sync::atomic::AtomicBool;
-use std::sync::Arc;
+use cozy_chess::{Board, Color, GameStatus, Move};
 
-pub fn mini_max(
+pub fn mini_max<E: Evaluator>(
     board: &Board,
+    thread: &mut SearchThread<E>,
     transposition_table: &mut TranspositionTable,
-    hash_history: Vec<u64>,
     depth: u8,
     mut alpha: i32,
     mut beta: i32,
     distance_from_root: u8,
-    is_playing: &Arc<AtomicBool>,
-    node_count: &mut u64,
-    killer_moves: &mut HashMap<u8, Vec<Move>>,
-) -> (i32, Option<Move>, bool, Vec<Move>) {
-    let mut best_pv: Vec<Move> = Vec::new();
-
-    if !is_playing.load(std::sync::atomic::Ordering::SeqCst) {
-        return (0, None, true, best_pv);
+) -> (i32, Option<Move>, bool) {
+    let ply = distance_from_root as usize;
+    thread.pv_table.clear_ply(ply);
+
+    if !thread.is_playing() {
+        return (0, None, true);
+    }
+
+    if ply >= MAX_PLY - 1 {
+        return (thread.eval(board, distance_from_root), None, false);
+    }
+
+    // Draws inside the tree end the line, the root still has to return a move.
+    if distance_from_root > 0 && is_draw(board, &thread.hash_history, distance_from_root) {
+        return (thread.draw_score(board), None, false);
+    }
+
+    // KPK draws are exact, won KPK positions are still searched so the engine makes progress
+    // towards promoting the pawn.
+    if distance_from_root > 0 && is_kpk(board) && !probe_kpk(board) {
+        return (thread.draw_score(board), None, false);
+    }
+
+    // Tablebase results are exact, at the root the move comes from probing the children instead
+    // of a search.
+    if let Some(result) = probe_tablebase(board, thread) {
+        if distance_from_root > 0 {
+            return (tablebase_score(result, board, thread, distance_from_root), None, false);
+        }
+
+        if let Some((mv, result)) = get_tablebase_move(board, thread) {
+            thread.pv_table.clear_ply(1);
+            thread.pv_table.update(0, mv);
+            return (tablebase_score(result, board, thread, 0), Some(mv), false);
+        }
+    }
+
+    let maximizing = board.side_to_move() == Color::White;
+
+    // Mate distance pruning: no line from this node can end in a mate faster than the next ply
+    // or be mated sooner than right now, so if the window lies outside those bounds we already
+    // know how this node fails.
+    if distance_from_root > 0 {
+        let mated_now = MATE_SCORE - distance_from_root as i32;
+        let mate_next = MATE_SCORE - distance_from_root as i32 - 1;
+        if maximizing {
+            alpha = alpha.max(-mated_now);
+            beta = beta.min(mate_next);
+        } else {
+            alpha = alpha.max(-mate_next);
+            beta = beta.min(mated_now);
+        }
+        if alpha >= beta {
+            return (alpha, None, false);
+        }
     }
 
     let hash = board.hash();
     // TT lookup: if an entry exists and its depth is sufficient, try to cut off.
     if let Some(entry) = transposition_table.get(hash) {
         if entry.depth >= depth && depth > 0 {
-            match entry.entry_type {
-                TranspositionTableEntryType::Exact => {
-                    // Exact values can be returned immediately.
-                    if entry.score.abs() < 900_000 {
-                        return (entry.score, entry.best_move, false, entry.pv.clone());
-                    }
-                }
-                TranspositionTableEntryType::LowerBound => {
-                    // If the TT says the score is at least a lower bound and that lower bound is ≥ β,
-                    // we can return immediately.
-                    if entry.score >= beta {
-                        return (entry.score, entry.best_move, false, entry.pv.clone());
-                    }
-                }
-                TranspositionTableEntryType::UpperBound => {
-                    // Similarly, if the TT says the score is at most an upper bound and that bound is ≤ α,
-                    // we can return immediately.
-                    if entry.score <= alpha {
-                        return (entry.score, entry.best_move, false, entry.pv.clone());
-                    }
-                }
+            let entry_score = score_from_tt(entry.score, distance_from_root);
+            let entry_best_move = entry.best_move;
+            let is_cutoff = match entry.entry_type {
+                // Exact values can be returned immediately.
+                TranspositionTableEntryType::Exact => true,
+                // If the TT says the score is at least a lower bound and that lower bound is ≥ β,
+                // we can return immediately.
+                TranspositionTableEntryType::LowerBound => entry_score >= beta,
+                // Similarly, if the TT says the score is at most an upper bound and that bound is ≤ α,
+                // we can return immediately.
+                TranspositionTableEntryType::UpperBound => entry_score <= alpha,
+            };
+
+            if is_cutoff {
+                thread
+                    .pv_table
+                    .fill_from_tt(ply, board, transposition_table, depth as usize);
+                return (entry_score, entry_best_move, false);
             }
         }
     }
 
-    if board.status() != GameStatus::Ongoing {
-        return (eval(&board, distance_from_root + 1), None, false, best_pv);
-    }
-    if is_threefold(hash, &hash_history) {
-        return (0, None, false, best_pv);
+    match board.status() {
+        GameStatus::Won => return (thread.eval(board, distance_from_root), None, false),
+        GameStatus::Drawn => return (thread.draw_score(board), None, false),
+        GameStatus::Ongoing => {}
     }
     if depth == 0 {
-        let score = eval(&board, distance_from_root + 1);
+        let score = thread.eval(board, distance_from_root);
 
-        if score.abs() < 900000 {
+        if score.abs() < MATE_THRESHOLD {
             let score = quiescence(
                 board,
-                hash_history,
+                thread,
+                transposition_table,
                 alpha,
                 beta,
                 distance_from_root,
-                is_playing,
-                node_count,
+                0,
             );
-            return (score, None, false, best_pv);
+            return (score, None, false);
         }
 
-        return (score, None, false, best_pv);
+        return (score, None, false);
     }
 
-    let maximizing = board.side_to_move() == Color::White;
-    *node_count += 1;
+    thread.node_count += 1;
 
-    let mut moves = Vec::new();
+    let original_alpha = alpha;
+    let original_beta = beta;
+
+    let mut moves = thread.take_move_list(ply);
     board.generate_moves(|mvs| {
-        moves.extend(mvs);
+        moves.extend(mvs.into_iter().map(|mv| (mv, 0)));
 
         false
     });
 
-    moves = order_moves(
-        &board,
-        moves,
-        &(*killer_moves)
-            .get(&distance_from_root)
-            .cloned()
-            .unwrap_or_default()
-            .into_iter()
-            .collect(),
-    );
+    order_moves(board, &mut moves, thread.killers(ply));
 
     let mut best_score = if maximizing { i32::MIN } else { i32::MAX };
     let mut best_move: Option<Move> = None;
 
-    let mut i = 0;
-
-    for mv in moves {
-        let is_capture = board.piece_on(mv.to).is_some();
+    for (i, &(mv, _)) in moves.iter().enumerate() {
+        let is_capture = is_capture(board, mv);
 
         let mut needs_full_search = true;
 
         let mut score = 0;
         let mut early_stop = false;
-        let mut child_pv = vec![];
+
+        let mut new_board = board.clone();
+        new_board.play_unchecked(mv);
+        thread.push_move(board, mv, &new_board);
 
         const REDUCE_DEPTH_A: i32 = 1;
         const REDUCE_DEPTH_B: i32 = 2;
@@ -134,27 +175,18 @@ pub fn mini_max(
                 reduce_depth = REDUCE_DEPTH_C
             }
 
-            let mut new_board = board.clone();
-            let mut new_hash_history = hash_history.clone();
-            new_board.play(mv);
-            new_hash_history.push(new_board.hash());
-
-            let (new_score, _, new_early_stop, new_child_pv) = mini_max(
+            let (new_score, _, new_early_stop) = mini_max(
                 &new_board,
+                thread,
                 transposition_table,
-                new_hash_history,
                 depth - 1 - reduce_depth as u8,
                 alpha,
                 beta,
                 distance_from_root + 1,
-                is_playing,
-                node_count,
-                &mut *killer_moves,
             );
 
             score = new_score;
             early_stop = new_early_stop;
-            child_pv = new_child_pv;
 
             if maximizing{
                 needs_full_search = new_score > alpha;
@@ -164,53 +196,35 @@ pub fn mini_max(
         }
 
         if needs_full_search{
-            let mut new_board = board.clone();
-            let mut new_hash_history = hash_history.clone();
-            new_board.play(mv);
-            new_hash_history.push(new_board.hash());
-
-            let (new_score, _, new_early_stop, new_child_pv) = mini_max(
+            let (new_score, _, new_early_stop) = mini_max(
                 &new_board,
+                thread,
                 transposition_table,
-                new_hash_history,
                 depth - 1,
                 alpha,
                 beta,
                 distance_from_root + 1,
-                is_playing,
-                node_count,
-                &mut *killer_moves,
             );
 
             score = new_score;
             early_stop = new_early_stop;
-            child_pv = new_child_pv;
         }
 
+        thread.pop_move();
+
         if early_stop {
-            return (0, None, true, Vec::new());
+            thread.restore_move_list(ply, moves);
+            return (0, None, true);
         }
 
-        let current_pv = {
-            let mut line = vec![mv];
-            line.extend(child_pv);
-            line
-        };
-
         if maximizing {
             if score > best_score {
                 best_score = score;
                 best_move = Some(mv);
-                best_pv = current_pv.clone();
+                thread.pv_table.update(ply, mv);
             }
             if best_score >= beta {
-                let killer_moves_vec = killer_moves
-                    .entry(distance_from_root)
-                    .or_insert_with(Vec::new);
-                killer_moves_vec.push(mv);
-                if killer_moves_vec.len() > 32 {
-                    killer_moves_vec.remove(0);
-                }
+                thread.add_killer(ply, mv);
                 break;
             }
             alpha = alpha.max(best_score);
@@ -218,30 +232,22 @@ pub fn mini_max(
             if score < best_score {
                 best_score = score;
                 best_move = Some(mv);
-                best_pv = current_pv.clone();
+                thread.pv_table.update(ply, mv);
             }
             if best_score <= alpha {
-                let killer_moves_vec = killer_moves
-                    .entry(distance_from_root)
-                    .or_insert_with(Vec::new);
-                killer_moves_vec.push(mv);
-                if killer_moves_vec.len() > 32 {
-                    killer_moves_vec.remove(0);
-                }
+                thread.add_killer(ply, mv);
                 break;
             }
             beta = beta.min(best_score);
         }
-
-        i += 1;
     }
 
+    thread.restore_move_list(ply, moves);
+
     // Determine what kind of bound to store in the TT.
-    let entry_type = if best_score.abs() >= 900_000 {
-        TranspositionTableEntryType::Exact
-    } else if best_score <= alpha {
+    let entry_type = if best_score <= original_alpha {
         TranspositionTableEntryType::UpperBound
-    } else if best_score >= beta {
+    } else if best_score >= original_beta {
         TranspositionTableEntryType::LowerBound
     } else {
         TranspositionTableEntryType::Exact
@@ -252,14 +258,13 @@ pub fn mini_max(
             hash,
             TranspositionTableEntry {
                 depth,
-                score: best_score,
+                score: score_to_tt(best_score, distance_from_root),
                 best_move: Some(mv),
                 entry_type,
-                pv: best_pv.clone(),
                 hash, // Store the hash so we can verify later if needed.
             },
         );
     }
 
-    (best_score, best_move, false, best_pv.clone())
+    (best_score, best_move, false)
 }
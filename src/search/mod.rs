@@ -1,5 +1,9 @@
 ﻿pub mod transposition_table;
 pub mod mini_max;
+pub mod pv_table;
+pub mod search_thread;
 mod quiescence;
-mod is_threefold;
-mod order_moves;
\ No newline at end of file
+pub mod is_draw;
+mod order_moves;
+mod see;
+mod probe_tablebase;
\ No newline at end of file
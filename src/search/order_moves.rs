@@ -1,24 +1,30 @@
 ﻿use crate::eval::eval_count_material::get_piece_value;
-use cozy_chess::{BitBoard, Board, Move};
-use std::collections::{HashMap, HashSet};
-
-pub fn order_moves(board: &Board, moves: Vec<Move>, killer_moves: &HashSet<Move>) -> Vec<Move> {
-    let mut moves_with_scores: Vec<(Move, i32)> = moves
-        .into_iter()
-        .map(|mv| {
-            let score = move_order_score(board, &mv, killer_moves);
-            (mv, score)
-        })
-        .collect();
+use cozy_chess::{Board, Move};
+
+pub fn order_moves(board: &Board, moves: &mut [(Move, i32)], killer_moves: &[Move]) {
+    for (mv, score) in moves.iter_mut() {
+        *score = move_order_score(board, mv, killer_moves);
+    }
 
     // Sort descending: highest score first.
-    moves_with_scores.sort_by(|a, b| b.1.cmp(&a.1));
+    sort_moves(moves);
+}
 
-    // Return the sorted moves.
-    moves_with_scores.into_iter().map(|(mv, _)| mv).collect()
+// Stable insertion sort on the move scores, descending. Move lists are short enough that this
+// beats the allocating standard library sort while keeping equal scores in generation order.
+pub fn sort_moves(moves: &mut [(Move, i32)]) {
+    for i in 1..moves.len() {
+        let current = moves[i];
+        let mut j = i;
+        while j > 0 && moves[j - 1].1 < current.1 {
+            moves[j] = moves[j - 1];
+            j -= 1;
+        }
+        moves[j] = current;
+    }
 }
 
-pub fn move_order_score(board: &Board, mv: &Move, killer_moves: &HashSet<Move>) -> i32 {
+pub fn move_order_score(board: &Board, mv: &Move, killer_moves: &[Move]) -> i32 {
     let mut score = 0;
 
     // Give a very big bonus for killer moves
@@ -0,0 +1,63 @@
+﻿use crate::eval::eval_is_mate::MATE_SCORE;
+use crate::eval::evaluator::Evaluator;
+use crate::search::search_thread::SearchThread;
+use crate::tablebase::tablebase::TbResult;
+use cozy_chess::{Board, Color, Move};
+
+pub fn probe_tablebase<E: Evaluator>(board: &Board, thread: &mut SearchThread<E>) -> Option<TbResult> {
+    let result = thread.tablebase.as_ref()?.probe(board)?;
+    thread.tb_hits += 1;
+
+    Some(result)
+}
+
+// White relative score of a tablebase result, mates are counted from the root like the eval does.
+pub fn tablebase_score<E: Evaluator>(result: TbResult, board: &Board, thread: &SearchThread<E>, distance_from_root: u8) -> i32 {
+    let score = match result {
+        TbResult::Win(plies) => MATE_SCORE - distance_from_root as i32 - plies as i32,
+        TbResult::Loss(plies) => -(MATE_SCORE - distance_from_root as i32 - plies as i32),
+        TbResult::Draw => return thread.draw_score(board),
+    };
+
+    if board.side_to_move() == Color::White {
+        score
+    } else {
+        -score
+    }
+}
+
+// Picks the root move that keeps the best tablebase result: the fastest win, any draw, or the
+// slowest loss. Returns None when a child isn't covered by the loaded tables.
+pub fn get_tablebase_move<E: Evaluator>(board: &Board, thread: &mut SearchThread<E>) -> Option<(Move, TbResult)> {
+    let mut moves = Vec::new();
+    board.generate_moves(|mvs| {
+        moves.extend(mvs);
+        false
+    });
+
+    let mut best: Option<(Move, TbResult)> = None;
+    for mv in moves {
+        let mut new_board = board.clone();
+        new_board.play_unchecked(mv);
+
+        let result = match probe_tablebase(&new_board, thread)? {
+            TbResult::Win(plies) => TbResult::Loss(plies + 1),
+            TbResult::Loss(plies) => TbResult::Win(plies + 1),
+            TbResult::Draw => TbResult::Draw,
+        };
+
+        if best.is_none_or(|(_, best_result)| tablebase_rank(result) > tablebase_rank(best_result)) {
+            best = Some((mv, result));
+        }
+    }
+
+    best
+}
+
+fn tablebase_rank(result: TbResult) -> (u8, i32) {
+    match result {
+        TbResult::Win(plies) => (2, -(plies as i32)),
+        TbResult::Draw => (1, 0),
+        TbResult::Loss(plies) => (0, plies as i32),
+    }
+}
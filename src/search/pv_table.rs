@@ -0,0 +1,98 @@
+﻿use crate::search::transposition_table::TranspositionTable;
+use cozy_chess::{Board, Move, Square};
+
+pub const MAX_PLY: usize = 128;
+
+const NULL_MOVE: Move = Move {
+    from: Square::A1,
+    to: Square::A1,
+    promotion: None,
+};
+
+// Triangular PV table (see: https://www.chessprogramming.org/Triangular_PV-Table)
+// Row `ply` holds the principal variation found from the node at that ply, so the row at
+// ply 0 is the PV of the whole search.
+pub struct PvTable {
+    moves: Vec<[Move; MAX_PLY]>,
+    lengths: [usize; MAX_PLY],
+}
+
+impl PvTable {
+    pub fn new() -> Self {
+        PvTable {
+            moves: vec![[NULL_MOVE; MAX_PLY]; MAX_PLY],
+            lengths: [0; MAX_PLY],
+        }
+    }
+
+    // Called when a node is entered, the line of a node that has not found a move yet is empty.
+    pub fn clear_ply(&mut self, ply: usize) {
+        if ply < MAX_PLY {
+            self.lengths[ply] = 0;
+        }
+    }
+
+    // Sets the line at `ply` to `mv` followed by the line found one ply deeper.
+    pub fn update(&mut self, ply: usize, mv: Move) {
+        if ply >= MAX_PLY {
+            return;
+        }
+
+        let child_length = if ply + 1 < MAX_PLY {
+            self.lengths[ply + 1].min(MAX_PLY - ply - 1)
+        } else {
+            0
+        };
+
+        let (parent_rows, child_rows) = self.moves.split_at_mut(ply + 1);
+        let parent = &mut parent_rows[ply];
+        parent[0] = mv;
+        if child_length > 0 {
+            parent[1..=child_length].copy_from_slice(&child_rows[0][..child_length]);
+        }
+        self.lengths[ply] = child_length + 1;
+    }
+
+    // A TT cutoff leaves no searched line behind, so rebuild one by following the best moves
+    // stored in the TT. Every move is checked for legality and the walk stops at the first
+    // repeated position so a cycle of entries can't produce an endless line.
+    pub fn fill_from_tt(
+        &mut self,
+        ply: usize,
+        board: &Board,
+        transposition_table: &TranspositionTable,
+        max_length: usize,
+    ) {
+        if ply >= MAX_PLY {
+            return;
+        }
+
+        let max_length = max_length.min(MAX_PLY - ply);
+        let mut board = board.clone();
+        let mut seen = [0u64; MAX_PLY];
+        let mut length = 0;
+
+        while length < max_length {
+            let hash = board.hash();
+            if seen[..length].contains(&hash) {
+                break;
+            }
+            seen[length] = hash;
+
+            let mv = match transposition_table.get(hash).and_then(|entry| entry.best_move) {
+                Some(mv) if board.is_legal(mv) => mv,
+                _ => break,
+            };
+
+            self.moves[ply][length] = mv;
+            length += 1;
+            board.play_unchecked(mv);
+        }
+
+        self.lengths[ply] = length;
+    }
+
+    pub fn line(&self, ply: usize) -> &[Move] {
+        &self.moves[ply][..self.lengths[ply]]
+    }
+}
@@ -1,86 +1,155 @@
-﻿use crate::eval::eval::eval;
-use crate::eval::eval_count_material::get_piece_value;
-use crate::search::transposition_table::TranspositionTable;
-use cozy_chess::{Board, Color, Move, Piece};
-use std::sync::atomic::AtomicBool;
-use std::sync::Arc;
-use crate::search::is_threefold::is_threefold;
-
-pub fn quiescence(
+﻿use crate::eval::eval_count_material::get_piece_value;
+use crate::eval::evaluator::Evaluator;
+use cozy_chess::{Board, Color, GameStatus, Move, Piece};
+use crate::search::is_draw::is_draw;
+use crate::search::order_moves::sort_moves;
+use crate::search::pv_table::MAX_PLY;
+use crate::search::search_thread::SearchThread;
+use crate::search::see::{is_capture, is_en_passant, see};
+use crate::search::transposition_table::{
+    score_from_tt, score_to_tt, TranspositionTable, TranspositionTableEntry,
+    TranspositionTableEntryType, QSEARCH_DEPTH,
+};
+
+// Captures that can't bring the score back up to alpha even with this margin are skipped.
+const DELTA_MARGIN: i32 = 200;
+
+pub fn quiescence<E: Evaluator>(
     board: &Board,
-    hash_history: Vec<u64>,
+    thread: &mut SearchThread<E>,
+    transposition_table: &mut TranspositionTable,
     mut alpha: i32,
     mut beta: i32,
     distance_from_root: u8,
-    is_playing: &Arc<AtomicBool>,
-    node_count: &mut u64,
+    qsearch_ply: u8,
 ) -> i32 {
-    if !is_playing.load(std::sync::atomic::Ordering::SeqCst) {
+    if !thread.is_playing() {
         return 0;
     }
 
-    if board.status() != cozy_chess::GameStatus::Ongoing {
-        return eval(board, distance_from_root);
+    let ply = distance_from_root as usize;
+    if ply >= MAX_PLY {
+        return thread.eval(board, distance_from_root);
+    }
+
+    if is_draw(board, &thread.hash_history, distance_from_root) {
+        return thread.draw_score(board);
+    }
+
+    match board.status() {
+        GameStatus::Won => return thread.eval(board, distance_from_root),
+        GameStatus::Drawn => return thread.draw_score(board),
+        GameStatus::Ongoing => {}
     }
 
-    *node_count += 1;
+    thread.node_count += 1;
 
     let hash = board.hash();
-    if is_threefold(hash, &hash_history) {
-        return 0;
+    let mut tt_move = None;
+    if let Some(entry) = transposition_table.get(hash) {
+        let entry_score = score_from_tt(entry.score, distance_from_root);
+        tt_move = entry.best_move;
+        let is_cutoff = match entry.entry_type {
+            TranspositionTableEntryType::Exact => true,
+            TranspositionTableEntryType::LowerBound => entry_score >= beta,
+            TranspositionTableEntryType::UpperBound => entry_score <= alpha,
+        };
+
+        if is_cutoff {
+            return entry_score;
+        }
     }
 
-    let stand_pat = eval(board, distance_from_root);
     let maximizing = board.side_to_move() == Color::White;
+    let in_check = !board.checkers().is_empty();
+    let original_alpha = alpha;
+    let original_beta = beta;
 
-    if maximizing {
-        if stand_pat >= beta {
-            return beta;
-        }
-        alpha = alpha.max(stand_pat);
+    // Standing pat is not an option while in check, every evasion has to be searched instead.
+    let stand_pat = if in_check {
+        0
     } else {
-        if stand_pat <= alpha {
-            return alpha;
+        thread.eval_in_window(board, distance_from_root, alpha, beta)
+    };
+
+    if !in_check {
+        if maximizing {
+            if stand_pat >= beta {
+                return beta;
+            }
+            alpha = alpha.max(stand_pat);
+        } else {
+            if stand_pat <= alpha {
+                return alpha;
+            }
+            beta = beta.min(stand_pat);
         }
-        beta = beta.min(stand_pat);
     }
 
-    let mut moves = Vec::new();
+    let search_quiet_checks = thread.qsearch_checks && qsearch_ply == 0;
+
+    let mut moves = thread.take_move_list(ply);
     board.generate_moves(|mvs| {
-        // Only consider capture moves
         for mv in mvs {
-            if board.piece_on(mv.to).is_some() {
-                moves.push(mv);
+            let is_underpromotion = mv.promotion.is_some() && mv.promotion != Some(Piece::Queen);
+
+            if in_check
+                || (!is_underpromotion && (is_capture(board, mv) || mv.promotion.is_some()))
+                || (search_quiet_checks && mv.promotion.is_none() && gives_check(board, mv))
+            {
+                moves.push((mv, qsearch_move_score(board, mv, tt_move)));
             }
         }
         false
     });
 
-    // Sort moves by capture value
-    moves.sort_by_cached_key(|mv| {
-        let target = board.piece_on(mv.to).unwrap_or(Piece::Pawn);
-        -get_piece_value(target) // Negative for descending sort
-    });
+    sort_moves(&mut moves);
+
+    let mut best_move: Option<Move> = None;
+
+    for &(mv, _) in moves.iter() {
+        if !in_check && Some(mv) != tt_move {
+            let material_gain = capture_gain(board, mv);
+
+            // Delta pruning: skip captures and promotions that can't raise the score to alpha
+            // (see: https://www.chessprogramming.org/Delta_Pruning)
+            if material_gain > 0 {
+                let is_futile = if maximizing {
+                    stand_pat + material_gain + DELTA_MARGIN <= alpha
+                } else {
+                    stand_pat - material_gain - DELTA_MARGIN >= beta
+                };
+                if is_futile {
+                    continue;
+                }
+            }
+
+            // Skip moves that lose material once all the recaptures are played out.
+            if see(board, mv) < 0 {
+                continue;
+            }
+        }
 
-    for mv in moves {
         let mut new_board = board.clone();
-        let mut new_hash_history = hash_history.clone();
-        new_board.play(mv);
-        new_hash_history.push(new_board.hash());
+        new_board.play_unchecked(mv);
+        thread.push_move(board, mv, &new_board);
 
         let score = quiescence(
             &new_board,
-            new_hash_history,
+            thread,
+            transposition_table,
             alpha,
             beta,
             distance_from_root + 1,
-            is_playing,
-            node_count,
+            qsearch_ply + 1,
         );
 
+        thread.pop_move();
+
         if maximizing {
             if score > alpha {
                 alpha = score;
+                best_move = Some(mv);
             }
             if alpha >= beta {
                 break;
@@ -88,6 +157,7 @@ pub fn quiescence(
         } else {
             if score < beta {
                 beta = score;
+                best_move = Some(mv);
             }
             if beta <= alpha {
                 break;
@@ -95,9 +165,61 @@ pub fn quiescence(
         }
     }
 
-    if maximizing {
-        alpha
+    thread.restore_move_list(ply, moves);
+
+    let score = if maximizing { alpha } else { beta };
+
+    let entry_type = if score <= original_alpha {
+        TranspositionTableEntryType::UpperBound
+    } else if score >= original_beta {
+        TranspositionTableEntryType::LowerBound
+    } else {
+        TranspositionTableEntryType::Exact
+    };
+
+    transposition_table.insert(
+        hash,
+        TranspositionTableEntry {
+            depth: QSEARCH_DEPTH,
+            score: score_to_tt(score, distance_from_root),
+            best_move,
+            entry_type,
+            hash,
+        },
+    );
+
+    score
+}
+
+// Material won by a capture or promotion before any recapture.
+fn capture_gain(board: &Board, mv: Move) -> i32 {
+    let mut gain = if is_en_passant(board, mv) {
+        get_piece_value(Piece::Pawn)
+    } else if is_capture(board, mv) {
+        get_piece_value(board.piece_on(mv.to).unwrap())
     } else {
-        beta
+        0
+    };
+
+    if let Some(promotion) = mv.promotion {
+        gain += get_piece_value(promotion) - get_piece_value(Piece::Pawn);
+    }
+
+    gain
+}
+
+fn gives_check(board: &Board, mv: Move) -> bool {
+    let mut new_board = board.clone();
+    new_board.play_unchecked(mv);
+    !new_board.checkers().is_empty()
+}
+
+// TT move first, then most valuable victim / least valuable attacker.
+fn qsearch_move_score(board: &Board, mv: Move, tt_move: Option<Move>) -> i32 {
+    if Some(mv) == tt_move {
+        return 1_000_000;
     }
+
+    let attacker_value = get_piece_value(board.piece_on(mv.from).unwrap());
+    capture_gain(board, mv) * 10 - attacker_value
 }
@@ -0,0 +1,186 @@
+﻿use crate::eval::eval_hash_table::EvalHashTable;
+use crate::eval::eval_is_mate::is_mate_score;
+use crate::eval::evaluator::Evaluator;
+use crate::eval::game_phase::get_game_phase;
+use crate::search::pv_table::{PvTable, MAX_PLY};
+use crate::tablebase::tablebase::Tablebase;
+use cozy_chess::{Board, Color, Move};
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+
+const MAX_KILLER_MOVES: usize = 32;
+const MAX_MOVES: usize = 256;
+
+// Everything a search needs besides the board and the TT. All the stacks are allocated once up
+// front, the recursion pushes and pops onto them instead of cloning for every child. The static
+// eval comes from the evaluator `E`.
+pub struct SearchThread<E: Evaluator> {
+    pub is_playing: Arc<AtomicBool>,
+    pub node_count: u64,
+    pub node_limit: u64, // The search stops once it has visited this many nodes
+    pub pv_table: PvTable,
+    pub qsearch_checks: bool, // Also search quiet checks at the first ply of the quiescence search
+    pub lazy_eval: bool, // Let eval_in_window skip the expensive terms far outside the window
+
+    // Hashes of every position from the start of the game up to the current node.
+    pub hash_history: Vec<u64>,
+
+    pub evaluator: E,
+
+    // Static evals of positions already seen, no caching without a table.
+    pub eval_hash_table: Option<Arc<EvalHashTable>>,
+    pub eval_hash_hits: u64,
+    pub eval_hash_misses: u64,
+
+    pub tablebase: Option<Arc<Tablebase>>,
+    pub tb_hits: u64,
+
+    // White relative score of a draw, negative when the engine plays white and wants to avoid
+    // draws.
+    contempt: i32,
+    contempt_scale_by_phase: bool,
+
+    killer_moves: Vec<Vec<Move>>,
+    move_lists: Vec<Vec<(Move, i32)>>,
+}
+
+impl<E: Evaluator> SearchThread<E> {
+    pub fn new(is_playing: Arc<AtomicBool>, hash_history: &[u64], evaluator: E) -> Self {
+        let mut history = Vec::with_capacity(hash_history.len() + MAX_PLY);
+        history.extend_from_slice(hash_history);
+
+        SearchThread {
+            is_playing,
+            node_count: 0,
+            node_limit: u64::MAX,
+            pv_table: PvTable::new(),
+            qsearch_checks: false,
+            lazy_eval: false,
+            hash_history: history,
+            evaluator,
+            eval_hash_table: None,
+            eval_hash_hits: 0,
+            eval_hash_misses: 0,
+            tablebase: None,
+            tb_hits: 0,
+            contempt: 0,
+            contempt_scale_by_phase: false,
+            killer_moves: (0..MAX_PLY)
+                .map(|_| Vec::with_capacity(MAX_KILLER_MOVES + 1))
+                .collect(),
+            move_lists: (0..MAX_PLY)
+                .map(|_| Vec::with_capacity(MAX_MOVES))
+                .collect(),
+        }
+    }
+
+    // White relative static eval.
+    pub fn eval(&mut self, board: &Board, distance_from_root: u8) -> i32 {
+        if let Some(score) = self.probe_eval_hash(board) {
+            return score;
+        }
+
+        let score = self.evaluator.evaluate(board, distance_from_root);
+        let score = if board.side_to_move() == Color::White { score } else { -score };
+
+        // Mate scores depend on the distance from the root, they are found again quickly anyway.
+        if let (Some(table), false) = (&self.eval_hash_table, is_mate_score(score)) {
+            table.insert(board.hash(), score);
+        }
+
+        score
+    }
+
+    fn probe_eval_hash(&mut self, board: &Board) -> Option<i32> {
+        let score = self.eval_hash_table.as_ref()?.get(board.hash());
+        if score.is_some() {
+            self.eval_hash_hits += 1;
+        } else {
+            self.eval_hash_misses += 1;
+        }
+        score
+    }
+
+    // The eval of a node searched with the white relative window alpha..beta. With lazy eval a
+    // score far outside the window is only an estimate.
+    pub fn eval_in_window(&mut self, board: &Board, distance_from_root: u8, alpha: i32, beta: i32) -> i32 {
+        if !self.lazy_eval {
+            return self.eval(board, distance_from_root);
+        }
+
+        // Lazy scores are estimates and never go into the eval hash, but an exact score from it
+        // is even better.
+        if let Some(score) = self.probe_eval_hash(board) {
+            return score;
+        }
+
+        if board.side_to_move() == Color::White {
+            self.evaluator.evaluate_lazy(board, distance_from_root, alpha, beta)
+        } else {
+            -self.evaluator.evaluate_lazy(board, distance_from_root, beta.saturating_neg(), alpha.saturating_neg())
+        }
+    }
+
+    // Every move the search makes goes through here so the repetition history and the evaluator
+    // follow the tree. `new_board` is `board` after `mv`.
+    pub fn push_move(&mut self, board: &Board, mv: Move, new_board: &Board) {
+        self.hash_history.push(new_board.hash());
+        self.evaluator.on_make(board, mv, new_board);
+    }
+
+    pub fn pop_move(&mut self) {
+        self.hash_history.pop();
+        self.evaluator.on_unmake();
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.node_count < self.node_limit && self.is_playing.load(std::sync::atomic::Ordering::SeqCst)
+    }
+
+    // Contempt is given in centipawns from the engine's point of view, a positive value makes the
+    // engine avoid draws. With phase scaling the contempt fades out towards the endgame.
+    pub fn set_contempt(&mut self, contempt: i32, engine_color: Color, scale_by_phase: bool) {
+        self.contempt = if engine_color == Color::White {
+            -contempt
+        } else {
+            contempt
+        };
+        self.contempt_scale_by_phase = scale_by_phase;
+    }
+
+    pub fn draw_score(&self, board: &Board) -> i32 {
+        if self.contempt_scale_by_phase {
+            self.contempt * (256 - get_game_phase(board)) / 256
+        } else {
+            self.contempt
+        }
+    }
+
+    pub fn killers(&self, ply: usize) -> &[Move] {
+        &self.killer_moves[ply]
+    }
+
+    pub fn add_killer(&mut self, ply: usize, mv: Move) {
+        let killers = &mut self.killer_moves[ply];
+        killers.push(mv);
+        if killers.len() > MAX_KILLER_MOVES {
+            killers.remove(0);
+        }
+    }
+
+    pub fn clear_killers(&mut self) {
+        self.killer_moves.iter_mut().for_each(|killers| killers.clear());
+    }
+
+    // Borrows the preallocated move list for a ply, it has to be handed back with
+    // `restore_move_list` before the node returns.
+    pub fn take_move_list(&mut self, ply: usize) -> Vec<(Move, i32)> {
+        let mut moves = std::mem::take(&mut self.move_lists[ply]);
+        moves.clear();
+        moves
+    }
+
+    pub fn restore_move_list(&mut self, ply: usize, moves: Vec<(Move, i32)>) {
+        self.move_lists[ply] = moves;
+    }
+}
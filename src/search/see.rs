@@ -0,0 +1,127 @@
+﻿use crate::eval::eval_count_material::get_piece_value;
+use cozy_chess::{
+    get_bishop_moves, get_king_moves, get_knight_moves, get_pawn_attacks, get_rook_moves, BitBoard,
+    Board, Color, Move, Piece, Square,
+};
+
+// Static exchange evaluation (see: https://www.chessprogramming.org/Static_Exchange_Evaluation)
+// Returns the material the side to move wins (or loses if negative) when `mv` is answered by
+// the full sequence of recaptures on the target square, each side always recapturing with its
+// least valuable piece and free to stop when continuing would lose material.
+pub fn see(board: &Board, mv: Move) -> i32 {
+    let moving_piece = match board.piece_on(mv.from) {
+        Some(piece) => piece,
+        None => return 0,
+    };
+
+    let mut occupied = board.occupied() ^ mv.from.bitboard();
+    let mut gain = [0i32; 32];
+
+    gain[0] = if is_en_passant(board, mv) {
+        // The captured pawn isn't on the target square.
+        let captured_square = Square::new(mv.to.file(), mv.from.rank());
+        occupied ^= captured_square.bitboard();
+        get_piece_value(Piece::Pawn)
+    } else if board.colors(!board.side_to_move()).has(mv.to) {
+        get_piece_value(board.piece_on(mv.to).unwrap())
+    } else {
+        0
+    };
+
+    let mut victim_value = get_piece_value(moving_piece);
+    if let Some(promotion) = mv.promotion {
+        gain[0] += get_piece_value(promotion) - get_piece_value(Piece::Pawn);
+        victim_value = get_piece_value(promotion);
+    }
+
+    let mut side = !board.side_to_move();
+    let mut attackers = attackers_to(board, mv.to, occupied);
+    let mut depth = 0;
+
+    while depth + 1 < gain.len() {
+        let (square, piece) = match least_valuable_attacker(board, attackers & board.colors(side)) {
+            Some(attacker) => attacker,
+            None => break,
+        };
+
+        // The king can only recapture if the square is no longer defended.
+        if piece == Piece::King && !(attackers & board.colors(!side)).is_empty() {
+            break;
+        }
+
+        depth += 1;
+        gain[depth] = victim_value - gain[depth - 1];
+
+        occupied ^= square.bitboard();
+        attackers = attackers_to(board, mv.to, occupied);
+        victim_value = get_piece_value(piece);
+        side = !side;
+    }
+
+    while depth > 0 {
+        gain[depth - 1] = -(-gain[depth - 1]).max(gain[depth]);
+        depth -= 1;
+    }
+
+    gain[0]
+}
+
+pub fn is_en_passant(board: &Board, mv: Move) -> bool {
+    board.piece_on(mv.from) == Some(Piece::Pawn)
+        && mv.from.file() != mv.to.file()
+        && board.piece_on(mv.to).is_none()
+}
+
+// Captures of enemy pieces, castling is encoded as the king capturing its own rook and is not
+// a capture.
+pub fn is_capture(board: &Board, mv: Move) -> bool {
+    board.colors(!board.side_to_move()).has(mv.to) || is_en_passant(board, mv)
+}
+
+// All pieces of both colours attacking `square` with the given occupancy, x-ray attackers show
+// up once the pieces in front of them are removed from `occupied`.
+fn attackers_to(board: &Board, square: Square, occupied: BitBoard) -> BitBoard {
+    let bishops = board.pieces(Piece::Bishop) | board.pieces(Piece::Queen);
+    let rooks = board.pieces(Piece::Rook) | board.pieces(Piece::Queen);
+
+    let attackers = (get_pawn_attacks(square, Color::White)
+        & board.colored_pieces(Color::Black, Piece::Pawn))
+        | (get_pawn_attacks(square, Color::Black) & board.colored_pieces(Color::White, Piece::Pawn))
+        | (get_knight_moves(square) & board.pieces(Piece::Knight))
+        | (get_king_moves(square) & board.pieces(Piece::King))
+        | (get_bishop_moves(square, occupied) & bishops)
+        | (get_rook_moves(square, occupied) & rooks);
+
+    attackers & occupied
+}
+
+fn least_valuable_attacker(board: &Board, attackers: BitBoard) -> Option<(Square, Piece)> {
+    Piece::ALL.iter().find_map(|&piece| {
+        (attackers & board.pieces(piece))
+            .next_square()
+            .map(|square| (square, piece))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Positions with the exact material the capture wins after all the recaptures that pay off.
+    const SEE_CASES: [(&str, &str, i32); 6] = [
+        ("4k3/8/8/4n3/3P4/8/8/4K3 w - - 0 1", "d4e5", 320),
+        ("4k3/8/3q4/4n3/3P4/8/8/4K3 w - - 0 1", "d4e5", 220),
+        ("4k3/8/5p2/4n3/3P4/8/8/4K3 w - - 0 1", "d4e5", 220),
+        ("4k3/8/4p3/5p2/8/8/8/4KQ2 w - - 0 1", "f1f5", -800),
+        ("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1", "e5d6", 100),
+        ("4k3/8/2p5/3n4/8/8/3R4/3RK3 w - - 0 1", "d2d5", -80),
+    ];
+
+    #[test]
+    fn see_scores_hand_picked_exchanges() {
+        for (fen, mv, expected) in SEE_CASES {
+            let board = Board::from_fen(fen, false).unwrap();
+            assert_eq!(see(&board, mv.parse().unwrap()), expected, "{mv}: {fen}");
+        }
+    }
+}
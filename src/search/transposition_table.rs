@@ -1,4 +1,8 @@
-﻿use std::collections::HashMap;
+﻿use crate::eval::eval_is_mate::is_mate_score;
+
+// Depth stored with entries written by the quiescence search, main search entries always have a
+// depth of at least one.
+pub const QSEARCH_DEPTH: u8 = 0;
 
 #[derive(Clone)]
 pub struct TranspositionTable {
@@ -36,11 +40,7 @@ impl TranspositionTable {
         }
     }
 
-    pub fn len(&mut self) -> usize {
-        self.table.len()
-    }
-
-    pub fn clear(&mut self){
+    pub fn clear(&mut self) {
         self.table.iter_mut().for_each(|e| *e = None);
     }
 }
@@ -51,7 +51,6 @@ pub struct TranspositionTableEntry {
     pub score: i32,
     pub best_move: Option<cozy_chess::Move>,
     pub entry_type: TranspositionTableEntryType,
-    pub pv: Vec<cozy_chess::Move>,
     pub hash: u64,
 }
 
@@ -61,3 +60,25 @@ pub enum TranspositionTableEntryType {
     LowerBound,
     UpperBound,
 }
+
+// Mate scores are relative to the root, but the same position can be reached at different plies.
+// Entries store mate scores relative to the node itself and convert them back on load.
+pub fn score_to_tt(score: i32, distance_from_root: u8) -> i32 {
+    if !is_mate_score(score) {
+        score
+    } else if score > 0 {
+        score + distance_from_root as i32
+    } else {
+        score - distance_from_root as i32
+    }
+}
+
+pub fn score_from_tt(score: i32, distance_from_root: u8) -> i32 {
+    if !is_mate_score(score) {
+        score
+    } else if score > 0 {
+        score - distance_from_root as i32
+    } else {
+        score + distance_from_root as i32
+    }
+}
@@ -0,0 +1,4 @@
+﻿#[allow(clippy::module_inception)]
+pub mod tablebase;
+pub mod tb_generate;
+pub mod tb_position;
\ No newline at end of file
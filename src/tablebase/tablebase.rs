@@ -0,0 +1,152 @@
+﻿use crate::tablebase::tb_position::{is_black_stronger, TbLayout, TbPosition};
+use cozy_chess::Board;
+use memmap2::Mmap;
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io;
+use std::path::Path;
+
+pub const TB_FILE_EXTENSION: &str = "ctb";
+
+// File layout: magic, material key (u64) and number of positions (u64) in little endian, then one
+// byte per position.
+const TB_MAGIC: &[u8; 4] = b"CTB1";
+const TB_HEADER_SIZE: usize = 20;
+
+// Distance to mate in plies from the side to move's point of view.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TbResult {
+    Win(u8),
+    Loss(u8),
+    Draw,
+}
+
+// 0 is a draw, otherwise the plies to mate plus one. Wins always take an odd number of plies and
+// losses an even number, so the parity tells them apart.
+pub fn encode_tb_result(result: TbResult) -> u8 {
+    match result {
+        TbResult::Win(plies) | TbResult::Loss(plies) => plies + 1,
+        TbResult::Draw => 0,
+    }
+}
+
+pub fn decode_tb_result(byte: u8) -> TbResult {
+    match byte {
+        0 => TbResult::Draw,
+        _ if (byte - 1).is_multiple_of(2) => TbResult::Loss(byte - 1),
+        _ => TbResult::Win(byte - 1),
+    }
+}
+
+// Tables loaded from disk are memory mapped so only the pages the search touches are read, tables
+// that were just generated are still in memory.
+pub enum TableData {
+    Owned(Vec<u8>),
+    Mapped(Mmap),
+}
+
+impl TableData {
+    pub fn bytes(&self) -> &[u8] {
+        match self {
+            TableData::Owned(data) => data,
+            TableData::Mapped(map) => &map[TB_HEADER_SIZE..],
+        }
+    }
+}
+
+pub struct Tablebase {
+    tables: HashMap<u64, (TbLayout, TableData)>,
+    max_pieces: usize,
+}
+
+impl Tablebase {
+    pub fn new() -> Self {
+        Tablebase {
+            tables: HashMap::new(),
+            max_pieces: 2,
+        }
+    }
+
+    // Maps every table file in `path`.
+    pub fn load(path: &str) -> io::Result<Self> {
+        let mut tablebase = Tablebase::new();
+
+        for entry in fs::read_dir(path)? {
+            let file_path = entry?.path();
+            if file_path.extension().and_then(|extension| extension.to_str()) == Some(TB_FILE_EXTENSION) {
+                let (layout, data) = read_table(&file_path)?;
+                tablebase.insert(layout, data);
+            }
+        }
+
+        Ok(tablebase)
+    }
+
+    pub fn insert(&mut self, layout: TbLayout, data: TableData) {
+        self.max_pieces = self.max_pieces.max(layout.piece_count());
+        self.tables.insert(layout.material_key, (layout, data));
+    }
+
+    pub fn table_count(&self) -> usize {
+        self.tables.len()
+    }
+
+    pub fn max_pieces(&self) -> usize {
+        self.max_pieces
+    }
+
+    pub fn probe(&self, board: &Board) -> Option<TbResult> {
+        if board.occupied().len() as usize > self.max_pieces {
+            return None;
+        }
+
+        self.probe_position(&TbPosition::from_board(board)?)
+    }
+
+    pub fn probe_position(&self, position: &TbPosition) -> Option<TbResult> {
+        // Two bare kings can't mate each other.
+        if position.piece_count() == 2 {
+            return Some(TbResult::Draw);
+        }
+
+        let flipped;
+        let position = if is_black_stronger(position.material_key()) {
+            flipped = position.flipped();
+            &flipped
+        } else {
+            position
+        };
+
+        let (layout, data) = self.tables.get(&position.material_key())?;
+        Some(decode_tb_result(data.bytes()[layout.index(position)]))
+    }
+}
+
+pub fn write_table(file_path: &Path, layout: &TbLayout, data: &[u8]) -> io::Result<()> {
+    let mut bytes = Vec::with_capacity(TB_HEADER_SIZE + data.len());
+    bytes.extend_from_slice(TB_MAGIC);
+    bytes.extend_from_slice(&layout.material_key.to_le_bytes());
+    bytes.extend_from_slice(&(data.len() as u64).to_le_bytes());
+    bytes.extend_from_slice(data);
+
+    fs::write(file_path, bytes)
+}
+
+pub fn read_table(file_path: &Path) -> io::Result<(TbLayout, TableData)> {
+    let file = File::open(file_path)?;
+    // Safety: the tables are only ever written by gen-tb, which doesn't touch existing files.
+    let map = unsafe { Mmap::map(&file)? };
+    let invalid = || io::Error::new(io::ErrorKind::InvalidData, format!("invalid tablebase file {}", file_path.display()));
+
+    if map.len() < TB_HEADER_SIZE || &map[..4] != TB_MAGIC {
+        return Err(invalid());
+    }
+    let material_key = u64::from_le_bytes(map[4..12].try_into().unwrap());
+    let size = u64::from_le_bytes(map[12..20].try_into().unwrap()) as usize;
+    let layout = TbLayout::new(material_key);
+    if size != layout.size || map.len() != TB_HEADER_SIZE + size {
+        return Err(invalid());
+    }
+
+    Ok((layout, TableData::Mapped(map)))
+}
@@ -0,0 +1,195 @@
+﻿use crate::tablebase::tablebase::{
+    encode_tb_result, read_table, write_table, TableData, Tablebase, TbResult, TB_FILE_EXTENSION,
+};
+use crate::tablebase::tb_position::{material_key_shift, TbLayout, TB_PIECE_ORDER};
+use cozy_chess::{Color, Piece};
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::Instant;
+
+// Marks positions that have a capture or promotion into a draw or win, these can never be lost.
+const CANNOT_LOSE: u8 = u8::MAX;
+
+// Generates every table with up to `max_pieces` pieces into `path`. Tables that already exist are
+// loaded instead, the bigger tables need the smaller ones to resolve captures and promotions.
+pub fn generate_tablebases(path: &str, max_pieces: usize) -> io::Result<()> {
+    fs::create_dir_all(path)?;
+    let mut tablebase = Tablebase::new();
+
+    for material_key in get_material_keys(max_pieces) {
+        let layout = TbLayout::new(material_key);
+        let file_path = Path::new(path).join(format!("{}.{TB_FILE_EXTENSION}", layout.name()));
+
+        if let Ok((layout, data)) = read_table(&file_path) {
+            println!("info string gen-tb {} already exists", layout.name());
+            tablebase.insert(layout, data);
+            continue;
+        }
+
+        let start = Instant::now();
+        let data = generate_table(&layout, &tablebase);
+        write_table(&file_path, &layout, &data)?;
+
+        let wins = data.iter().filter(|&&byte| byte % 2 == 0 && byte != 0).count();
+        let longest = data.iter().max().map_or(0, |&byte| byte.saturating_sub(1));
+        println!(
+            "info string gen-tb {} positions {} wins {} longest {} plies time {}ms",
+            layout.name(),
+            layout.size,
+            wins,
+            longest,
+            start.elapsed().as_millis()
+        );
+        tablebase.insert(layout, TableData::Owned(data));
+    }
+
+    Ok(())
+}
+
+// All material combinations with the stronger side as white. Captures and promotions always lead
+// to a combination that comes earlier in the list: fewer pieces, or as many with fewer pawns.
+fn get_material_keys(max_pieces: usize) -> Vec<u64> {
+    let key = |color: Color, piece: Piece| 1u64 << material_key_shift(color, piece);
+    let mut keys = Vec::new();
+
+    if max_pieces >= 3 {
+        for piece in TB_PIECE_ORDER {
+            keys.push(key(Color::White, piece));
+        }
+    }
+    if max_pieces >= 4 {
+        for (index, &first) in TB_PIECE_ORDER.iter().enumerate() {
+            for &second in &TB_PIECE_ORDER[index..] {
+                keys.push(key(Color::White, first) + key(Color::White, second));
+                keys.push(key(Color::White, first) + key(Color::Black, second));
+            }
+        }
+    }
+
+    let pawn_count = |material_key: u64| {
+        [Color::White, Color::Black]
+            .iter()
+            .map(|&color| (material_key >> material_key_shift(color, Piece::Pawn)) & 0xF)
+            .sum::<u64>()
+    };
+    keys.sort_by_key(|&material_key| (TbLayout::new(material_key).piece_count(), pawn_count(material_key)));
+    keys
+}
+
+// Retrograde analysis for distance to mate. Mates, and positions decided by a capture or
+// promotion into a smaller table, are known at the start. From there the results spread backwards
+// one ply at a time: a position is won as soon as one move leads to a lost position, and lost
+// once every move leads to a won one.
+// (see: https://www.chessprogramming.org/Retrograde_Analysis)
+fn generate_table(layout: &TbLayout, tablebase: &Tablebase) -> Vec<u8> {
+    let size = layout.size;
+    let mut values = vec![0u8; size];
+    // Moves that stay inside this table and haven't been shown to win for the opponent yet.
+    let mut open_moves = vec![0u8; size];
+    // The longest loss through a capture or promotion, or CANNOT_LOSE.
+    let mut exit_losses = vec![0u8; size];
+    // Positions to resolve, indexed by their distance to mate in plies.
+    let mut buckets: Vec<Vec<u32>> = vec![Vec::new(); u8::MAX as usize + 1];
+
+    let mut children = Vec::new();
+    let mut child_indices = Vec::new();
+
+    for index in 0..size {
+        let Some(position) = layout.decode(index) else {
+            continue;
+        };
+
+        position.children(&mut children);
+        if children.is_empty() {
+            if position.in_check() {
+                buckets[0].push(index as u32);
+            }
+            exit_losses[index] = CANNOT_LOSE;
+            continue;
+        }
+
+        child_indices.clear();
+        let mut exit_win: Option<u8> = None;
+        let mut exit_loss = 0;
+        let mut cannot_lose = false;
+
+        for child in &children {
+            if child.material_key() == layout.material_key {
+                child_indices.push(layout.index(child));
+                continue;
+            }
+
+            match tablebase.probe_position(child) {
+                Some(TbResult::Loss(plies)) => {
+                    let plies = plies.saturating_add(1);
+                    exit_win = Some(exit_win.map_or(plies, |best| best.min(plies)));
+                }
+                Some(TbResult::Win(plies)) => exit_loss = exit_loss.max(plies.saturating_add(1)),
+                _ => cannot_lose = true,
+            }
+        }
+
+        // Symmetric children share an index, count every distinct one once.
+        child_indices.sort_unstable();
+        child_indices.dedup();
+        open_moves[index] = child_indices.len() as u8;
+
+        if let Some(plies) = exit_win {
+            buckets[plies as usize].push(index as u32);
+            cannot_lose = true;
+        }
+
+        exit_losses[index] = if cannot_lose { CANNOT_LOSE } else { exit_loss };
+        if child_indices.is_empty() && !cannot_lose {
+            buckets[exit_loss as usize].push(index as u32);
+        }
+    }
+
+    let mut predecessors = Vec::new();
+    let mut predecessor_indices = Vec::new();
+
+    for plies in 0..u8::MAX as usize {
+        for index in std::mem::take(&mut buckets[plies]) {
+            let index = index as usize;
+            if values[index] != 0 {
+                continue;
+            }
+
+            let result = if plies % 2 == 0 {
+                TbResult::Loss(plies as u8)
+            } else {
+                TbResult::Win(plies as u8)
+            };
+            values[index] = encode_tb_result(result);
+
+            layout.decode(index).unwrap().predecessors(&mut predecessors);
+            predecessor_indices.clear();
+            predecessor_indices.extend(predecessors.iter().map(|predecessor| layout.index(predecessor)));
+            predecessor_indices.sort_unstable();
+            predecessor_indices.dedup();
+
+            for &predecessor in &predecessor_indices {
+                if values[predecessor] != 0 {
+                    continue;
+                }
+
+                match result {
+                    // Moving into a lost position wins.
+                    TbResult::Loss(_) => buckets[plies + 1].push(predecessor as u32),
+                    // Lost once the last move that might have saved the position turns out to lose.
+                    _ if open_moves[predecessor] > 0 => {
+                        open_moves[predecessor] -= 1;
+                        if open_moves[predecessor] == 0 && exit_losses[predecessor] != CANNOT_LOSE {
+                            let loss_plies = (plies + 1).max(exit_losses[predecessor] as usize);
+                            buckets[loss_plies].push(predecessor as u32);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    values
+}
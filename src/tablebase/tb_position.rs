@@ -0,0 +1,478 @@
+﻿use cozy_chess::{
+    get_bishop_moves, get_king_moves, get_knight_moves, get_pawn_attacks, get_rook_moves,
+    BitBoard, Board, Color, File, Piece, Rank, Square,
+};
+
+pub const MAX_TB_PIECES: usize = 4;
+
+// Order of the non-king pieces in table names and indices, strongest first.
+pub const TB_PIECE_ORDER: [Piece; 5] = [
+    Piece::Queen,
+    Piece::Rook,
+    Piece::Bishop,
+    Piece::Knight,
+    Piece::Pawn,
+];
+
+const PROMOTION_PIECES: [Piece; 4] = [Piece::Queen, Piece::Rook, Piece::Bishop, Piece::Knight];
+
+// Without pawns every position can be mirrored and rotated so the white king ends up in the
+// a1-d1-d4 triangle.
+const KING_TRIANGLE: [Square; 10] = [
+    Square::A1,
+    Square::B1,
+    Square::C1,
+    Square::D1,
+    Square::B2,
+    Square::C2,
+    Square::D2,
+    Square::C3,
+    Square::D3,
+    Square::D4,
+];
+
+#[derive(Clone, Copy)]
+pub struct TbPiece {
+    pub color: Color,
+    pub piece: Piece,
+    pub square: Square,
+}
+
+// A position with at most MAX_TB_PIECES pieces including both kings. Castling and en passant are
+// never possible in tablebase positions.
+#[derive(Clone, Copy)]
+pub struct TbPosition {
+    pub side_to_move: Color,
+    pieces: [TbPiece; MAX_TB_PIECES],
+    count: usize,
+}
+
+impl TbPosition {
+    fn empty(side_to_move: Color) -> Self {
+        TbPosition {
+            side_to_move,
+            pieces: [TbPiece {
+                color: Color::White,
+                piece: Piece::King,
+                square: Square::A1,
+            }; MAX_TB_PIECES],
+            count: 0,
+        }
+    }
+
+    fn push(&mut self, color: Color, piece: Piece, square: Square) {
+        self.pieces[self.count] = TbPiece {
+            color,
+            piece,
+            square,
+        };
+        self.count += 1;
+    }
+
+    // None if the board has too many pieces or castling or en passant is still possible.
+    pub fn from_board(board: &Board) -> Option<Self> {
+        if board.occupied().len() as usize > MAX_TB_PIECES
+            || board.en_passant().is_some()
+            || [Color::White, Color::Black].into_iter().any(|color| {
+                let rights = board.castle_rights(color);
+                rights.short.is_some() || rights.long.is_some()
+            })
+        {
+            return None;
+        }
+
+        let mut position = TbPosition::empty(board.side_to_move());
+        for square in board.occupied() {
+            position.push(
+                board.color_on(square).unwrap(),
+                board.piece_on(square).unwrap(),
+                square,
+            );
+        }
+
+        Some(position)
+    }
+
+    pub fn pieces(&self) -> &[TbPiece] {
+        &self.pieces[..self.count]
+    }
+
+    pub fn piece_count(&self) -> usize {
+        self.count
+    }
+
+    fn occupied(&self) -> BitBoard {
+        self.pieces()
+            .iter()
+            .fold(BitBoard::EMPTY, |occupied, piece| occupied | piece.square.bitboard())
+    }
+
+    fn colors(&self, color: Color) -> BitBoard {
+        self.pieces()
+            .iter()
+            .filter(|piece| piece.color == color)
+            .fold(BitBoard::EMPTY, |occupied, piece| occupied | piece.square.bitboard())
+    }
+
+    fn king(&self, color: Color) -> Square {
+        self.pieces()
+            .iter()
+            .find(|piece| piece.color == color && piece.piece == Piece::King)
+            .unwrap()
+            .square
+    }
+
+    fn is_attacked(&self, square: Square, by: Color) -> bool {
+        let occupied = self.occupied();
+        self.pieces()
+            .iter()
+            .filter(|piece| piece.color == by)
+            .any(|piece| piece_attacks(piece, occupied).has(square))
+    }
+
+    pub fn in_check(&self) -> bool {
+        self.is_attacked(self.king(self.side_to_move), !self.side_to_move)
+    }
+
+    // Number of pieces of every type and colour packed into four bits each, kings excluded.
+    pub fn material_key(&self) -> u64 {
+        self.pieces()
+            .iter()
+            .filter(|piece| piece.piece != Piece::King)
+            .map(|piece| 1 << material_key_shift(piece.color, piece.piece))
+            .sum()
+    }
+
+    // The same position with the colours swapped and the board flipped upside down.
+    pub fn flipped(&self) -> Self {
+        let mut position = TbPosition::empty(!self.side_to_move);
+        for piece in self.pieces() {
+            position.push(!piece.color, piece.piece, piece.square.flip_rank());
+        }
+        position
+    }
+
+    // All legal positions after a move of the side to move.
+    pub fn children(&self, children: &mut Vec<TbPosition>) {
+        children.clear();
+        let occupied = self.occupied();
+        let own = self.colors(self.side_to_move);
+        let enemy_king = self.king(!self.side_to_move).bitboard();
+
+        for (index, piece) in self.pieces().iter().enumerate() {
+            if piece.color != self.side_to_move {
+                continue;
+            }
+
+            let targets = if piece.piece == Piece::Pawn {
+                pawn_targets(piece, occupied, self.colors(!self.side_to_move))
+            } else {
+                piece_attacks(piece, occupied) & !own
+            };
+
+            for target in targets & !enemy_king {
+                let last_rank = Rank::Eighth.relative_to(piece.color);
+                if piece.piece == Piece::Pawn && target.rank() == last_rank {
+                    for promotion in PROMOTION_PIECES {
+                        self.add_child(children, index, target, promotion);
+                    }
+                } else {
+                    self.add_child(children, index, target, piece.piece);
+                }
+            }
+        }
+    }
+
+    fn add_child(&self, children: &mut Vec<TbPosition>, index: usize, target: Square, piece: Piece) {
+        let mover = self.side_to_move;
+        let mut child = TbPosition::empty(!mover);
+
+        for (other_index, other) in self.pieces().iter().enumerate() {
+            if other_index == index {
+                child.push(mover, piece, target);
+            } else if other.square != target {
+                child.push(other.color, other.piece, other.square);
+            }
+        }
+
+        if !child.is_attacked(child.king(mover), !mover) {
+            children.push(child);
+        }
+    }
+
+    // All legal positions the side that is not to move could have come from with a move that
+    // doesn't capture or promote.
+    pub fn predecessors(&self, predecessors: &mut Vec<TbPosition>) {
+        predecessors.clear();
+        let mover = !self.side_to_move;
+        let occupied = self.occupied();
+
+        for (index, piece) in self.pieces().iter().enumerate() {
+            if piece.color != mover {
+                continue;
+            }
+
+            let origins = if piece.piece == Piece::Pawn {
+                pawn_origins(piece, occupied)
+            } else {
+                piece_attacks(piece, occupied) & !occupied
+            };
+
+            for origin in origins {
+                let mut predecessor = *self;
+                predecessor.side_to_move = mover;
+                predecessor.pieces[index].square = origin;
+
+                // The side that just got the move can't have been left in check.
+                if !predecessor.is_attacked(predecessor.king(self.side_to_move), mover) {
+                    predecessors.push(predecessor);
+                }
+            }
+        }
+    }
+}
+
+fn piece_attacks(piece: &TbPiece, occupied: BitBoard) -> BitBoard {
+    match piece.piece {
+        Piece::Pawn => get_pawn_attacks(piece.square, piece.color),
+        Piece::Knight => get_knight_moves(piece.square),
+        Piece::Bishop => get_bishop_moves(piece.square, occupied),
+        Piece::Rook => get_rook_moves(piece.square, occupied),
+        Piece::Queen => {
+            get_bishop_moves(piece.square, occupied) | get_rook_moves(piece.square, occupied)
+        }
+        Piece::King => get_king_moves(piece.square),
+    }
+}
+
+fn pawn_targets(pawn: &TbPiece, occupied: BitBoard, enemies: BitBoard) -> BitBoard {
+    let direction = if pawn.color == Color::White { 1 } else { -1 };
+    let mut targets = get_pawn_attacks(pawn.square, pawn.color) & enemies;
+
+    if let Some(push) = pawn.square.try_offset(0, direction) {
+        if !occupied.has(push) {
+            targets |= push.bitboard();
+
+            if pawn.square.rank() == Rank::Second.relative_to(pawn.color) {
+                let double_push = pawn.square.offset(0, 2 * direction);
+                if !occupied.has(double_push) {
+                    targets |= double_push.bitboard();
+                }
+            }
+        }
+    }
+
+    targets
+}
+
+fn pawn_origins(pawn: &TbPiece, occupied: BitBoard) -> BitBoard {
+    let direction = if pawn.color == Color::White { -1 } else { 1 };
+    let relative_rank = pawn.square.rank().relative_to(pawn.color);
+    let mut origins = BitBoard::EMPTY;
+
+    if relative_rank >= Rank::Third {
+        let origin = pawn.square.offset(0, direction);
+        if !occupied.has(origin) {
+            origins |= origin.bitboard();
+
+            if relative_rank == Rank::Fourth {
+                let double_origin = pawn.square.offset(0, 2 * direction);
+                if !occupied.has(double_origin) {
+                    origins |= double_origin.bitboard();
+                }
+            }
+        }
+    }
+
+    origins
+}
+
+pub fn material_key_shift(color: Color, piece: Piece) -> u32 {
+    (color as u32 * 5 + piece as u32) * 4
+}
+
+fn material_count(material_key: u64, color: Color, piece: Piece) -> u64 {
+    (material_key >> material_key_shift(color, piece)) & 0xF
+}
+
+// Tables are stored with the stronger side as white: more pieces first, then the better pieces.
+pub fn is_black_stronger(material_key: u64) -> bool {
+    let side = |color| {
+        let counts = TB_PIECE_ORDER.map(|piece| material_count(material_key, color, piece));
+        (counts.iter().sum::<u64>(), counts)
+    };
+
+    side(Color::Black) > side(Color::White)
+}
+
+// Describes how the positions of one material combination are laid out in its table.
+pub struct TbLayout {
+    pub material_key: u64,
+    pieces: Vec<(Color, Piece)>,
+    has_pawns: bool,
+    pub size: usize,
+}
+
+impl TbLayout {
+    pub fn new(material_key: u64) -> Self {
+        let mut pieces = Vec::new();
+        for color in [Color::White, Color::Black] {
+            for piece in TB_PIECE_ORDER {
+                for _ in 0..material_count(material_key, color, piece) {
+                    pieces.push((color, piece));
+                }
+            }
+        }
+
+        let has_pawns = pieces.iter().any(|&(_, piece)| piece == Piece::Pawn);
+        let king_squares = if has_pawns { 32 } else { KING_TRIANGLE.len() };
+        let size = 2 * king_squares * 64 * 64usize.pow(pieces.len() as u32);
+
+        TbLayout {
+            material_key,
+            pieces,
+            has_pawns,
+            size,
+        }
+    }
+
+    pub fn piece_count(&self) -> usize {
+        self.pieces.len() + 2
+    }
+
+    // Name like KRvKB, white's pieces before the 'v'.
+    pub fn name(&self) -> String {
+        let mut name = String::from("K");
+        for color in [Color::White, Color::Black] {
+            if color == Color::Black {
+                name.push_str("vK");
+            }
+            for &(piece_color, piece) in &self.pieces {
+                if piece_color == color {
+                    name.push(char::from(piece).to_ascii_uppercase());
+                }
+            }
+        }
+        name
+    }
+
+    // Index of `position`, which must have this layout's material with the stronger side as white.
+    // Symmetric positions share an index.
+    pub fn index(&self, position: &TbPosition) -> usize {
+        let white_king = position.king(Color::White);
+
+        if self.has_pawns {
+            let flip_file = white_king.file() >= File::E;
+            return self.raw_index(position, |square| {
+                if flip_file {
+                    square.flip_file()
+                } else {
+                    square
+                }
+            });
+        }
+
+        let flip_file = white_king.file() >= File::E;
+        let flip_rank = white_king.rank() >= Rank::Fifth;
+        let to_quadrant = |square: Square| {
+            let square = if flip_file { square.flip_file() } else { square };
+            if flip_rank {
+                square.flip_rank()
+            } else {
+                square
+            }
+        };
+        let transpose = |square: Square| {
+            Square::new(File::index(square.rank() as usize), Rank::index(square.file() as usize))
+        };
+
+        let king = to_quadrant(white_king);
+        if king.rank() as usize > king.file() as usize {
+            self.raw_index(position, |square| transpose(to_quadrant(square)))
+        } else if (king.rank() as usize) < king.file() as usize {
+            self.raw_index(position, to_quadrant)
+        } else {
+            // A king on the diagonal leaves two equivalent positions, always take the same one.
+            let index = self.raw_index(position, to_quadrant);
+            let transposed_index = self.raw_index(position, |square| transpose(to_quadrant(square)));
+            index.min(transposed_index)
+        }
+    }
+
+    fn raw_index(&self, position: &TbPosition, transform: impl Fn(Square) -> Square) -> usize {
+        let white_king = transform(position.king(Color::White));
+        let king_index = if self.has_pawns {
+            white_king.rank() as usize * 4 + white_king.file() as usize
+        } else {
+            KING_TRIANGLE.iter().position(|&square| square == white_king).unwrap()
+        };
+        let mut index = king_index * 64 + transform(position.king(Color::Black)) as usize;
+
+        // Identical pieces are indexed with their squares in ascending order.
+        let mut slot = 0;
+        while slot < self.pieces.len() {
+            let (color, piece) = self.pieces[slot];
+            let mut squares = [0; MAX_TB_PIECES];
+            let mut count = 0;
+            for other in position.pieces() {
+                if other.color == color && other.piece == piece {
+                    squares[count] = transform(other.square) as usize;
+                    count += 1;
+                }
+            }
+            squares[..count].sort_unstable();
+
+            for &square in &squares[..count] {
+                index = index * 64 + square;
+            }
+            slot += count.max(1);
+        }
+
+        index * 2 + position.side_to_move as usize
+    }
+
+    // The position at `index`, None if the index doesn't hold a legal position in its canonical
+    // form.
+    pub fn decode(&self, index: usize) -> Option<TbPosition> {
+        let mut rest = index;
+        let side_to_move = Color::index(rest % 2);
+        rest /= 2;
+
+        let mut squares = [Square::A1; MAX_TB_PIECES];
+        for slot in (0..self.pieces.len()).rev() {
+            squares[slot] = Square::index(rest % 64);
+            rest /= 64;
+        }
+        let black_king = Square::index(rest % 64);
+        rest /= 64;
+        let white_king = if self.has_pawns {
+            Square::new(File::index(rest % 4), Rank::index(rest / 4))
+        } else {
+            KING_TRIANGLE[rest]
+        };
+
+        let mut position = TbPosition::empty(side_to_move);
+        position.push(Color::White, Piece::King, white_king);
+        position.push(Color::Black, Piece::King, black_king);
+        for (slot, &(color, piece)) in self.pieces.iter().enumerate() {
+            let square = squares[slot];
+            if position.occupied().has(square) {
+                return None;
+            }
+            if piece == Piece::Pawn && (square.rank() == Rank::First || square.rank() == Rank::Eighth) {
+                return None;
+            }
+            position.push(color, piece, square);
+        }
+
+        if white_king == black_king
+            || get_king_moves(white_king).has(black_king)
+            || position.is_attacked(position.king(!side_to_move), side_to_move)
+            || self.index(&position) != index
+        {
+            return None;
+        }
+
+        Some(position)
+    }
+}
@@ -0,0 +1,2 @@
+﻿pub mod tune_params;
+pub mod tuner;
\ No newline at end of file
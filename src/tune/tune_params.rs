@@ -0,0 +1,464 @@
+﻿use crate::eval::eval_imbalance::{get_imbalance_terms, IMBALANCE_TABLE_SIZE};
+use crate::eval::eval_king_safety::{get_shelter_terms, ShelterTerm};
+use crate::eval::eval_mobility::get_mobility_terms;
+use crate::eval::eval_params::EvalParams;
+use crate::eval::eval_pawn_structure::{get_passed_pawn_terms, get_pawn_terms, PawnTerm};
+use crate::eval::eval_pieces::{get_piece_terms, PieceTerm};
+use crate::eval::eval_threats::{get_threat_terms, ThreatTerm};
+use crate::eval::score::Score;
+use cozy_chess::{Board, Color, Piece};
+
+// Layout of the tuned parameters, every parameter is a middlegame and an endgame value. Every eval
+// parameter is tuned except the king attack units and the king safety table: the units only pick
+// which table entry applies, so the evaluation is a step function of them with no gradient to
+// follow, and the table is left alone so it keeps matching the units that index it.
+pub const MATERIAL_PARAMS: usize = 0;
+pub const PST_PARAMS: usize = MATERIAL_PARAMS + 5;
+pub const STACKED_PAWN_PARAM: usize = PST_PARAMS + 6 * 64;
+pub const ISOLATED_PAWN_PARAM: usize = STACKED_PAWN_PARAM + 1;
+pub const BACKWARD_PAWN_PARAM: usize = ISOLATED_PAWN_PARAM + 1;
+pub const WEAK_UNOPPOSED_PAWN_PARAM: usize = BACKWARD_PAWN_PARAM + 1;
+pub const SUPPORTED_PAWN_PARAMS: usize = WEAK_UNOPPOSED_PAWN_PARAM + 1;
+pub const PHALANX_PAWN_PARAMS: usize = SUPPORTED_PAWN_PARAMS + 8;
+pub const CANDIDATE_PASSER_PARAMS: usize = PHALANX_PAWN_PARAMS + 8;
+pub const PASSED_PAWN_PARAMS: usize = CANDIDATE_PASSER_PARAMS + 8;
+pub const PASSED_PAWN_BLOCKED_PARAM: usize = PASSED_PAWN_PARAMS + 8;
+pub const PASSED_PAWN_FREE_PATH_PARAM: usize = PASSED_PAWN_BLOCKED_PARAM + 1;
+pub const PASSED_PAWN_OWN_KING_DISTANCE_PARAM: usize = PASSED_PAWN_FREE_PATH_PARAM + 1;
+pub const PASSED_PAWN_ENEMY_KING_DISTANCE_PARAM: usize = PASSED_PAWN_OWN_KING_DISTANCE_PARAM + 1;
+pub const PASSED_PAWN_ROOK_BEHIND_PARAM: usize = PASSED_PAWN_ENEMY_KING_DISTANCE_PARAM + 1;
+pub const UNSTOPPABLE_PASSER_PARAM: usize = PASSED_PAWN_ROOK_BEHIND_PARAM + 1;
+pub const KNIGHT_MOBILITY_PARAMS: usize = UNSTOPPABLE_PASSER_PARAM + 1;
+pub const BISHOP_MOBILITY_PARAMS: usize = KNIGHT_MOBILITY_PARAMS + 9;
+pub const ROOK_MOBILITY_PARAMS: usize = BISHOP_MOBILITY_PARAMS + 14;
+pub const QUEEN_MOBILITY_PARAMS: usize = ROOK_MOBILITY_PARAMS + 15;
+pub const TEMPO_PARAM: usize = QUEEN_MOBILITY_PARAMS + 28;
+pub const IMBALANCE_OURS_PARAMS: usize = TEMPO_PARAM + 1;
+pub const IMBALANCE_THEIRS_PARAMS: usize = IMBALANCE_OURS_PARAMS + IMBALANCE_TABLE_SIZE;
+pub const PAWN_SHIELD_PARAMS: usize = IMBALANCE_THEIRS_PARAMS + IMBALANCE_TABLE_SIZE;
+pub const MISSING_SHIELD_PAWN_PARAM: usize = PAWN_SHIELD_PARAMS + 3;
+pub const PAWN_STORM_PARAMS: usize = MISSING_SHIELD_PAWN_PARAM + 1;
+pub const SEMI_OPEN_FILE_PARAM: usize = PAWN_STORM_PARAMS + 5;
+pub const OPEN_FILE_PARAM: usize = SEMI_OPEN_FILE_PARAM + 1;
+pub const BISHOP_PAIR_PARAM: usize = OPEN_FILE_PARAM + 1;
+pub const ROOK_OPEN_FILE_PARAM: usize = BISHOP_PAIR_PARAM + 1;
+pub const ROOK_SEMI_OPEN_FILE_PARAM: usize = ROOK_OPEN_FILE_PARAM + 1;
+pub const QUEEN_OPEN_FILE_PARAM: usize = ROOK_SEMI_OPEN_FILE_PARAM + 1;
+pub const QUEEN_SEMI_OPEN_FILE_PARAM: usize = QUEEN_OPEN_FILE_PARAM + 1;
+pub const ROOK_ON_SEVENTH_PARAM: usize = QUEEN_SEMI_OPEN_FILE_PARAM + 1;
+pub const QUEEN_ON_SEVENTH_PARAM: usize = ROOK_ON_SEVENTH_PARAM + 1;
+pub const KNIGHT_OUTPOST_PARAM: usize = QUEEN_ON_SEVENTH_PARAM + 1;
+pub const BISHOP_OUTPOST_PARAM: usize = KNIGHT_OUTPOST_PARAM + 1;
+pub const BAD_BISHOP_PAWN_PARAM: usize = BISHOP_OUTPOST_PARAM + 1;
+pub const TRAPPED_BISHOP_PARAM: usize = BAD_BISHOP_PAWN_PARAM + 1;
+pub const TRAPPED_ROOK_PARAM: usize = TRAPPED_BISHOP_PARAM + 1;
+pub const THREAT_BY_PAWN_PARAMS: usize = TRAPPED_ROOK_PARAM + 1;
+pub const THREAT_BY_MINOR_PARAMS: usize = THREAT_BY_PAWN_PARAMS + 5;
+pub const THREAT_BY_ROOK_ON_QUEEN_PARAM: usize = THREAT_BY_MINOR_PARAMS + 5;
+pub const HANGING_PIECE_PARAM: usize = THREAT_BY_ROOK_ON_QUEEN_PARAM + 1;
+pub const PAWN_PUSH_THREAT_PARAM: usize = HANGING_PIECE_PARAM + 1;
+pub const PARAM_COUNT: usize = PAWN_PUSH_THREAT_PARAM + 1;
+
+// The evaluation halves the piece square tables.
+const PST_WEIGHT: f32 = 0.5;
+// The imbalance tables are in 1/16 centipawns.
+const IMBALANCE_WEIGHT: f32 = 1.0 / 16.0;
+
+// Middlegame and endgame value of every parameter.
+pub type Params = Vec<[f64; 2]>;
+
+// Parameters that only have an endgame value in the evaluation, their middlegame half stays 0.
+pub fn is_eg_only(param: usize) -> bool {
+    param == PASSED_PAWN_OWN_KING_DISTANCE_PARAM || param == PASSED_PAWN_ENEMY_KING_DISTANCE_PARAM
+}
+
+// Parameters that are a single value the evaluation uses for both phases, their two halves start
+// equal and have to move together.
+pub fn is_single_value(param: usize) -> bool {
+    (IMBALANCE_OURS_PARAMS..PAWN_SHIELD_PARAMS).contains(&param)
+}
+
+// The values `eval_params` has for the tuned parameters.
+pub fn get_initial_params(eval_params: &EvalParams) -> Params {
+    let mut params = vec![[0.0; 2]; PARAM_COUNT];
+    let mut set = |param: usize, score: Score| params[param] = [score.mg() as f64, score.eg() as f64];
+
+    for (i, &score) in eval_params.piece_scores.iter().enumerate() {
+        set(MATERIAL_PARAMS + i, score);
+    }
+    for (piece_index, table) in eval_params.pst.iter().enumerate() {
+        for (index, &score) in table.iter().enumerate() {
+            set(PST_PARAMS + piece_index * 64 + index, score);
+        }
+    }
+
+    set(STACKED_PAWN_PARAM, eval_params.stacked_pawn_penalty);
+    set(ISOLATED_PAWN_PARAM, eval_params.isolated_pawn_penalty);
+    set(BACKWARD_PAWN_PARAM, eval_params.backward_pawn_penalty);
+    set(WEAK_UNOPPOSED_PAWN_PARAM, eval_params.weak_unopposed_pawn_penalty);
+    for rank in 0..8 {
+        set(SUPPORTED_PAWN_PARAMS + rank, eval_params.supported_pawn_bonus[rank]);
+        set(PHALANX_PAWN_PARAMS + rank, eval_params.phalanx_pawn_bonus[rank]);
+        set(CANDIDATE_PASSER_PARAMS + rank, eval_params.candidate_passer_bonus[rank]);
+        set(PASSED_PAWN_PARAMS + rank, eval_params.passed_pawn_bonus[rank]);
+    }
+    set(PASSED_PAWN_BLOCKED_PARAM, eval_params.passed_pawn_blocked_penalty);
+    set(PASSED_PAWN_FREE_PATH_PARAM, eval_params.passed_pawn_free_path_bonus);
+    set(PASSED_PAWN_OWN_KING_DISTANCE_PARAM, Score::new(0, eval_params.passed_pawn_own_king_distance));
+    set(PASSED_PAWN_ENEMY_KING_DISTANCE_PARAM, Score::new(0, eval_params.passed_pawn_enemy_king_distance));
+    set(PASSED_PAWN_ROOK_BEHIND_PARAM, eval_params.passed_pawn_rook_behind_bonus);
+    set(UNSTOPPABLE_PASSER_PARAM, eval_params.unstoppable_passer_bonus);
+    for (i, &score) in eval_params.knight_mobility.iter().enumerate() {
+        set(KNIGHT_MOBILITY_PARAMS + i, score);
+    }
+    for (i, &score) in eval_params.bishop_mobility.iter().enumerate() {
+        set(BISHOP_MOBILITY_PARAMS + i, score);
+    }
+    for (i, &score) in eval_params.rook_mobility.iter().enumerate() {
+        set(ROOK_MOBILITY_PARAMS + i, score);
+    }
+    for (i, &score) in eval_params.queen_mobility.iter().enumerate() {
+        set(QUEEN_MOBILITY_PARAMS + i, score);
+    }
+    set(TEMPO_PARAM, eval_params.tempo);
+    for i in 0..IMBALANCE_TABLE_SIZE {
+        set(IMBALANCE_OURS_PARAMS + i, Score::new(eval_params.imbalance_ours[i], eval_params.imbalance_ours[i]));
+        set(IMBALANCE_THEIRS_PARAMS + i, Score::new(eval_params.imbalance_theirs[i], eval_params.imbalance_theirs[i]));
+    }
+
+    for (i, &score) in eval_params.pawn_shield_bonus.iter().enumerate() {
+        set(PAWN_SHIELD_PARAMS + i, score);
+    }
+    set(MISSING_SHIELD_PAWN_PARAM, eval_params.missing_shield_pawn_penalty);
+    for (i, &score) in eval_params.pawn_storm_penalty.iter().enumerate() {
+        set(PAWN_STORM_PARAMS + i, score);
+    }
+    set(SEMI_OPEN_FILE_PARAM, eval_params.semi_open_file_penalty);
+    set(OPEN_FILE_PARAM, eval_params.open_file_penalty);
+
+    set(BISHOP_PAIR_PARAM, eval_params.bishop_pair_bonus);
+    set(ROOK_OPEN_FILE_PARAM, eval_params.rook_open_file_bonus);
+    set(ROOK_SEMI_OPEN_FILE_PARAM, eval_params.rook_semi_open_file_bonus);
+    set(QUEEN_OPEN_FILE_PARAM, eval_params.queen_open_file_bonus);
+    set(QUEEN_SEMI_OPEN_FILE_PARAM, eval_params.queen_semi_open_file_bonus);
+    set(ROOK_ON_SEVENTH_PARAM, eval_params.rook_on_seventh_bonus);
+    set(QUEEN_ON_SEVENTH_PARAM, eval_params.queen_on_seventh_bonus);
+    set(KNIGHT_OUTPOST_PARAM, eval_params.knight_outpost_bonus);
+    set(BISHOP_OUTPOST_PARAM, eval_params.bishop_outpost_bonus);
+    set(BAD_BISHOP_PAWN_PARAM, eval_params.bad_bishop_pawn_penalty);
+    set(TRAPPED_BISHOP_PARAM, eval_params.trapped_bishop_penalty);
+    set(TRAPPED_ROOK_PARAM, eval_params.trapped_rook_penalty);
+
+    for i in 0..5 {
+        set(THREAT_BY_PAWN_PARAMS + i, eval_params.threat_by_pawn[i]);
+        set(THREAT_BY_MINOR_PARAMS + i, eval_params.threat_by_minor[i]);
+    }
+    set(THREAT_BY_ROOK_ON_QUEEN_PARAM, eval_params.threat_by_rook_on_queen);
+    set(HANGING_PIECE_PARAM, eval_params.hanging_piece_bonus);
+    set(PAWN_PUSH_THREAT_PARAM, eval_params.pawn_push_threat_bonus);
+
+    params
+}
+
+// Writes the tuned parameters back into `eval_params`, rounded to whole values. The parameters
+// that aren't tuned are left as they are.
+pub fn apply_params(params: &Params, eval_params: &mut EvalParams) {
+    let score = |param: usize| {
+        let [mg, eg] = params[param];
+        Score::new(mg.round() as i32, eg.round() as i32)
+    };
+
+    for (i, piece_score) in eval_params.piece_scores.iter_mut().enumerate() {
+        *piece_score = score(MATERIAL_PARAMS + i);
+    }
+    for (piece_index, table) in eval_params.pst.iter_mut().enumerate() {
+        for (index, pst_score) in table.iter_mut().enumerate() {
+            *pst_score = score(PST_PARAMS + piece_index * 64 + index);
+        }
+    }
+
+    eval_params.stacked_pawn_penalty = score(STACKED_PAWN_PARAM);
+    eval_params.isolated_pawn_penalty = score(ISOLATED_PAWN_PARAM);
+    eval_params.backward_pawn_penalty = score(BACKWARD_PAWN_PARAM);
+    eval_params.weak_unopposed_pawn_penalty = score(WEAK_UNOPPOSED_PAWN_PARAM);
+    for rank in 0..8 {
+        eval_params.supported_pawn_bonus[rank] = score(SUPPORTED_PAWN_PARAMS + rank);
+        eval_params.phalanx_pawn_bonus[rank] = score(PHALANX_PAWN_PARAMS + rank);
+        eval_params.candidate_passer_bonus[rank] = score(CANDIDATE_PASSER_PARAMS + rank);
+        eval_params.passed_pawn_bonus[rank] = score(PASSED_PAWN_PARAMS + rank);
+    }
+    eval_params.passed_pawn_blocked_penalty = score(PASSED_PAWN_BLOCKED_PARAM);
+    eval_params.passed_pawn_free_path_bonus = score(PASSED_PAWN_FREE_PATH_PARAM);
+    eval_params.passed_pawn_own_king_distance = score(PASSED_PAWN_OWN_KING_DISTANCE_PARAM).eg();
+    eval_params.passed_pawn_enemy_king_distance = score(PASSED_PAWN_ENEMY_KING_DISTANCE_PARAM).eg();
+    eval_params.passed_pawn_rook_behind_bonus = score(PASSED_PAWN_ROOK_BEHIND_PARAM);
+    eval_params.unstoppable_passer_bonus = score(UNSTOPPABLE_PASSER_PARAM);
+    for (i, mobility_score) in eval_params.knight_mobility.iter_mut().enumerate() {
+        *mobility_score = score(KNIGHT_MOBILITY_PARAMS + i);
+    }
+    for (i, mobility_score) in eval_params.bishop_mobility.iter_mut().enumerate() {
+        *mobility_score = score(BISHOP_MOBILITY_PARAMS + i);
+    }
+    for (i, mobility_score) in eval_params.rook_mobility.iter_mut().enumerate() {
+        *mobility_score = score(ROOK_MOBILITY_PARAMS + i);
+    }
+    for (i, mobility_score) in eval_params.queen_mobility.iter_mut().enumerate() {
+        *mobility_score = score(QUEEN_MOBILITY_PARAMS + i);
+    }
+    eval_params.tempo = score(TEMPO_PARAM);
+    for i in 0..IMBALANCE_TABLE_SIZE {
+        eval_params.imbalance_ours[i] = score(IMBALANCE_OURS_PARAMS + i).mg();
+        eval_params.imbalance_theirs[i] = score(IMBALANCE_THEIRS_PARAMS + i).mg();
+    }
+
+    for (i, shield_score) in eval_params.pawn_shield_bonus.iter_mut().enumerate() {
+        *shield_score = score(PAWN_SHIELD_PARAMS + i);
+    }
+    eval_params.missing_shield_pawn_penalty = score(MISSING_SHIELD_PAWN_PARAM);
+    for (i, storm_score) in eval_params.pawn_storm_penalty.iter_mut().enumerate() {
+        *storm_score = score(PAWN_STORM_PARAMS + i);
+    }
+    eval_params.semi_open_file_penalty = score(SEMI_OPEN_FILE_PARAM);
+    eval_params.open_file_penalty = score(OPEN_FILE_PARAM);
+
+    eval_params.bishop_pair_bonus = score(BISHOP_PAIR_PARAM);
+    eval_params.rook_open_file_bonus = score(ROOK_OPEN_FILE_PARAM);
+    eval_params.rook_semi_open_file_bonus = score(ROOK_SEMI_OPEN_FILE_PARAM);
+    eval_params.queen_open_file_bonus = score(QUEEN_OPEN_FILE_PARAM);
+    eval_params.queen_semi_open_file_bonus = score(QUEEN_SEMI_OPEN_FILE_PARAM);
+    eval_params.rook_on_seventh_bonus = score(ROOK_ON_SEVENTH_PARAM);
+    eval_params.queen_on_seventh_bonus = score(QUEEN_ON_SEVENTH_PARAM);
+    eval_params.knight_outpost_bonus = score(KNIGHT_OUTPOST_PARAM);
+    eval_params.bishop_outpost_bonus = score(BISHOP_OUTPOST_PARAM);
+    eval_params.bad_bishop_pawn_penalty = score(BAD_BISHOP_PAWN_PARAM);
+    eval_params.trapped_bishop_penalty = score(TRAPPED_BISHOP_PARAM);
+    eval_params.trapped_rook_penalty = score(TRAPPED_ROOK_PARAM);
+
+    for i in 0..5 {
+        eval_params.threat_by_pawn[i] = score(THREAT_BY_PAWN_PARAMS + i);
+        eval_params.threat_by_minor[i] = score(THREAT_BY_MINOR_PARAMS + i);
+    }
+    eval_params.threat_by_rook_on_queen = score(THREAT_BY_ROOK_ON_QUEEN_PARAM);
+    eval_params.hanging_piece_bonus = score(HANGING_PIECE_PARAM);
+    eval_params.pawn_push_threat_bonus = score(PAWN_PUSH_THREAT_PARAM);
+}
+
+// How often each parameter counts towards the white relative evaluation of `board`, white's
+// terms count positive and black's negative. Parameters that cancel out are left out.
+pub fn get_coefficients(board: &Board, coefficients: &mut Vec<(u16, f32)>) {
+    let mut dense = [0.0f32; PARAM_COUNT];
+
+    for color in [Color::White, Color::Black] {
+        let sign = if color == Color::White { 1.0 } else { -1.0 };
+
+        for (piece_index, &piece) in Piece::ALL.iter().enumerate() {
+            for square in board.colored_pieces(color, piece) {
+                if piece != Piece::King {
+                    dense[MATERIAL_PARAMS + piece_index] += sign;
+                }
+                let index = if color == Color::White { square as usize ^ 56 } else { square as usize };
+                dense[PST_PARAMS + piece_index * 64 + index] += sign * PST_WEIGHT;
+            }
+        }
+
+        let mut add_pawn_term = |term: PawnTerm| {
+            let (param, count) = get_pawn_term_param(term);
+            dense[param] += sign * count as f32;
+            if let PawnTerm::PassedKingDistance { own, .. } = term {
+                dense[PASSED_PAWN_OWN_KING_DISTANCE_PARAM] -= sign * own as f32;
+            }
+        };
+        let passed_pawns = get_pawn_terms(board, color, &mut add_pawn_term);
+        get_passed_pawn_terms(board, passed_pawns, color, &mut add_pawn_term);
+
+        get_mobility_terms(board, color, |piece, count| {
+            let params = match piece {
+                Piece::Knight => KNIGHT_MOBILITY_PARAMS,
+                Piece::Bishop => BISHOP_MOBILITY_PARAMS,
+                Piece::Rook => ROOK_MOBILITY_PARAMS,
+                _ => QUEEN_MOBILITY_PARAMS,
+            };
+            dense[params + count] += sign;
+        });
+
+        if board.side_to_move() == color {
+            dense[TEMPO_PARAM] += sign;
+        }
+
+        get_imbalance_terms(board, color, |index, ours, theirs| {
+            dense[IMBALANCE_OURS_PARAMS + index] += sign * ours as f32 * IMBALANCE_WEIGHT;
+            dense[IMBALANCE_THEIRS_PARAMS + index] += sign * theirs as f32 * IMBALANCE_WEIGHT;
+        });
+        get_shelter_terms(board, color, |term| {
+            let (param, count) = get_shelter_term_param(term);
+            dense[param] += sign * count as f32;
+        });
+        get_piece_terms(board, color, |term| {
+            let (param, count) = get_piece_term_param(term);
+            dense[param] += sign * count as f32;
+        });
+        get_threat_terms(board, color, |term| {
+            let (param, count) = get_threat_term_param(term);
+            dense[param] += sign * count as f32;
+        });
+    }
+
+    coefficients.clear();
+    coefficients.extend(
+        dense
+            .iter()
+            .enumerate()
+            .filter(|(_, &coefficient)| coefficient != 0.0)
+            .map(|(param, &coefficient)| (param as u16, coefficient)),
+    );
+}
+
+// The parameter behind a pawn term and how many times it counts. Penalties are stored as positive
+// values, so they count negative. The king distance term uses two parameters, this returns the
+// enemy king one.
+fn get_pawn_term_param(term: PawnTerm) -> (usize, i32) {
+    match term {
+        PawnTerm::Stacked => (STACKED_PAWN_PARAM, -1),
+        PawnTerm::Isolated => (ISOLATED_PAWN_PARAM, -1),
+        PawnTerm::Backward => (BACKWARD_PAWN_PARAM, -1),
+        PawnTerm::WeakUnopposed => (WEAK_UNOPPOSED_PAWN_PARAM, -1),
+        PawnTerm::Supported(rank) => (SUPPORTED_PAWN_PARAMS + rank, 1),
+        PawnTerm::Phalanx(rank) => (PHALANX_PAWN_PARAMS + rank, 1),
+        PawnTerm::CandidatePasser(rank) => (CANDIDATE_PASSER_PARAMS + rank, 1),
+        PawnTerm::Passed(rank) => (PASSED_PAWN_PARAMS + rank, 1),
+        PawnTerm::PassedBlocked(weight) => (PASSED_PAWN_BLOCKED_PARAM, -weight),
+        PawnTerm::PassedFreePath(weight) => (PASSED_PAWN_FREE_PATH_PARAM, weight),
+        PawnTerm::PassedKingDistance { enemy, .. } => (PASSED_PAWN_ENEMY_KING_DISTANCE_PARAM, enemy),
+        PawnTerm::PassedRookBehind => (PASSED_PAWN_ROOK_BEHIND_PARAM, 1),
+        PawnTerm::UnstoppablePasser => (UNSTOPPABLE_PASSER_PARAM, 1),
+    }
+}
+
+fn get_shelter_term_param(term: ShelterTerm) -> (usize, i32) {
+    match term {
+        ShelterTerm::OpenFile => (OPEN_FILE_PARAM, -1),
+        ShelterTerm::SemiOpenFile => (SEMI_OPEN_FILE_PARAM, -1),
+        ShelterTerm::Shield(distance) => (PAWN_SHIELD_PARAMS + distance, 1),
+        ShelterTerm::MissingShield => (MISSING_SHIELD_PAWN_PARAM, -1),
+        ShelterTerm::Storm(distance) => (PAWN_STORM_PARAMS + distance, -1),
+    }
+}
+
+fn get_piece_term_param(term: PieceTerm) -> (usize, i32) {
+    match term {
+        PieceTerm::BishopPair => (BISHOP_PAIR_PARAM, 1),
+        PieceTerm::OpenFile(Piece::Rook) => (ROOK_OPEN_FILE_PARAM, 1),
+        PieceTerm::OpenFile(_) => (QUEEN_OPEN_FILE_PARAM, 1),
+        PieceTerm::SemiOpenFile(Piece::Rook) => (ROOK_SEMI_OPEN_FILE_PARAM, 1),
+        PieceTerm::SemiOpenFile(_) => (QUEEN_SEMI_OPEN_FILE_PARAM, 1),
+        PieceTerm::SeventhRank(Piece::Rook) => (ROOK_ON_SEVENTH_PARAM, 1),
+        PieceTerm::SeventhRank(_) => (QUEEN_ON_SEVENTH_PARAM, 1),
+        PieceTerm::Outpost(Piece::Knight) => (KNIGHT_OUTPOST_PARAM, 1),
+        PieceTerm::Outpost(_) => (BISHOP_OUTPOST_PARAM, 1),
+        PieceTerm::BadBishopPawns(count) => (BAD_BISHOP_PAWN_PARAM, -count),
+        PieceTerm::TrappedBishop => (TRAPPED_BISHOP_PARAM, -1),
+        PieceTerm::TrappedRook => (TRAPPED_ROOK_PARAM, -1),
+    }
+}
+
+fn get_threat_term_param(term: ThreatTerm) -> (usize, i32) {
+    match term {
+        ThreatTerm::ByPawn(victim) => (THREAT_BY_PAWN_PARAMS + victim, 1),
+        ThreatTerm::ByMinor(victim) => (THREAT_BY_MINOR_PARAMS + victim, 1),
+        ThreatTerm::RookOnQueen(count) => (THREAT_BY_ROOK_ON_QUEEN_PARAM, count),
+        ThreatTerm::Hanging(count) => (HANGING_PIECE_PARAM, count),
+        ThreatTerm::PawnPush(count) => (PAWN_PUSH_THREAT_PARAM, count),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eval::eval::eval_tapered;
+    use crate::eval::material_hash_table::{MaterialHashTable, MATERIAL_HASH_TABLE_SIZE};
+    use crate::eval::pawn_hash_table::{PawnHashTable, PAWN_HASH_TABLE_SIZE};
+
+    const POSITIONS: [&str; 8] = [
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        "2kr3r/ppp2ppp/2n1bn2/2b1p3/4P3/2NP1N2/PPP1BPPP/R1B2RK1 b - - 3 9",
+        "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+        "6k1/5pp1/7p/8/8/1Q5P/5PP1/6K1 b - - 0 1",
+        "6k1/R4ppp/8/8/8/8/5PPP/6K1 w - - 0 1",
+        "4k3/pp3ppp/8/3N4/4P3/8/PP3PPP/4K3 w - - 0 1",
+        "4k3/B7/1p6/8/8/8/8/4K3 w - - 0 1",
+    ];
+
+    // Moves every parameter the `include` filter picks by a small even amount and returns the
+    // changed parameters together with the evaluation parameters they apply to.
+    fn change_params(eval_params: &EvalParams, include: impl Fn(usize) -> bool) -> (Params, EvalParams) {
+        let mut changed = get_initial_params(eval_params);
+        for (param, values) in changed.iter_mut().enumerate().filter(|(param, _)| include(*param)) {
+            let delta = [-4.0, -2.0, 2.0, 4.0][param % 4];
+            if is_single_value(param) {
+                values[0] += delta / IMBALANCE_WEIGHT as f64;
+                values[1] += delta / IMBALANCE_WEIGHT as f64;
+                continue;
+            }
+            values[0] += if is_eg_only(param) { 0.0 } else { delta };
+            values[1] -= delta;
+        }
+        let mut changed_eval_params = eval_params.clone();
+        apply_params(&changed, &mut changed_eval_params);
+        (changed, changed_eval_params)
+    }
+
+    // Returns the change of the evaluation of `board` the coefficients predict.
+    fn predicted_difference(board: &Board, initial: &Params, changed: &Params) -> (f64, f64) {
+        let mut coefficients = Vec::new();
+        get_coefficients(board, &mut coefficients);
+        let (mut mg, mut eg) = (0.0, 0.0);
+        for &(param, coefficient) in &coefficients {
+            let param = param as usize;
+            mg += coefficient as f64 * (changed[param][0] - initial[param][0]);
+            eg += coefficient as f64 * (changed[param][1] - initial[param][1]);
+        }
+        (mg, eg)
+    }
+
+    fn eval_tapered_with(board: &Board, eval_params: &EvalParams) -> Score {
+        let mut pawn_hash_table = PawnHashTable::new(PAWN_HASH_TABLE_SIZE);
+        let mut material_hash_table = MaterialHashTable::new(MATERIAL_HASH_TABLE_SIZE);
+        eval_tapered(board, eval_params, &mut pawn_hash_table, &mut material_hash_table)
+    }
+
+    // Changing the tuned parameters has to change the evaluation by exactly what the coefficients
+    // say, otherwise the tuner optimises something else than the evaluation. The changes are even
+    // so halving the piece square tables doesn't round.
+    #[test]
+    fn coefficients_match_eval() {
+        let eval_params = EvalParams::default();
+        let initial = get_initial_params(&eval_params);
+        let (changed, changed_eval_params) = change_params(&eval_params, |param| !is_single_value(param));
+
+        for fen in POSITIONS {
+            let board = Board::from_fen(fen, false).unwrap();
+            let (mg, eg) = predicted_difference(&board, &initial, &changed);
+            let difference = eval_tapered_with(&board, &changed_eval_params) - eval_tapered_with(&board, &eval_params);
+            assert_eq!(difference, Score::new(mg as i32, eg as i32), "{fen}");
+        }
+    }
+
+    // The imbalance of each side is divided by 16 and truncated, so both evaluations can be off
+    // from the linear model by less than 2 and their difference by less than 4.
+    #[test]
+    fn imbalance_coefficients_match_eval() {
+        let eval_params = EvalParams::default();
+        let initial = get_initial_params(&eval_params);
+        let (changed, changed_eval_params) = change_params(&eval_params, is_single_value);
+
+        for fen in POSITIONS {
+            let board = Board::from_fen(fen, false).unwrap();
+            let (mg, eg) = predicted_difference(&board, &initial, &changed);
+            assert_eq!(mg, eg, "{fen}");
+            let difference = eval_tapered_with(&board, &changed_eval_params) - eval_tapered_with(&board, &eval_params);
+            assert!((difference.mg() as f64 - mg).abs() < 4.0, "{fen}: {} vs {mg}", difference.mg());
+            assert!((difference.eg() as f64 - eg).abs() < 4.0, "{fen}: {} vs {eg}", difference.eg());
+        }
+    }
+}
@@ -0,0 +1,299 @@
+﻿use crate::eval::endgame::{get_scale_factor, probe_endgame_eval, SCALE_FACTOR_NORMAL};
+use crate::eval::eval::eval_tapered;
+use crate::eval::eval_params::EvalParams;
+use crate::eval::game_phase::get_game_phase;
+use crate::eval::material_hash_table::{MaterialHashTable, MATERIAL_HASH_TABLE_SIZE};
+use crate::eval::pawn_hash_table::{PawnHashTable, PAWN_HASH_TABLE_SIZE};
+use crate::tune::tune_params::{apply_params, get_coefficients, get_initial_params, is_eg_only, is_single_value, Params, PARAM_COUNT};
+use crate::util::adam::Adam;
+use crate::util::options::parse_option_pairs;
+use cozy_chess::{Board, GameStatus};
+use std::fs;
+use std::io;
+use std::time::Instant;
+
+const REPORT_INTERVAL: usize = 10;
+// K is searched for in this range by golden section search.
+const K_SEARCH_RANGE: (f64, f64) = (0.0, 4.0);
+const K_SEARCH_ITERATIONS: usize = 50;
+
+pub struct TuneOptions {
+    pub positions_path: String,
+    pub output_path: String,
+    pub epochs: usize,
+    pub learning_rate: f64,
+    pub threads: usize,
+    pub k: Option<f64>, // Fitted to the data when not given
+    pub save_interval: usize, // Epochs between writing the parameters out
+    pub eval_params: EvalParams, // Starting point of the tuning, set with the --eval-params flag
+}
+
+impl TuneOptions {
+    // tune <positions file> [epochs N] [learning-rate X] [threads N] [k X] [output PATH]
+    //      [save-interval N]
+    pub fn from_args(args: &[String]) -> Result<Self, String> {
+        let mut options = TuneOptions {
+            positions_path: args.first().cloned().ok_or("missing positions file")?,
+            output_path: "tuned.txt".to_string(),
+            epochs: 1000,
+            learning_rate: 1.0,
+            threads: 1,
+            k: None,
+            save_interval: 100,
+            eval_params: EvalParams::default(),
+        };
+
+        parse_option_pairs(&args[1..], |name, value| {
+            match name {
+                "epochs" => options.epochs = value.parse().map_err(|_| ())?,
+                "learning-rate" => options.learning_rate = value.parse().map_err(|_| ())?,
+                "threads" => options.threads = value.parse::<usize>().map_err(|_| ())?.max(1),
+                "k" => options.k = Some(value.parse().map_err(|_| ())?),
+                "output" => options.output_path = value.to_string(),
+                "save-interval" => options.save_interval = value.parse::<usize>().map_err(|_| ())?.max(1),
+                _ => return Ok(false),
+            }
+            Ok(true)
+        })?;
+
+        Ok(options)
+    }
+}
+
+// The white relative evaluation of a position is linear in the tuned parameters:
+//   eval = offset + sum(coefficient * (mg * mg_weight + eg * eg_weight))
+// where the weights hold the game phase and the endgame scale factor, and the offset holds every
+// term that isn't tuned. The coefficients of all entries live in one shared vector.
+struct TuneEntry {
+    coefficients_start: u32,
+    coefficients_count: u32,
+    mg_weight: f32,
+    eg_weight: f32,
+    offset: f32,
+    result: f32,
+}
+
+struct TuneData {
+    entries: Vec<TuneEntry>,
+    coefficients: Vec<(u16, f32)>,
+}
+
+pub fn run_tune(options: &TuneOptions) -> io::Result<()> {
+    let start = Instant::now();
+    let mut params = get_initial_params(&options.eval_params);
+    let data = load_positions(&options.positions_path, &options.eval_params, &params)?;
+    println!(
+        "info string tune loaded {} positions with {} coefficients in {}ms",
+        data.entries.len(),
+        data.coefficients.len(),
+        start.elapsed().as_millis()
+    );
+    if data.entries.is_empty() {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "no usable positions"));
+    }
+
+    let k = options.k.unwrap_or_else(|| fit_k(&data, &params, options.threads));
+    println!("info string tune k {k:.4} loss {:.6}", get_loss_and_gradient(&data, &params, k, options.threads, false).0);
+
+    let mut adam = Adam::new(PARAM_COUNT * 2, options.learning_rate);
+
+    for epoch in 1..=options.epochs {
+        let (loss, mut gradient) = get_loss_and_gradient(&data, &params, k, options.threads, true);
+
+        // The middlegame half of the endgame only parameters isn't used, it has to stay 0.
+        for (param, g) in gradient.iter_mut().enumerate() {
+            if is_eg_only(param) {
+                g[0] = 0.0;
+            }
+            // Both halves of a single value parameter get the gradient of the whole, so they stay
+            // equal.
+            if is_single_value(param) {
+                let sum = g[0] + g[1];
+                *g = [sum, sum];
+            }
+        }
+        adam.step(params.as_flattened_mut(), gradient.as_flattened());
+
+        if epoch % REPORT_INTERVAL == 0 || epoch == options.epochs {
+            println!("info string tune epoch {epoch} loss {loss:.6} time {}ms", start.elapsed().as_millis());
+        }
+        if epoch % options.save_interval == 0 || epoch == options.epochs {
+            let header = format!(
+                "Tuned on {} positions from {}, k {k:.4}, epoch {epoch}, loss {loss:.6}",
+                data.entries.len(),
+                options.positions_path
+            );
+            let mut eval_params = options.eval_params.clone();
+            apply_params(&params, &mut eval_params);
+            eval_params.save(&options.output_path, &header)?;
+        }
+    }
+
+    Ok(())
+}
+
+// Reads one position per line in any of these formats:
+//   <fen> | <score> | <result>       as written by datagen
+//   <epd> c9 "<result>";
+//   <fen or epd> [<result>]
+// with the result as 1-0, 0-1, 1/2-1/2 or 1.0, 0.0, 0.5 from white's point of view. Positions
+// that are over or have a specialised endgame evaluator are skipped, the tuned terms don't apply
+// to them.
+fn load_positions(path: &str, eval_params: &EvalParams, params: &Params) -> io::Result<TuneData> {
+    let mut data = TuneData {
+        entries: Vec::new(),
+        coefficients: Vec::new(),
+    };
+    let mut pawn_hash_table = PawnHashTable::new(PAWN_HASH_TABLE_SIZE);
+    let mut material_hash_table = MaterialHashTable::new(MATERIAL_HASH_TABLE_SIZE);
+    let mut coefficients = Vec::new();
+
+    for line in fs::read_to_string(path)?.lines() {
+        let Some((board, result)) = parse_position(line) else {
+            continue;
+        };
+        if board.status() != GameStatus::Ongoing || probe_endgame_eval(&board).is_some() {
+            continue;
+        }
+
+        let score = eval_tapered(&board, eval_params, &mut pawn_hash_table, &mut material_hash_table);
+        let phase = get_game_phase(&board) as f32;
+        let scale_factor = get_scale_factor(&board, score.eg()) as f32 / SCALE_FACTOR_NORMAL as f32;
+        let mg_weight = (256.0 - phase) / 256.0;
+        let eg_weight = phase / 256.0 * scale_factor;
+
+        get_coefficients(&board, &mut coefficients);
+        let (mut linear_mg, mut linear_eg) = (0.0, 0.0);
+        for &(param, coefficient) in &coefficients {
+            linear_mg += coefficient * params[param as usize][0] as f32;
+            linear_eg += coefficient * params[param as usize][1] as f32;
+        }
+
+        data.entries.push(TuneEntry {
+            coefficients_start: data.coefficients.len() as u32,
+            coefficients_count: coefficients.len() as u32,
+            mg_weight,
+            eg_weight,
+            offset: (score.mg() as f32 - linear_mg) * mg_weight + (score.eg() as f32 - linear_eg) * eg_weight,
+            result,
+        });
+        data.coefficients.extend_from_slice(&coefficients);
+    }
+
+    Ok(data)
+}
+
+fn parse_position(line: &str) -> Option<(Board, f32)> {
+    let line = line.trim();
+    let (fen, result) = if line.contains('|') {
+        let mut parts = line.split('|');
+        (parts.next()?.trim(), parts.next_back()?.trim())
+    } else if let Some(index) = line.find(" c9 ") {
+        (&line[..index], line[index + 4..].trim().trim_end_matches(';').trim_matches('"'))
+    } else if let Some(index) = line.find('[') {
+        (line[..index].trim(), line[index + 1..].trim_end().trim_end_matches(']'))
+    } else {
+        return None;
+    };
+
+    let result = match result {
+        "1-0" | "1.0" | "1" => 1.0,
+        "1/2-1/2" | "0.5" => 0.5,
+        "0-1" | "0.0" | "0" => 0.0,
+        _ => return None,
+    };
+
+    // EPD leaves out the move counters.
+    let board = if fen.split_whitespace().count() == 4 {
+        Board::from_fen(&format!("{fen} 0 1"), false)
+    } else {
+        Board::from_fen(fen, false)
+    };
+
+    Some((board.ok()?, result))
+}
+
+fn sigmoid(score: f64, k: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-k * score / 400.0))
+}
+
+// Mean logistic (cross entropy) loss over all entries, and its gradient for every parameter when
+// `with_gradient` is set. The entries are split over `threads` threads.
+fn get_loss_and_gradient(data: &TuneData, params: &Params, k: f64, threads: usize, with_gradient: bool) -> (f64, Params) {
+    let chunk_size = data.entries.len().div_ceil(threads);
+
+    let partials: Vec<(f64, Params)> = std::thread::scope(|scope| {
+        let handles: Vec<_> = data
+            .entries
+            .chunks(chunk_size)
+            .map(|entries| {
+                scope.spawn(move || {
+                    let mut loss = 0.0;
+                    let mut gradient = if with_gradient { vec![[0.0; 2]; PARAM_COUNT] } else { Vec::new() };
+
+                    for entry in entries {
+                        let start = entry.coefficients_start as usize;
+                        let coefficients = &data.coefficients[start..start + entry.coefficients_count as usize];
+                        let (mg_weight, eg_weight) = (entry.mg_weight as f64, entry.eg_weight as f64);
+
+                        let mut score = entry.offset as f64;
+                        for &(param, coefficient) in coefficients {
+                            let [mg, eg] = params[param as usize];
+                            score += coefficient as f64 * (mg * mg_weight + eg * eg_weight);
+                        }
+
+                        let result = entry.result as f64;
+                        let prediction = sigmoid(score, k).clamp(1e-12, 1.0 - 1e-12);
+                        loss -= result * prediction.ln() + (1.0 - result) * (1.0 - prediction).ln();
+
+                        if with_gradient {
+                            let score_gradient = (prediction - result) * k * std::f64::consts::LN_10 / 400.0;
+                            for &(param, coefficient) in coefficients {
+                                let g = score_gradient * coefficient as f64;
+                                gradient[param as usize][0] += g * mg_weight;
+                                gradient[param as usize][1] += g * eg_weight;
+                            }
+                        }
+                    }
+
+                    (loss, gradient)
+                })
+            })
+            .collect();
+
+        handles.into_iter().map(|handle| handle.join().unwrap()).collect()
+    });
+
+    let count = data.entries.len() as f64;
+    let mut loss = 0.0;
+    let mut gradient = if with_gradient { vec![[0.0; 2]; PARAM_COUNT] } else { Vec::new() };
+    for (partial_loss, partial_gradient) in partials {
+        loss += partial_loss;
+        for (total, partial) in gradient.iter_mut().zip(partial_gradient) {
+            total[0] += partial[0] / count;
+            total[1] += partial[1] / count;
+        }
+    }
+
+    (loss / count, gradient)
+}
+
+// The K that makes the current evaluation fit the results best, found by golden section search
+// (see: https://en.wikipedia.org/wiki/Golden-section_search)
+fn fit_k(data: &TuneData, params: &Params, threads: usize) -> f64 {
+    let ratio = (5f64.sqrt() - 1.0) / 2.0;
+    let loss = |k: f64| get_loss_and_gradient(data, params, k, threads, false).0;
+    let (mut low, mut high) = K_SEARCH_RANGE;
+
+    for _ in 0..K_SEARCH_ITERATIONS {
+        let a = high - ratio * (high - low);
+        let b = low + ratio * (high - low);
+        if loss(a) < loss(b) {
+            high = b;
+        } else {
+            low = a;
+        }
+    }
+
+    (low + high) / 2.0
+}
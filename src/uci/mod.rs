@@ -1,6 +1,9 @@
 ﻿pub mod uci_loop;
 mod uci_command_uci;
+mod uci_command_bench;
 mod uci_command_perft;
 mod uci_command_position;
 mod uci_command_go;
-mod uci_command_testeval;
\ No newline at end of file
+mod uci_command_testeval;
+mod uci_command_testmate;
+mod uci_command_eval;
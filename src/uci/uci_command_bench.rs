@@ -0,0 +1,69 @@
+﻿use crate::eval::classical_evaluator::ClassicalEvaluator;
+use crate::eval::eval_hash_table::{EvalHashTable, EVAL_HASH_TABLE_SIZE};
+use crate::search::mini_max::mini_max;
+use crate::search::search_thread::SearchThread;
+use crate::search::transposition_table::TranspositionTable;
+use cozy_chess::Board;
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+use std::time::Instant;
+
+// Fixed depth search over a set of positions. The total node count is a signature of the search,
+// changes that should not alter the search (speedups, refactors) must leave it unchanged.
+const BENCH_POSITIONS: [&str; 8] = [
+    "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+    "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+    "r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4",
+    "r2q1rk1/pp2bppp/2n1pn2/3p4/2PP4/2N1PN2/PP2BPPP/R2QK2R w KQ - 0 9",
+    "2r3k1/pp3ppp/4p3/3pP3/3P4/P4N2/1P3PPP/2R3K1 b - - 0 24",
+    "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+    "8/8/4k3/3p4/3P4/4K3/8/8 w - - 0 1",
+    "6k1/5pp1/7p/8/8/1Q5P/5PP1/6K1 w - - 0 1",
+];
+
+pub fn do_uci_command_bench(tokens: &[String]) {
+    let depth = tokens.get(1).and_then(|d| d.parse::<u8>().ok()).unwrap_or(6);
+    let is_playing = Arc::new(AtomicBool::new(true));
+    let mut total_nodes: u64 = 0;
+    let mut eval_hash_hits: u64 = 0;
+    let mut eval_hash_misses: u64 = 0;
+    let start = Instant::now();
+
+    for fen in BENCH_POSITIONS {
+        let board = Board::from_fen(fen, false).unwrap();
+        let mut transposition_table = TranspositionTable::new(1 << 18);
+        let mut thread = SearchThread::new(is_playing.clone(), &[board.hash()], ClassicalEvaluator::default());
+        thread.eval_hash_table = Some(Arc::new(EvalHashTable::new(EVAL_HASH_TABLE_SIZE)));
+
+        for current_depth in 1..=depth {
+            thread.clear_killers();
+            mini_max(
+                &board,
+                &mut thread,
+                &mut transposition_table,
+                current_depth,
+                i32::MIN,
+                i32::MAX,
+                0,
+            );
+        }
+
+        println!("info string bench nodes {} fen {fen}", thread.node_count);
+        total_nodes += thread.node_count;
+        eval_hash_hits += thread.eval_hash_hits;
+        eval_hash_misses += thread.eval_hash_misses;
+    }
+
+    let elapsed = start.elapsed().as_millis();
+    let nps = if elapsed > 0 {
+        total_nodes * 1000 / elapsed as u64
+    } else {
+        0
+    };
+    let eval_hash_probes = (eval_hash_hits + eval_hash_misses).max(1);
+    println!(
+        "info string bench eval hash hits {eval_hash_hits} misses {eval_hash_misses} hit rate {:.1}%",
+        eval_hash_hits as f64 * 100.0 / eval_hash_probes as f64
+    );
+    println!("nodes: {total_nodes}, time: {elapsed}ms, nps: {nps}");
+}
@@ -0,0 +1,48 @@
+﻿use crate::eval::eval::to_side_to_move;
+use crate::eval::eval_trace::get_eval_trace;
+use crate::eval::score::Score;
+use crate::eval::evaluator::{Evaluator, EvaluatorKind};
+use crate::nnue::nnue_evaluator::NnueEvaluator;
+use crate::uci::uci_loop::UciData;
+
+// Prints every term of the handcrafted evaluation of the current position, split by side and by
+// middlegame / endgame, and how they are blended into the final score. All scores are white
+// relative centipawns.
+pub fn do_uci_command_eval(uci_data: &UciData) {
+    let board = &uci_data.board;
+    let trace = get_eval_trace(board, &uci_data.eval_params);
+    let scores = |score: Score| format!("{:>6} {:>6}", score.mg(), score.eg());
+
+    println!("info string eval fen {board}");
+    println!("info string {:>12} | {:>13} | {:>13} | {:>13}", "Term", "White", "Black", "Total");
+    println!("info string {:>12} | {:>6} {:>6} | {:>6} {:>6} | {:>6} {:>6}", "", "MG", "EG", "MG", "EG", "MG", "EG");
+    println!("info string {:-<12}-+-{:-<13}-+-{:-<13}-+-{:-<13}", "", "", "", "");
+    for row in &trace.rows {
+        println!(
+            "info string {:>12} | {} | {} | {}",
+            row.name,
+            scores(row.white),
+            scores(row.black),
+            scores(row.total)
+        );
+    }
+    println!("info string {:-<12}-+-{:-<13}-+-{:-<13}-+-{:-<13}", "", "", "", "");
+    println!("info string {:>12} | {:>13} | {:>13} | {}", "Total", "", "", scores(trace.total));
+
+    println!("info string eval phase {} (0 = opening, 256 = endgame)", trace.phase);
+    println!("info string eval endgame scale factor {}/64", trace.scale_factor);
+    println!("info string eval tapered score cp {}", trace.tapered);
+    if let Some(score) = trace.mate_score {
+        println!("info string eval replaced by the game result with {score}");
+    } else if let Some(score) = trace.endgame_score {
+        println!("info string eval replaced by the specialised endgame evaluator with cp {score}");
+    }
+    println!("info string eval final score cp {}", trace.eval);
+
+    if let Some(network) = &uci_data.network {
+        let score = NnueEvaluator::new(network.clone(), board).evaluate(board, 0);
+        let score = to_side_to_move(score, board);
+        let used = if uci_data.evaluator == EvaluatorKind::Nnue { " (used by the search)" } else { "" };
+        println!("info string eval nnue score cp {score}{used}");
+    }
+}
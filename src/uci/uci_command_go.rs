@@ -1,8 +1,14 @@
-﻿use std::collections::HashMap;
+﻿use crate::eval::classical_evaluator::ClassicalEvaluator;
+use crate::eval::eval_is_mate::{is_mate_score, mate_in_moves};
+use crate::eval::evaluator::{Evaluator, EvaluatorKind};
+use crate::eval::material_evaluator::MaterialEvaluator;
+use crate::nnue::nnue_evaluator::NnueEvaluator;
 use crate::search::mini_max::mini_max;
+use crate::search::search_thread::SearchThread;
 use crate::search::transposition_table::TranspositionTable;
 use crate::uci::uci_loop::UciData;
-use cozy_chess::{Color, Move};
+use crate::wdl::wdl_model::{get_wdl, normalize_score};
+use cozy_chess::{Board, Color, Move};
 use std::sync::atomic::AtomicBool;
 use std::sync::Arc;
 use std::time::Instant;
@@ -17,9 +23,28 @@ pub fn set_go_timer(is_playing: &Arc<AtomicBool>, time: u64) {
     });
 }
 
-pub fn do_uci_command_go(uci_data: &mut UciData, tokens: &Vec<String>, transposition_table: &mut TranspositionTable) {
+// UCI scores are reported from the point of view of the side to move.
+pub fn get_uci_score_string(score: i32, side_to_move: Color) -> String {
+    let score = if side_to_move == Color::White { score } else { -score };
+
+    if is_mate_score(score) {
+        "mate ".to_string() + &*mate_in_moves(score).to_string()
+    } else {
+        "cp ".to_string() + &*score.to_string()
+    }
+}
+
+// The "wdl W D L" part of an info line, in permille from the point of view of the side to move.
+pub fn get_uci_wdl_string(score: i32, board: &Board) -> String {
+    let score = if board.side_to_move() == Color::White { score } else { -score };
+    let [win, draw, loss] = get_wdl(score, board);
+
+    format!("wdl {win} {draw} {loss}")
+}
+
+pub fn do_uci_command_go(uci_data: &mut UciData, tokens: &[String], transposition_table: &mut TranspositionTable) {
     let mut max_depth = 64;
-    let mut time = 0;
+    let time;
 
     let mut white_time: u64 = 3 * 60 * 1000;
     let mut black_time: u64 = 3 * 60 * 1000;
@@ -60,13 +85,6 @@ pub fn do_uci_command_go(uci_data: &mut UciData, tokens: &Vec<String>, transposi
         };
     }
 
-    let mut current_depth = 1;
-    let mut best_move: Option<Move> = None;
-    let mut best_score: Option<i32> = None;
-    let mut best_pv: Option<Vec<Move>> = None;
-    let mut best_pv_string = String::new();
-    let timer_handle: Option<std::thread::JoinHandle<()>> = None;
-
     if time > 0 {
         set_go_timer(&uci_data.is_playing, time);
     } else {
@@ -75,7 +93,53 @@ pub fn do_uci_command_go(uci_data: &mut UciData, tokens: &Vec<String>, transposi
             .store(true, std::sync::atomic::Ordering::SeqCst);
     }
 
-    let mut node_count = 0;
+    // The search is compiled once for every evaluator, the option picks which one runs.
+    match (uci_data.evaluator, uci_data.network.clone()) {
+        (EvaluatorKind::Nnue, Some(network)) => {
+            let evaluator = NnueEvaluator::new(network, &uci_data.board);
+            search_position(uci_data, transposition_table, max_depth, time, evaluator);
+        }
+        (EvaluatorKind::Material, _) => {
+            let evaluator = MaterialEvaluator::new(uci_data.eval_params.clone());
+            search_position(uci_data, transposition_table, max_depth, time, evaluator);
+        }
+        (kind, _) => {
+            if kind == EvaluatorKind::Nnue {
+                println!("info string no network loaded from EvalFile, falling back to the classical eval");
+            }
+            let evaluator = ClassicalEvaluator::new(uci_data.eval_params.clone());
+            search_position(uci_data, transposition_table, max_depth, time, evaluator);
+        }
+    }
+}
+
+// Iterative deepening on the current position until `max_depth` or the time runs out, printing
+// the info lines and the best move.
+fn search_position<E: Evaluator>(
+    uci_data: &UciData,
+    transposition_table: &mut TranspositionTable,
+    max_depth: u8,
+    time: u64,
+    evaluator: E,
+) {
+    let mut current_depth = 1;
+    let mut best_move: Option<Move> = None;
+    let mut best_score: Option<i32> = None;
+    let mut best_pv_string = String::new();
+    let timer_handle: Option<std::thread::JoinHandle<()>> = None;
+
+    println!("info string evaluator {}", evaluator.name());
+    let mut thread = SearchThread::new(uci_data.is_playing.clone(), &uci_data.current_move_history, evaluator);
+    let contempt = if uci_data.analyse_mode {
+        uci_data.analysis_contempt
+    } else {
+        uci_data.contempt
+    };
+    thread.set_contempt(contempt, uci_data.board.side_to_move(), uci_data.contempt_scale_by_phase);
+    thread.qsearch_checks = uci_data.qsearch_checks;
+    thread.lazy_eval = uci_data.lazy_eval;
+    thread.tablebase = uci_data.tablebase.clone();
+    thread.eval_hash_table = Some(uci_data.eval_hash_table.clone());
 
     let start = Instant::now();
 
@@ -93,19 +157,15 @@ pub fn do_uci_command_go(uci_data: &mut UciData, tokens: &Vec<String>, transposi
 
         let depth_start = Instant::now();
 
-        let mut killer_moves: HashMap<u8, Vec<Move>> = HashMap::new();
-        let new_board = uci_data.board.clone();
-        let (score, mv, early_stop, pv) = mini_max(
-            &new_board,
+        thread.clear_killers();
+        let (score, mv, early_stop) = mini_max(
+            &uci_data.board,
+            &mut thread,
             &mut *transposition_table,
-            uci_data.current_move_history.clone(),
             current_depth,
             i32::MIN,
             i32::MAX,
             0,
-            &uci_data.is_playing,
-            &mut node_count,
-            &mut killer_moves
         );
 
         if early_stop {
@@ -115,17 +175,14 @@ pub fn do_uci_command_go(uci_data: &mut UciData, tokens: &Vec<String>, transposi
         if let Some(mv) = mv {
             best_move = Some(mv);
             best_score = Some(score);
-            best_pv = Some(pv);
 
             // Build the best pv string
             best_pv_string = String::new();
             let mut new_board = uci_data.board.clone();
 
-            if let Some(pv) = &best_pv {
-                for mv in pv {
-                    best_pv_string.push_str(&format!(" {}", cozy_chess::util::display_uci_move(&new_board, *mv)));
-                    new_board.play(*mv);
-                }
+            for mv in thread.pv_table.line(0) {
+                best_pv_string.push_str(&format!(" {}", cozy_chess::util::display_uci_move(&new_board, *mv)));
+                new_board.play(*mv);
             }
             best_pv_string = best_pv_string.trim().to_string();
         }
@@ -133,31 +190,29 @@ pub fn do_uci_command_go(uci_data: &mut UciData, tokens: &Vec<String>, transposi
         let elapsed_ms = start.elapsed().as_millis();
         last_time = depth_start.elapsed().as_millis() as u64;
         let nodes_per_s = if elapsed_ms > 0 {
-            (node_count as f64 / elapsed_ms as f64) as u64 * 1000
+            (thread.node_count as f64 / elapsed_ms as f64) as u64 * 1000
         } else {
             0
         };
 
-        let mut score_string: String = String::new();
-
-        if best_score.unwrap().abs() < 900_000 {
-            score_string = "cp ".to_string() + &*best_score.unwrap().to_string();
+        let score = best_score.unwrap();
+        let reported_score = if uci_data.normalize_score {
+            normalize_score(score, &uci_data.board)
         } else {
-            let mate_depth = if best_score.unwrap() > 0 {
-                ((1_000_000 - best_score.unwrap()) / 1000) / 2
-            } else {
-                ((best_score.unwrap() + 1_000_000) / 1000) / 2
-            };
-
-            score_string = "mate ".to_string() + &*mate_depth.to_string();
+            score
+        };
+        let mut score_string = get_uci_score_string(reported_score, uci_data.board.side_to_move());
+        if uci_data.show_wdl {
+            score_string = format!("{score_string} {}", get_uci_wdl_string(score, &uci_data.board));
         }
 
         println!(
-            "info depth {} score {} nodes {} nps {} time {} bestmove {} pv {}",
+            "info depth {} score {} nodes {} nps {} tbhits {} time {} bestmove {} pv {}",
             current_depth,
             score_string,
-            node_count,
+            thread.node_count,
             nodes_per_s,
+            thread.tb_hits,
             elapsed_ms,
             cozy_chess::util::display_uci_move(&uci_data.board, best_move.unwrap()),
             best_pv_string
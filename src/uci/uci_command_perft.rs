@@ -2,7 +2,7 @@
 use crate::perft::uci_perft;
 use crate::uci::uci_loop::UciData;
 
-pub fn do_uci_command_perft(uci_data: &UciData, tokens: &Vec<String>) {
+pub fn do_uci_command_perft(uci_data: &UciData, tokens: &[String]) {
     let depth = tokens[1].parse::<u8>().unwrap_or(5);
     let start = Instant::now();
     let nodes = uci_perft(&uci_data.board, depth);
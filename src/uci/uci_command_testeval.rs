@@ -1,8 +1,12 @@
 ﻿use crate::eval::eval::eval;
+use crate::eval::material_hash_table::{MaterialHashTable, MATERIAL_HASH_TABLE_SIZE};
+use crate::eval::pawn_hash_table::{PawnHashTable, PAWN_HASH_TABLE_SIZE};
 use crate::uci::uci_loop::UciData;
 
 pub fn do_uci_command_testeval(uci_data: &UciData){
-    let score = eval(&uci_data.board, 0);
+    let mut pawn_hash_table = PawnHashTable::new(PAWN_HASH_TABLE_SIZE);
+    let mut material_hash_table = MaterialHashTable::new(MATERIAL_HASH_TABLE_SIZE);
+    let score = eval(&uci_data.board, &uci_data.eval_params, &mut pawn_hash_table, &mut material_hash_table, 0);
     let fen = uci_data.board.to_string();
 
     println!("info score cp {score} position fen {fen}");
@@ -0,0 +1,85 @@
+﻿use crate::eval::classical_evaluator::ClassicalEvaluator;
+use crate::eval::eval_params::EvalParams;
+use crate::eval::evaluator::{Evaluator, EvaluatorKind};
+use crate::eval::material_evaluator::MaterialEvaluator;
+use crate::search::mini_max::mini_max;
+use crate::search::search_thread::SearchThread;
+use crate::search::transposition_table::TranspositionTable;
+use crate::uci::uci_command_go::get_uci_score_string;
+use cozy_chess::Board;
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+
+// Regression suite of known mate in N positions, scores are from the side to move's point of view.
+const MATE_PUZZLES: [(&str, i32); 9] = [
+    ("6k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - 0 1", 1),
+    ("3r2k1/5ppp/8/8/8/8/5PPP/6K1 b - - 0 1", 1),
+    ("r1bqkb1r/pppp1ppp/2n2n2/4p2Q/2B1P3/8/PPPP1PPP/RNB1K1NR w KQkq - 4 4", 1),
+    ("7k/R7/8/8/8/8/8/1R4K1 b - - 0 1", -1),
+    ("7k/8/8/8/8/8/R7/1R4K1 w - - 0 1", 2),
+    ("1k6/8/2K5/8/8/8/8/7R w - - 0 1", 2),
+    ("k7/8/8/2K5/8/8/8/6Q1 w - - 0 1", 2),
+    ("r1b1kb1r/pppp1ppp/5q2/4n3/3KP3/2N3PN/PPP4P/R1BQ1B1R b kq - 0 1", 3),
+    ("8/8/6k1/8/8/8/8/RR4K1 w - - 0 1", 4),
+];
+
+pub fn do_uci_command_testmate() {
+    let mut passed = 0;
+    let mut total = 0;
+
+    // Mates are found by the search, they have to come out the same with any evaluator.
+    for kind in [EvaluatorKind::Classical, EvaluatorKind::Material] {
+        for (fen, expected_mate) in MATE_PUZZLES {
+            let board = Board::from_fen(fen, false).unwrap();
+            let score_string = match kind {
+                EvaluatorKind::Material => {
+                    let evaluator = MaterialEvaluator::new(Arc::new(EvalParams::default()));
+                    search_mate(&board, expected_mate, evaluator)
+                }
+                _ => search_mate(&board, expected_mate, ClassicalEvaluator::default()),
+            };
+
+            let expected_string = format!("mate {expected_mate}");
+            let result = if score_string == expected_string {
+                passed += 1;
+                "ok"
+            } else {
+                "FAILED"
+            };
+            total += 1;
+
+            println!(
+                "info string testmate {result} evaluator {} expected {expected_string} got {score_string} fen {fen}",
+                kind.name()
+            );
+        }
+    }
+
+    println!("info string testmate passed {passed}/{total}");
+}
+
+// The UCI score string of the search after the last iteration.
+fn search_mate<E: Evaluator>(board: &Board, expected_mate: i32, evaluator: E) -> String {
+    let is_playing = Arc::new(AtomicBool::new(true));
+    let mut transposition_table = TranspositionTable::new(1 << 16);
+    let mut thread = SearchThread::new(is_playing, &[board.hash()], evaluator);
+    let mut score_string = String::new();
+
+    // Search a couple of plies deeper than the mate so reduced moves still see it.
+    let max_depth = expected_mate.unsigned_abs() as u8 * 2 + 2;
+    for depth in 1..=max_depth {
+        thread.clear_killers();
+        let (score, _, _) = mini_max(
+            board,
+            &mut thread,
+            &mut transposition_table,
+            depth,
+            i32::MIN,
+            i32::MAX,
+            0,
+        );
+        score_string = get_uci_score_string(score, board.side_to_move());
+    }
+
+    score_string
+}
@@ -1,7 +1,27 @@
-﻿pub fn do_uci_command_uci() {
+﻿use crate::eval::evaluator::EvaluatorKind;
+
+pub fn do_uci_command_uci() {
     println!("id name Chessosity");
     println!("id author Piper Mania Deluxe");
     println!();
     println!("option name hash_size type spin default 16777216 min 1 max 67108864");
+    println!("option name EvalHashSize type spin default 262144 min 1 max 16777216");
+    println!("option name Contempt type spin default 0 min -200 max 200");
+    println!("option name ContemptPhaseScaling type check default false");
+    println!("option name UCI_AnalyseMode type check default false");
+    println!("option name AnalysisContempt type spin default 0 min -200 max 200");
+    println!("option name QSearchChecks type check default false");
+    println!("option name TablebasePath type string default <empty>");
+    println!("option name EvalFile type string default <empty>");
+    let evaluators: String = EvaluatorKind::ALL.iter().map(|kind| format!(" var {}", kind.name())).collect();
+    println!(
+        "option name Evaluator type combo default {}{evaluators}",
+        EvaluatorKind::Classical.name()
+    );
+    println!("option name UseNNUE type check default false");
+    println!("option name EvalParamsFile type string default <empty>");
+    println!("option name LazyEval type check default false");
+    println!("option name UCI_ShowWDL type check default false");
+    println!("option name NormalizeScore type check default false");
     println!("uciok");
 }
\ No newline at end of file
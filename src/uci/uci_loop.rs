@@ -1,11 +1,19 @@
 ﻿use std::sync::{Arc, Mutex, Condvar};
-use std::sync::atomic::AtomicBool;
+use std::sync::atomic::{AtomicBool, Ordering};
 use cozy_chess::Board;
+use crate::eval::eval_hash_table::{EvalHashTable, EVAL_HASH_TABLE_SIZE};
+use crate::eval::eval_params::EvalParams;
+use crate::eval::evaluator::EvaluatorKind;
 use crate::search::transposition_table::TranspositionTable;
+use crate::nnue::network::Network;
+use crate::tablebase::tablebase::Tablebase;
+use crate::uci::uci_command_bench::do_uci_command_bench;
+use crate::uci::uci_command_eval::do_uci_command_eval;
 use crate::uci::uci_command_go::do_uci_command_go;
 use crate::uci::uci_command_perft::do_uci_command_perft;
 use crate::uci::uci_command_position::do_uci_command_position;
 use crate::uci::uci_command_testeval::do_uci_command_testeval;
+use crate::uci::uci_command_testmate::do_uci_command_testmate;
 use crate::uci::uci_command_uci::do_uci_command_uci;
 
 #[derive(Clone)]
@@ -15,16 +23,42 @@ pub struct UciData {
     pub is_playing: Arc<AtomicBool>,
 
     // UCI OPTIONS
-    pub hash_size: u64 // Max amount of TT entries to store, higher = faster search but more RAM usage
+    pub hash_size: u64, // Max amount of TT entries to store, higher = faster search but more RAM usage
+    pub eval_hash_table: Arc<EvalHashTable>, // Static evals kept between searches, EvalHashSize entries
+    pub contempt: i32, // Centipawns a draw is worth less than equality to the engine, higher = avoids draws more
+    pub contempt_scale_by_phase: bool, // Fade contempt out as the game goes into the endgame
+    pub analyse_mode: bool, // Set by GUIs when analysing, analysis_contempt is used instead of contempt
+    pub analysis_contempt: i32,
+    pub qsearch_checks: bool, // Search quiet checks at the first quiescence ply, slower but sees more tactics
+    pub tablebase: Option<Arc<Tablebase>>, // Tables found in TablebasePath, shared with every search
+    pub network: Option<Arc<Network>>, // Network loaded from EvalFile
+    pub evaluator: EvaluatorKind, // What the search evaluates with, NNUE needs a network from EvalFile
+    pub lazy_eval: bool, // Skip the expensive eval terms in the quiescence search when material alone decides a node
+    pub eval_params: Arc<EvalParams>, // Weights of the handcrafted eval, from EvalParamsFile and the hidden parameter options
+    pub show_wdl: bool, // Add the win, draw and loss chances of the WDL model to the info lines
+    pub normalize_score: bool, // Report centipawns scaled so that 100 means a 50% chance to win
 }
 
 impl UciData {
-    pub fn new() -> Self {
+    pub fn new(eval_params: Arc<EvalParams>) -> Self {
         UciData {
             board: Board::default(),
             current_move_history: vec![Board::default().hash()],
             is_playing: Arc::new(AtomicBool::new(false)),
-            hash_size: 1048576
+            hash_size: 1048576,
+            eval_hash_table: Arc::new(EvalHashTable::new(EVAL_HASH_TABLE_SIZE)),
+            contempt: 0,
+            contempt_scale_by_phase: false,
+            analyse_mode: false,
+            analysis_contempt: 0,
+            qsearch_checks: false,
+            tablebase: None,
+            network: None,
+            evaluator: EvaluatorKind::Classical,
+            lazy_eval: false,
+            eval_params,
+            show_wdl: false,
+            normalize_score: false,
         }
     }
 }
@@ -33,6 +67,7 @@ impl UciData {
 struct SharedTT {
     table: Mutex<Option<TranspositionTable>>,
     condvar: Condvar,
+    clear_pending: AtomicBool,
 }
 
 impl SharedTT {
@@ -40,12 +75,21 @@ impl SharedTT {
         SharedTT {
             table: Mutex::new(None),
             condvar: Condvar::new(),
+            clear_pending: AtomicBool::new(false),
         }
     }
+
+    // For when the evaluation changes and the stored scores no longer match it. A running search
+    // holds the table for as long as it runs, so the next go clears it instead of waiting here.
+    fn request_clear(&self) {
+        self.clear_pending.store(true, Ordering::SeqCst);
+    }
 }
 
-pub fn do_uci_loop() {
-    let mut uci_data = UciData::new();
+// `eval_params` are the weights the engine starts with, EvalParamsFile replaces them.
+pub fn do_uci_loop(eval_params: Arc<EvalParams>) {
+    let mut uci_data = UciData::new(eval_params.clone());
+    let startup_eval_params = eval_params;
     let mut go_thread: Option<std::thread::JoinHandle<()>> = None;
 
     // Create a shared transposition table wrapper that starts out empty.
@@ -95,6 +139,9 @@ pub fn do_uci_loop() {
                 }
                 println!("readyok");
             }
+            "bench" => {
+                do_uci_command_bench(&tokens);
+            }
             "perft" => {
                 do_uci_command_perft(&uci_data, &tokens);
             }
@@ -113,6 +160,9 @@ pub fn do_uci_loop() {
                     }
                     // Unwrap safely because the condition variable guarantees initialization.
                     let tt = lock.as_mut().unwrap();
+                    if shared_tt_clone.clear_pending.swap(false, Ordering::SeqCst) {
+                        tt.clear();
+                    }
                     do_uci_command_go(&mut new_uci_data, &tokens, tt);
                 }));
             }
@@ -127,6 +177,12 @@ pub fn do_uci_loop() {
             "testeval" => {
                 do_uci_command_testeval(&uci_data);
             }
+            "eval" => {
+                do_uci_command_eval(&uci_data);
+            }
+            "testmate" => {
+                do_uci_command_testmate();
+            }
             "ucinewgame" => {
                 // Spawn a thread that initializes the transposition table.
                 {
@@ -152,6 +208,7 @@ pub fn do_uci_loop() {
 
                 uci_data.board = Board::default();
                 uci_data.current_move_history = vec![uci_data.board.hash()];
+                uci_data.eval_hash_table.clear();
 
                 println!("readyok");
             }
@@ -182,8 +239,122 @@ pub fn do_uci_loop() {
                                 });
                             }
                         }
-                        _ => {}
+                        "EvalHashSize" => {
+                            let size = tokens[4].parse::<usize>().unwrap();
+                            uci_data.eval_hash_table = Arc::new(EvalHashTable::new(size));
+                        }
+                        "Contempt" => {
+                            uci_data.contempt = tokens[4].parse::<i32>().unwrap();
+                        }
+                        "ContemptPhaseScaling" => {
+                            uci_data.contempt_scale_by_phase = tokens[4].parse::<bool>().unwrap();
+                        }
+                        "UCI_AnalyseMode" => {
+                            uci_data.analyse_mode = tokens[4].parse::<bool>().unwrap();
+                        }
+                        "AnalysisContempt" => {
+                            uci_data.analysis_contempt = tokens[4].parse::<i32>().unwrap();
+                        }
+                        "QSearchChecks" => {
+                            uci_data.qsearch_checks = tokens[4].parse::<bool>().unwrap();
+                        }
+                        "TablebasePath" => {
+                            // Paths may contain spaces, so the value is everything after "value".
+                            let path = tokens[4..].join(" ");
+                            uci_data.tablebase = None;
+
+                            if path != "<empty>" {
+                                match Tablebase::load(&path) {
+                                    Ok(tablebase) => {
+                                        println!(
+                                            "info string loaded {} tablebases with up to {} pieces",
+                                            tablebase.table_count(),
+                                            tablebase.max_pieces()
+                                        );
+                                        uci_data.tablebase = Some(Arc::new(tablebase));
+                                    }
+                                    Err(error) => println!("info string failed to load tablebases: {error}"),
+                                }
+                            }
+                        }
+                        "EvalFile" => {
+                            let path = tokens[4..].join(" ");
+                            uci_data.network = None;
+
+                            if path != "<empty>" {
+                                match Network::load(&path) {
+                                    Ok(network) => {
+                                        println!(
+                                            "info string loaded network with {} king buckets and {} hidden neurons",
+                                            network.bucket_count,
+                                            network.hidden_size
+                                        );
+                                        uci_data.network = Some(Arc::new(network));
+                                    }
+                                    Err(error) => println!("info string failed to load network: {error}"),
+                                }
+                            }
+                            shared_tt.request_clear();
+                        }
+                        "Evaluator" | "UseNNUE" => {
+                            let kind = if option_name == "UseNNUE" {
+                                // Switches between the NNUE and the classical evaluator, Evaluator picks any of them.
+                                if tokens[4].parse::<bool>().unwrap() {
+                                    Some(EvaluatorKind::Nnue)
+                                } else {
+                                    Some(EvaluatorKind::Classical)
+                                }
+                            } else {
+                                EvaluatorKind::from_name(&tokens[4])
+                            };
+
+                            match kind {
+                                Some(kind) if kind != uci_data.evaluator => {
+                                    shared_tt.request_clear();
+                                    uci_data.evaluator = kind;
+                                }
+                                Some(_) => {}
+                                None => println!("info string unknown evaluator {}", tokens[4]),
+                            }
+                        }
+                        "LazyEval" => {
+                            uci_data.lazy_eval = tokens[4].parse::<bool>().unwrap();
+                        }
+                        "UCI_ShowWDL" => {
+                            uci_data.show_wdl = tokens[4].parse::<bool>().unwrap();
+                        }
+                        "NormalizeScore" => {
+                            uci_data.normalize_score = tokens[4].parse::<bool>().unwrap();
+                        }
+                        "EvalParamsFile" => {
+                            let path = tokens[4..].join(" ");
+
+                            if path == "<empty>" {
+                                uci_data.eval_params = startup_eval_params.clone();
+                            } else {
+                                match EvalParams::load(&path) {
+                                    Ok(eval_params) => {
+                                        println!("info string loaded eval parameters from {path}");
+                                        uci_data.eval_params = Arc::new(eval_params);
+                                    }
+                                    Err(error) => println!("info string failed to load eval parameters: {error}"),
+                                }
+                            }
+                            shared_tt.request_clear();
+                        }
+                        // Every single eval parameter is a hidden option for SPSA tuning, see
+                        // EvalParams::set_param for the names.
+                        name => {
+                            if let Ok(value) = tokens[4].parse::<i32>() {
+                                if Arc::make_mut(&mut uci_data.eval_params).set_param(name, value) {
+                                    shared_tt.request_clear();
+                                }
+                            }
+                        }
                     }
+
+                    // Most options change what the eval returns, evals cached before would be wrong.
+                    uci_data.eval_hash_table.clear();
                 }
             }
             "quit" => std::process::exit(0),
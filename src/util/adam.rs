@@ -0,0 +1,40 @@
+﻿const ADAM_BETA1: f64 = 0.9;
+const ADAM_BETA2: f64 = 0.999;
+const ADAM_EPSILON: f64 = 1e-8;
+
+// Adam (see: https://arxiv.org/abs/1412.6980), keeps the running first and second moment of the
+// gradient of every parameter.
+pub struct Adam {
+    learning_rate: f64,
+    momentum: Vec<f64>,
+    velocity: Vec<f64>,
+    steps: i32,
+}
+
+impl Adam {
+    pub fn new(param_count: usize, learning_rate: f64) -> Self {
+        Adam {
+            learning_rate,
+            momentum: vec![0.0; param_count],
+            velocity: vec![0.0; param_count],
+            steps: 0,
+        }
+    }
+
+    // Moves every parameter against its gradient. A parameter whose gradient is always 0 stays
+    // where it is.
+    pub fn step(&mut self, params: &mut [f64], gradient: &[f64]) {
+        self.steps += 1;
+        let momentum_correction = 1.0 - ADAM_BETA1.powi(self.steps);
+        let velocity_correction = 1.0 - ADAM_BETA2.powi(self.steps);
+
+        for (i, (param, &g)) in params.iter_mut().zip(gradient).enumerate() {
+            self.momentum[i] = ADAM_BETA1 * self.momentum[i] + (1.0 - ADAM_BETA1) * g;
+            self.velocity[i] = ADAM_BETA2 * self.velocity[i] + (1.0 - ADAM_BETA2) * g * g;
+
+            let m = self.momentum[i] / momentum_correction;
+            let v = self.velocity[i] / velocity_correction;
+            *param -= self.learning_rate * m / (v.sqrt() + ADAM_EPSILON);
+        }
+    }
+}
@@ -0,0 +1,3 @@
+﻿pub mod random;
+pub mod adam;
+pub mod options;
@@ -0,0 +1,20 @@
+﻿// Goes through command line options written as `name value` pairs. `set` gets every pair and
+// returns false for a name it doesn't know, or an error for a value that doesn't parse.
+pub fn parse_option_pairs(
+    args: &[String],
+    mut set: impl FnMut(&str, &str) -> Result<bool, ()>,
+) -> Result<(), String> {
+    for pair in args.chunks(2) {
+        let [name, value] = pair else {
+            return Err(format!("missing value for {}", pair[0]));
+        };
+
+        match set(name, value) {
+            Ok(true) => {}
+            Ok(false) => return Err(format!("unknown option {name}")),
+            Err(()) => return Err(format!("invalid value {value} for {name}")),
+        }
+    }
+
+    Ok(())
+}
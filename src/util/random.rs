@@ -0,0 +1,10 @@
+﻿// One step of xorshift64 (see: https://en.wikipedia.org/wiki/Xorshift), returns the next state
+// which is also the random number. Fast and deterministic, for hash keys, random games and test
+// data. The state must not be 0, it would stay 0.
+pub const fn xorshift64(state: u64) -> u64 {
+    let mut state = state;
+    state ^= state << 13;
+    state ^= state >> 7;
+    state ^= state << 17;
+    state
+}
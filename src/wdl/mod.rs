@@ -0,0 +1,2 @@
+﻿pub mod wdl_fit;
+pub mod wdl_model;
\ No newline at end of file
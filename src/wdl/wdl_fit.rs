@@ -0,0 +1,158 @@
+﻿use crate::eval::eval_is_mate::is_mate_score;
+use crate::util::adam::Adam;
+use crate::util::options::parse_option_pairs;
+use crate::wdl::wdl_model::{get_wdl_material, get_wdl_params, get_win_rate, WDL_AS, WDL_BS, WDL_MATERIAL_NORMAL};
+use cozy_chess::Board;
+use std::fs;
+use std::io;
+use std::time::Instant;
+
+const REPORT_INTERVAL: usize = 100;
+const MIN_PROBABILITY: f64 = 1e-12;
+
+pub struct FitWdlOptions {
+    pub data_path: String,
+    pub epochs: usize,
+    pub learning_rate: f64,
+}
+
+impl FitWdlOptions {
+    // fit-wdl <data file> [epochs N] [learning-rate X]
+    pub fn from_args(args: &[String]) -> Result<Self, String> {
+        let mut options = FitWdlOptions {
+            data_path: args.first().cloned().ok_or("missing data file")?,
+            epochs: 10000,
+            learning_rate: 2.0,
+        };
+
+        parse_option_pairs(&args[1..], |name, value| {
+            match name {
+                "epochs" => options.epochs = value.parse().map_err(|_| ())?,
+                "learning-rate" => options.learning_rate = value.parse().map_err(|_| ())?,
+                _ => return Ok(false),
+            }
+            Ok(true)
+        })?;
+
+        Ok(options)
+    }
+}
+
+struct WdlEntry {
+    score: f64, // White relative
+    material: i32,
+    result: usize, // Index into [win, draw, loss] from white's point of view
+}
+
+// Fits the coefficients of the WDL model to the search scores and game results of datagen text
+// output by maximum likelihood, starting from the current coefficients, and prints them in the
+// form wdl_model.rs wants them.
+pub fn run_fit_wdl(options: &FitWdlOptions) -> io::Result<()> {
+    let start = Instant::now();
+    let entries = load_entries(&options.data_path)?;
+    println!("info string fit-wdl loaded {} positions in {}ms", entries.len(), start.elapsed().as_millis());
+    if entries.is_empty() {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "no usable positions"));
+    }
+
+    let mut coefficients = [WDL_AS, WDL_BS];
+    println!("info string fit-wdl start loss {:.6}", get_loss_and_gradient(&entries, &coefficients).0);
+
+    let mut adam = Adam::new(8, options.learning_rate);
+
+    for epoch in 1..=options.epochs {
+        let (loss, gradient) = get_loss_and_gradient(&entries, &coefficients);
+        adam.step(coefficients.as_flattened_mut(), gradient.as_flattened());
+
+        if epoch % REPORT_INTERVAL == 0 || epoch == options.epochs {
+            println!("info string fit-wdl epoch {epoch} loss {loss:.6} time {}ms", start.elapsed().as_millis());
+        }
+    }
+
+    let format_coefficients = |c: &[f64; 4]| format!("[{:.3}, {:.3}, {:.3}, {:.3}]", c[0], c[1], c[2], c[3]);
+    println!("pub const WDL_AS: [f64; 4] = {};", format_coefficients(&coefficients[0]));
+    println!("pub const WDL_BS: [f64; 4] = {};", format_coefficients(&coefficients[1]));
+
+    // The a and b at a few material counts make it easier to see whether the fit is sensible.
+    for material in [17, 30, 45, 58, 78] {
+        let (a, b) = get_wdl_params(material, &coefficients[0], &coefficients[1]);
+        println!("info string fit-wdl material {material} a {a:.1} b {b:.1}");
+    }
+
+    Ok(())
+}
+
+// Reads the "<fen> | <score> | <result>" lines datagen writes, positions with mate scores are
+// skipped as the model doesn't cover them.
+fn load_entries(path: &str) -> io::Result<Vec<WdlEntry>> {
+    let mut entries = Vec::new();
+
+    for line in fs::read_to_string(path)?.lines() {
+        let mut parts = line.split('|').map(str::trim);
+        let (Some(fen), Some(score), Some(result)) = (parts.next(), parts.next(), parts.next()) else {
+            continue;
+        };
+        let (Ok(board), Ok(score)) = (Board::from_fen(fen, false), score.parse::<i32>()) else {
+            continue;
+        };
+        let result = match result {
+            "1.0" | "1-0" => 0,
+            "0.5" | "1/2-1/2" => 1,
+            "0.0" | "0-1" => 2,
+            _ => continue,
+        };
+        if is_mate_score(score) {
+            continue;
+        }
+
+        entries.push(WdlEntry {
+            score: score as f64,
+            material: get_wdl_material(&board),
+            result,
+        });
+    }
+
+    Ok(entries)
+}
+
+// Mean negative log likelihood of the results and its gradient for the coefficients of a and b.
+fn get_loss_and_gradient(entries: &[WdlEntry], coefficients: &[[f64; 4]; 2]) -> (f64, [[f64; 4]; 2]) {
+    let mut loss = 0.0;
+    let mut gradient = [[0.0; 4]; 2];
+
+    for entry in entries {
+        let (a, b) = get_wdl_params(entry.material, &coefficients[0], &coefficients[1]);
+        let win = get_win_rate(entry.score, a, b);
+        let loss_rate = get_win_rate(-entry.score, a, b);
+
+        // Derivatives of the win and loss rates for a and b, the draw rate is what is left.
+        let win_da = -win * (1.0 - win) / b;
+        let win_db = -win * (1.0 - win) * (entry.score - a) / (b * b);
+        let loss_da = -loss_rate * (1.0 - loss_rate) / b;
+        let loss_db = -loss_rate * (1.0 - loss_rate) * (-entry.score - a) / (b * b);
+
+        let (probability, da, db) = match entry.result {
+            0 => (win, win_da, win_db),
+            1 => (1.0 - win - loss_rate, -win_da - loss_da, -win_db - loss_db),
+            _ => (loss_rate, loss_da, loss_db),
+        };
+        let probability = probability.max(MIN_PROBABILITY);
+        loss -= probability.ln();
+
+        let x = entry.material as f64 / WDL_MATERIAL_NORMAL;
+        let powers = [x * x * x, x * x, x, 1.0];
+        for i in 0..4 {
+            gradient[0][i] -= da / probability * powers[i];
+            gradient[1][i] -= db / probability * powers[i];
+        }
+    }
+
+    let count = entries.len() as f64;
+    for polynomial in gradient.iter_mut() {
+        for g in polynomial.iter_mut() {
+            *g /= count;
+        }
+    }
+
+    (loss / count, gradient)
+}
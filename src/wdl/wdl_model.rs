@@ -0,0 +1,65 @@
+﻿use crate::eval::eval_is_mate::is_mate_score;
+use cozy_chess::{Board, Piece};
+
+// Win, draw and loss probabilities from a score, using the same model as Stockfish: the win rate
+// is a logistic function of the score whose midpoint a and width b depend on the material left.
+//   win rate = 1 / (1 + exp((a - score) / b))
+//   loss rate = win rate of -score
+// a and b are cubic polynomials in material / 58, with the coefficients below fitted by fit-wdl on
+// datagen games. fit-wdl starts from these, so running it on new games refines them. Material is
+// used rather than the game ply because it is known at every node without the game history and
+// says more about how drawish a position is.
+// (see: https://github.com/official-stockfish/WDL_model)
+pub const WDL_AS: [f64; 4] = [-167.342, 413.613, -509.670, 669.047];
+pub const WDL_BS: [f64; 4] = [19.557, -178.805, 268.328, 176.630];
+
+// The material count is P = 1, N = B = 3, R = 5, Q = 9 for both sides, clamped to this range.
+pub const WDL_MATERIAL_RANGE: (i32, i32) = (17, 78);
+pub const WDL_MATERIAL_NORMAL: f64 = 58.0;
+
+pub fn get_wdl_material(board: &Board) -> i32 {
+    let material = board.pieces(Piece::Pawn).len()
+        + 3 * board.pieces(Piece::Knight).len()
+        + 3 * board.pieces(Piece::Bishop).len()
+        + 5 * board.pieces(Piece::Rook).len()
+        + 9 * board.pieces(Piece::Queen).len();
+
+    (material as i32).clamp(WDL_MATERIAL_RANGE.0, WDL_MATERIAL_RANGE.1)
+}
+
+// The a and b of the model for a material count from get_wdl_material, the coefficients are
+// passed in so fit-wdl can use the same function.
+pub fn get_wdl_params(material: i32, wdl_as: &[f64; 4], wdl_bs: &[f64; 4]) -> (f64, f64) {
+    let x = material as f64 / WDL_MATERIAL_NORMAL;
+    let polynomial = |c: &[f64; 4]| ((c[0] * x + c[1]) * x + c[2]) * x + c[3];
+
+    (polynomial(wdl_as), polynomial(wdl_bs).max(1.0))
+}
+
+pub fn get_win_rate(score: f64, a: f64, b: f64) -> f64 {
+    1.0 / (1.0 + ((a - score) / b).exp())
+}
+
+// Win, draw and loss chances in permille for the side the score is relative to.
+pub fn get_wdl(score: i32, board: &Board) -> [u32; 3] {
+    if is_mate_score(score) {
+        return if score > 0 { [1000, 0, 0] } else { [0, 0, 1000] };
+    }
+
+    let (a, b) = get_wdl_params(get_wdl_material(board), &WDL_AS, &WDL_BS);
+    let win = (1000.0 * get_win_rate(score as f64, a, b)).round() as u32;
+    let loss = (1000.0 * get_win_rate(-score as f64, a, b)).round() as u32;
+
+    [win, 1000 - win - loss, loss]
+}
+
+// Rescales a score so that 100 means a 50% chance to win with the material on the board, the a
+// of the model. Mate scores are left alone.
+pub fn normalize_score(score: i32, board: &Board) -> i32 {
+    if is_mate_score(score) {
+        return score;
+    }
+
+    let (a, _) = get_wdl_params(get_wdl_material(board), &WDL_AS, &WDL_BS);
+    (100.0 * score as f64 / a).round() as i32
+}